@@ -0,0 +1,10 @@
+-- Notices posted by operators to the whole region
+CREATE TABLE notices (
+    id VARCHAR(36) PRIMARY KEY NOT NULL,
+    author_node_id VARCHAR(64) NOT NULL,
+    title VARCHAR(255) NOT NULL,
+    body TEXT NOT NULL,
+    priority VARCHAR(16) NOT NULL,
+    posted_at INTEGER NOT NULL,
+    expires_at INTEGER
+);
@@ -0,0 +1,9 @@
+-- Direct messages addressed to this node, stored decrypted
+CREATE TABLE direct_messages (
+    id VARCHAR(36) PRIMARY KEY NOT NULL,
+    sender_node_id VARCHAR(64) NOT NULL,
+    body TEXT NOT NULL,
+    sent_at INTEGER NOT NULL,
+    received_at INTEGER NOT NULL,
+    read_at INTEGER
+);
@@ -0,0 +1,6 @@
+-- Maps node ids that were retired by a key rotation to the id that replaced them
+CREATE TABLE key_rotations (
+    old_node_id VARCHAR(64) PRIMARY KEY NOT NULL,
+    new_node_id VARCHAR(64) NOT NULL,
+    rotated_at INTEGER NOT NULL
+);
@@ -0,0 +1,3 @@
+-- The node private key, encrypted with a passphrase (JSON encoded EncryptedBlob). When set,
+-- private_key_hex is NULL.
+ALTER TABLE node_configs ADD COLUMN private_key_encrypted TEXT;
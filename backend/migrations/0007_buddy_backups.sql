@@ -0,0 +1,28 @@
+-- Nodes this node pushes its scheduled backups to
+CREATE TABLE backup_buddies (
+    node_id VARCHAR(64) PRIMARY KEY NOT NULL,
+    added_at INTEGER NOT NULL
+);
+
+-- Encrypted backups that other nodes pushed to this node
+CREATE TABLE held_backups (
+    blob_hash VARCHAR(64) PRIMARY KEY NOT NULL,
+    owner_node_id VARCHAR(64) NOT NULL,
+    created_at INTEGER NOT NULL,
+    archive BLOB NOT NULL
+);
+
+-- Backups of a lost node that buddies offered to this node, to restore from
+CREATE TABLE backup_offers (
+    blob_hash VARCHAR(64) PRIMARY KEY NOT NULL,
+    owner_node_id VARCHAR(64) NOT NULL,
+    buddy_node_id VARCHAR(64) NOT NULL,
+    created_at INTEGER NOT NULL,
+    offered_at INTEGER NOT NULL
+);
+
+-- Backups this node pushed to its buddies, the latest one tells when the next one is due
+CREATE TABLE backup_pushes (
+    blob_hash VARCHAR(64) PRIMARY KEY NOT NULL,
+    pushed_at INTEGER NOT NULL
+);
@@ -0,0 +1,23 @@
+-- Samples of the host the node runs on, kept for a day
+CREATE TABLE system_samples (
+    sampled_at INTEGER PRIMARY KEY NOT NULL,
+    cpu_percent REAL,
+    memory_used_percent REAL NOT NULL,
+    disk_used_percent REAL NOT NULL,
+    disk_free_bytes INTEGER NOT NULL,
+    temperature_c REAL,
+    uptime_secs INTEGER NOT NULL,
+    load_1m REAL NOT NULL,
+    -- JSON array of InterfaceStats
+    interfaces TEXT NOT NULL
+);
+
+-- Thresholds the host crossed, open until resolved_at is set
+CREATE TABLE system_alerts (
+    id VARCHAR(36) PRIMARY KEY NOT NULL,
+    kind VARCHAR(32) NOT NULL,
+    value REAL NOT NULL,
+    threshold REAL NOT NULL,
+    raised_at INTEGER NOT NULL,
+    resolved_at INTEGER
+);
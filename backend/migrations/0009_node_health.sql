@@ -0,0 +1,12 @@
+-- The latest health report of every node in the region
+CREATE TABLE node_health (
+    node_id VARCHAR(64) PRIMARY KEY NOT NULL,
+    reported_at INTEGER NOT NULL,
+    version VARCHAR(32) NOT NULL,
+    uptime_secs INTEGER,
+    disk_free_bytes INTEGER,
+    disk_used_percent REAL,
+    load_1m REAL,
+    -- JSON array of alert kinds
+    open_alerts TEXT NOT NULL
+);
@@ -0,0 +1,26 @@
+-- Alerting rules of the region. Deleted rules are kept, with deleted_at set, so that an older
+-- save arriving later doesn't bring them back.
+CREATE TABLE alert_rules (
+    id VARCHAR(36) PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    -- JSON AlertCondition
+    condition TEXT NOT NULL,
+    author_node_id VARCHAR(64) NOT NULL,
+    updated_at INTEGER NOT NULL,
+    deleted_at INTEGER
+);
+
+-- Alerts this node raised by checking the rules
+CREATE TABLE alerts (
+    id VARCHAR(36) PRIMARY KEY NOT NULL,
+    rule_id VARCHAR(36) NOT NULL,
+    node_id VARCHAR(64) NOT NULL,
+    -- open, acknowledged or resolved
+    state VARCHAR(16) NOT NULL,
+    message TEXT NOT NULL,
+    opened_at INTEGER NOT NULL,
+    acknowledged_at INTEGER,
+    resolved_at INTEGER
+);
+
+CREATE INDEX alerts_by_state ON alerts (state);
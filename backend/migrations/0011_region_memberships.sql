@@ -0,0 +1,4 @@
+-- One row per region this node is a member of, the one with id 0 being its first region. A
+-- topic or log left NULL is taken from the settings.
+ALTER TABLE network_configs ADD COLUMN topic_name VARCHAR(255);
+ALTER TABLE network_configs ADD COLUMN log_id VARCHAR(255);
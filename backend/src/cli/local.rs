@@ -0,0 +1,127 @@
+use anyhow::{anyhow, Result};
+use rocket::tokio::sync::mpsc;
+use sqlx::sqlite::SqlitePoolOptions;
+
+use super::{join_details, print_nodes, Command, Invite};
+use crate::infra::backup::{create_backup, restore_backup, BackupContents};
+use crate::infra::db::{migrate, MainDb};
+use crate::infra::settings::Settings;
+use crate::infra::supervisor::Supervisor;
+use crate::panda_comms::container::P2PandaContainer;
+use crate::panda_comms::fairing::load_node_params;
+use crate::panda_comms::identity::key_passphrase_from_env;
+use crate::repos::helpers::NETWORK_CONFIG_ID;
+use crate::repos::nodes::NodesRepo;
+use crate::repos::this_p2panda_node::{SimplifiedNodeAddress, ThisP2PandaNodeRepo};
+
+/// The database of a node that isn't running, with a container holding its params. The container
+/// is never started.
+struct LocalNode {
+    db: MainDb,
+    container: P2PandaContainer,
+}
+
+impl LocalNode {
+    /// Opens (or creates) the database at `database_url` and loads the node from it, like the
+    /// server does on startup.
+    async fn open(database_url: &str) -> Result<Self> {
+        let pool = SqlitePoolOptions::new()
+            .connect(database_url)
+            .await?;
+        migrate(&pool).await?;
+
+        let db = MainDb::from(pool);
+        let (events_tx, _) = mpsc::channel(1);
+        let settings = Settings::default();
+        let container = P2PandaContainer::new(
+            events_tx,
+            None,
+            settings.network_options(),
+            settings.mesh_topic(),
+            Supervisor::default(),
+            NETWORK_CONFIG_ID as i64,
+        );
+        load_node_params(&db, &container, key_passphrase_from_env()?).await;
+
+        Ok(LocalNode { db, container })
+    }
+
+    async fn node_id(&self) -> Result<String> {
+        let params = self.container.get_params().await;
+        let private_key = params
+            .private_key
+            .ok_or(anyhow!("Private key is {:?}, set LORES_KEY_PASSPHRASE", params.key_status))?;
+
+        Ok(private_key.public_key().to_hex())
+    }
+}
+
+pub async fn run(database_url: &str, command: Command) -> Result<()> {
+    let node = LocalNode::open(database_url).await?;
+
+    match command {
+        Command::Serve => unreachable!("serving is handled by main"),
+        Command::Ready => unreachable!("readiness is checked by cli::run"),
+        Command::Init => {
+            println!("Initialized node {}", node.node_id().await?);
+        }
+        Command::Identity => {
+            let params = node.container.get_params().await;
+            println!("Key status: {:?}", params.key_status);
+            if let Some(private_key) = params.private_key {
+                println!("Node id: {}", private_key.public_key().to_hex());
+            }
+            println!("Region: {}", params.network_name.as_deref().unwrap_or("none"));
+        }
+        Command::Invite => {
+            let network_name = node
+                .container
+                .get_params()
+                .await
+                .network_name
+                .ok_or(anyhow!("This node hasn't joined a region"))?;
+
+            let invite = Invite {
+                network_name,
+                bootstrap_node_id: node.node_id().await?,
+            };
+            println!("{}", invite);
+        }
+        Command::Join {
+            network_name,
+            bootstrap_node_id,
+            invite,
+        } => {
+            let (network_name, bootstrap_node_id) = join_details(network_name, bootstrap_node_id, invite)?;
+            let mut connection = node.db.sqlite_pool().acquire().await?;
+
+            ThisP2PandaNodeRepo::init()
+                .set_network_config(
+                    &mut connection,
+                    network_name.clone(),
+                    bootstrap_node_id.map(|node_id| SimplifiedNodeAddress { node_id }),
+                )
+                .await?;
+            println!("Joined region {}, it is used from the next start of the node", network_name);
+        }
+        Command::Nodes => {
+            let mut connection = node.db.sqlite_pool().acquire().await?;
+            print_nodes(&NodesRepo::init().all(&mut connection).await?);
+        }
+        Command::Peers => {
+            return Err(anyhow!("Peers are only known to a running node, use --api"));
+        }
+        Command::Backup { output } => {
+            let (manifest, archive) = create_backup(node.db.sqlite_pool(), &node.container, BackupContents::Full).await?;
+            rocket::tokio::fs::write(&output, archive).await?;
+            println!("Wrote backup from {} to {}", manifest.created_at, output.display());
+        }
+        Command::Restore { archive } => {
+            let archive = rocket::tokio::fs::read(&archive).await?;
+            let manifest = restore_backup(&node.db, &node.container, archive).await?;
+            println!("Restored backup from {}", manifest.created_at);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use std::env;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand};
+
+use crate::panda_comms::container::build_public_key_from_hex;
+use crate::repos::entities::Node;
+
+mod local;
+mod remote;
+
+/// Runs a LoRes node, or administers one from the command line.
+///
+/// Admin commands work on the node's database (`--database-url` or `DATABASE_URL`) directly,
+/// which is meant for a node that isn't running. Pass `--api` to manage a running node through
+/// its API instead.
+#[derive(Parser)]
+#[command(name = "lores-node", version)]
+pub struct Cli {
+    /// URL of a running node to manage, e.g. http://localhost:8000
+    #[arg(long, global = true, env = "LORES_API_URL")]
+    api: Option<String>,
+
+    /// Database of the node to manage when it isn't running, e.g. sqlite://lores.db
+    #[arg(long, global = true, env = "DATABASE_URL")]
+    database_url: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the node (the default)
+    Serve,
+    /// Create the database and the identity of this node
+    Init,
+    /// Show the node id and whether its key is usable
+    Identity,
+    /// Print an invite other nodes can join this region with
+    Invite,
+    /// Join a region, given its name and a node in it, or an invite
+    Join {
+        #[arg(required_unless_present = "invite")]
+        network_name: Option<String>,
+        /// Node id of a node in the region to connect to first
+        #[arg(long)]
+        bootstrap_node_id: Option<String>,
+        /// Invite printed by `lores-node invite` on a node in the region
+        #[arg(long, conflicts_with_all = ["network_name", "bootstrap_node_id"])]
+        invite: Option<Invite>,
+    },
+    /// List the nodes in the region
+    Nodes,
+    /// List the peers the network knows about (needs --api)
+    Peers,
+    /// Write a backup archive of this node to a file
+    Backup { output: PathBuf },
+    /// Replace the state of this node with a backup archive
+    Restore { archive: PathBuf },
+    /// Check a running node is ready, failing if it isn't (for container healthchecks)
+    Ready,
+}
+
+impl Cli {
+    /// Whether to run the node rather than an admin command.
+    pub fn serves(&self) -> bool {
+        matches!(self.command, None | Some(Command::Serve))
+    }
+}
+
+/// Runs an admin command, serving is left to the caller. Calls to a running node block, so they
+/// are made off the async runtime.
+pub async fn run(cli: Cli) -> Result<()> {
+    let Some(command) = cli.command else {
+        return Ok(());
+    };
+
+    if let Command::Ready = command {
+        // without --api, the node served from this container
+        let url = cli
+            .api
+            .unwrap_or_else(|| format!("http://localhost:{}", env::var("ROCKET_PORT").unwrap_or("8000".to_string())));
+        return rocket::tokio::task::spawn_blocking(move || remote::check_ready(&url)).await?;
+    }
+
+    match (cli.api, cli.database_url) {
+        (Some(api), _) => rocket::tokio::task::spawn_blocking(move || remote::run(&remote::NodeApi::new(api), command)).await?,
+        (None, Some(database_url)) => local::run(&database_url, command).await,
+        (None, None) => Err(anyhow!("Set DATABASE_URL, or pass --api to manage a running node")),
+    }
+}
+
+fn print_nodes(nodes: &[Node]) {
+    for node in nodes {
+        println!("{}  {}", node.id, node.name);
+    }
+}
+
+/// What a node needs to join a region: `<network name>@<bootstrap node id>`. Short enough to read
+/// out or paste into an SSH session.
+#[derive(Clone, Debug)]
+pub struct Invite {
+    pub network_name: String,
+    pub bootstrap_node_id: String,
+}
+
+impl fmt::Display for Invite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.network_name, self.bootstrap_node_id)
+    }
+}
+
+impl FromStr for Invite {
+    type Err = anyhow::Error;
+
+    fn from_str(invite: &str) -> Result<Self> {
+        // node ids are hex, so the last `@` separates them from a network name that may hold one
+        let (network_name, bootstrap_node_id) = invite
+            .rsplit_once('@')
+            .ok_or(anyhow!("Invite should look like <network name>@<node id>"))?;
+
+        if network_name.is_empty() {
+            return Err(anyhow!("Invite has no network name"));
+        }
+        build_public_key_from_hex(bootstrap_node_id.to_string()).ok_or(anyhow!("Invite has an invalid node id"))?;
+
+        Ok(Invite {
+            network_name: network_name.to_string(),
+            bootstrap_node_id: bootstrap_node_id.to_string(),
+        })
+    }
+}
+
+/// The network name and bootstrap node id to join, from either the arguments of `Join` or its
+/// invite.
+fn join_details(network_name: Option<String>, bootstrap_node_id: Option<String>, invite: Option<Invite>) -> Result<(String, Option<String>)> {
+    if let Some(invite) = invite {
+        return Ok((invite.network_name, Some(invite.bootstrap_node_id)));
+    }
+
+    let network_name = network_name.ok_or(anyhow!("No network name given"))?;
+    if let Some(node_id) = &bootstrap_node_id {
+        build_public_key_from_hex(node_id.clone()).ok_or(anyhow!("Invalid bootstrap node id"))?;
+    }
+
+    Ok((network_name, bootstrap_node_id))
+}
@@ -0,0 +1,159 @@
+use std::io::Read;
+
+use anyhow::{anyhow, Result};
+use rocket::serde::json::{json, Value};
+use rocket::serde::DeserializeOwned;
+
+use super::{join_details, print_nodes, Command, Invite};
+use crate::infra::api_error::ApiError;
+use crate::repos::entities::{Node, Region};
+use crate::routes::admin::RestoreDetails;
+use crate::routes::this_p2panda_node::{KeyDetails, NodeDetails};
+
+/// A running node, reached through its API.
+pub struct NodeApi {
+    base_url: String,
+}
+
+impl NodeApi {
+    pub fn new(url: String) -> Self {
+        NodeApi {
+            base_url: format!("{}/api/v1", url.trim_end_matches('/')),
+        }
+    }
+
+    fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let response = self.call(ureq::get(&self.url(path)).call())?;
+        Ok(response.into_json()?)
+    }
+
+    fn post<T: DeserializeOwned>(&self, path: &str, body: Value) -> Result<T> {
+        let response = self.call(ureq::post(&self.url(path)).send_json(body))?;
+        Ok(response.into_json()?)
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url, path)
+    }
+
+    /// Turns the JSON error body of a failed call into an error the operator can read.
+    fn call(&self, result: Result<ureq::Response, ureq::Error>) -> Result<ureq::Response> {
+        match result {
+            Ok(response) => Ok(response),
+            Err(ureq::Error::Status(status, response)) => match response.into_json::<ApiError>() {
+                Ok(error) => Err(anyhow!("{} ({:?})", error.message, error.code)),
+                Err(_) => Err(anyhow!("Node answered with status {}", status)),
+            },
+            Err(e) => Err(anyhow!("Can't reach the node at {}: {}", self.base_url, e)),
+        }
+    }
+}
+
+/// Prints the readiness checks of the node at `url`, failing unless all of them pass.
+pub fn check_ready(url: &str) -> Result<()> {
+    let response = match ureq::get(&format!("{}/readyz", url.trim_end_matches('/'))).call() {
+        Ok(response) | Err(ureq::Error::Status(503, response)) => response,
+        Err(e) => return Err(anyhow!("Can't reach the node at {}: {}", url, e)),
+    };
+    let readiness: Value = response.into_json()?;
+
+    for check in readiness["checks"]
+        .as_array()
+        .into_iter()
+        .flatten()
+    {
+        let status = if check["ok"].as_bool() == Some(true) { "ok" } else { "FAILING" };
+        println!(
+            "{:<14} {:<8} {}",
+            check["name"].as_str().unwrap_or(""),
+            status,
+            check["detail"].as_str().unwrap_or("")
+        );
+    }
+
+    match readiness["ready"].as_bool() {
+        Some(true) => Ok(()),
+        _ => Err(anyhow!("Node is not ready")),
+    }
+}
+
+pub fn run(api: &NodeApi, command: Command) -> Result<()> {
+    match command {
+        Command::Serve => unreachable!("serving is handled by main"),
+        Command::Ready => unreachable!("readiness is checked by cli::run"),
+        Command::Init => {
+            return Err(anyhow!("A running node is initialized already, run init without --api"));
+        }
+        Command::Identity => {
+            let key: KeyDetails = api.get("this_p2panda_node/key")?;
+            println!("Key status: {:?}", key.status);
+
+            let node: NodeDetails = api.get("this_p2panda_node")?;
+            println!("Node id: {}", node.panda_node_id);
+
+            let region: Option<Region> = api.get("this_region")?;
+            println!(
+                "Region: {}",
+                region
+                    .map(|region| region.network_id)
+                    .unwrap_or("none".to_string())
+            );
+        }
+        Command::Invite => {
+            let region: Option<Region> = api.get("this_region")?;
+            let region = region.ok_or(anyhow!("This node hasn't joined a region"))?;
+            let node: NodeDetails = api.get("this_p2panda_node")?;
+
+            let invite = Invite {
+                network_name: region.network_id,
+                bootstrap_node_id: node.panda_node_id,
+            };
+            println!("{}", invite);
+        }
+        Command::Join {
+            network_name,
+            bootstrap_node_id,
+            invite,
+        } => {
+            let (network_name, bootstrap_node_id) = join_details(network_name, bootstrap_node_id, invite)?;
+            let bootstrap_peer = bootstrap_node_id.map(|node_id| json!({ "node_id": node_id }));
+
+            let _: Value = api.post(
+                "this_region/bootstrap",
+                json!({ "network_name": network_name, "bootstrap_peer": bootstrap_peer }),
+            )?;
+            println!("Joined region {}", network_name);
+        }
+        Command::Nodes => {
+            let nodes: Vec<Node> = api.get("this_region/nodes")?;
+            print_nodes(&nodes);
+        }
+        Command::Peers => {
+            let node: NodeDetails = api.get("this_p2panda_node")?;
+            for peer in node.peers {
+                println!("{}", rocket::serde::json::to_string(&peer)?);
+            }
+        }
+        Command::Backup { output } => {
+            let response = api.call(ureq::post(&api.url("admin/backup")).call())?;
+            let mut archive = Vec::new();
+            response.into_reader().read_to_end(&mut archive)?;
+
+            std::fs::write(&output, archive)?;
+            println!("Wrote backup to {}", output.display());
+        }
+        Command::Restore { archive } => {
+            let archive = std::fs::read(&archive)?;
+            let response = api.call(
+                ureq::post(&api.url("admin/restore"))
+                    .set("Content-Type", "application/octet-stream")
+                    .send_bytes(&archive),
+            )?;
+
+            let restored: RestoreDetails = response.into_json()?;
+            println!("Restored backup from {}, key is {:?}", restored.manifest.created_at, restored.key_status);
+        }
+    }
+
+    Ok(())
+}
@@ -2,14 +2,22 @@ use rocket::fairing::{Fairing, Info, Kind};
 use rocket::tokio;
 use rocket::{Orbit, Rocket};
 use rocket_db_pools::Database;
+use sqlx::Sqlite;
 use std::sync::Arc;
 use tokio::sync::{mpsc, Mutex};
+use tracing::{error, info, info_span, Instrument};
 
 use crate::infra::db::MainDb;
+use crate::infra::metrics::metrics;
+use crate::infra::supervisor::Supervisor;
+use crate::panda_comms::container::P2PandaContainer;
 use crate::panda_comms::lores_events::LoResEvent;
 
 use super::handler_map::handle_event;
 
+/// Name the event handler loop is supervised under.
+pub const EVENT_HANDLER_TASK: &str = "event_handler";
+
 pub struct EventHandlerFairing {
     events_rx: Arc<Mutex<mpsc::Receiver<LoResEvent>>>,
 }
@@ -35,6 +43,46 @@ impl EventHandlerFairing {
     // }
 }
 
+/// Handles the events `container` receives, projecting them into `db_pool`. Restarted after a
+/// panic, the loop picks up the receiver again and carries on with the next event.
+pub fn spawn_event_handler(
+    supervisor: &Supervisor,
+    events_rx: Arc<Mutex<mpsc::Receiver<LoResEvent>>>,
+    db_pool: sqlx::Pool<Sqlite>,
+    container: P2PandaContainer,
+) {
+    supervisor.spawn(EVENT_HANDLER_TASK, move || {
+        let events_rx = Arc::clone(&events_rx);
+        let db_pool = db_pool.clone();
+        let container = container.clone();
+
+        async move {
+            let mut events_rx = events_rx.lock().await;
+
+            while let Some(event) = events_rx.recv().await {
+                let name = event.payload.name();
+                let span = info_span!("event", event = name, author = %event.header.author_node_id);
+                metrics().event(name, "received");
+
+                match handle_event(event, &db_pool, &container)
+                    .instrument(span.clone())
+                    .await
+                {
+                    Ok(()) => metrics().event(name, "processed"),
+                    Err(err) => {
+                        span.in_scope(|| error!(error = format!("{:#}", err), "Failed to handle event"));
+                        metrics().event(name, "failed");
+                    }
+                }
+            }
+
+            // the container is gone for good, e.g. its region was left
+            info!("Event channel closed, stopped handling events");
+            Ok(())
+        }
+    });
+}
+
 #[rocket::async_trait]
 impl Fairing for EventHandlerFairing {
     fn info(&self) -> Info {
@@ -47,18 +95,15 @@ impl Fairing for EventHandlerFairing {
     async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
         let events_rx_arc = Arc::clone(&self.events_rx);
 
-        if let Some(db) = MainDb::fetch(&rocket) {
-            let db_pool = db.sqlite_pool().clone();
+        let container = rocket.state::<P2PandaContainer>().cloned();
+        let supervisor = rocket.state::<Supervisor>();
 
-            tokio::spawn(async move {
-                let mut events_rx = events_rx_arc.lock().await;
+        if let (Some(db), Some(container), Some(supervisor)) = (MainDb::fetch(&rocket), container, supervisor) {
+            let db_pool = db.sqlite_pool().clone();
 
-                while let Some(event) = events_rx.recv().await {
-                    handle_event(event, &db_pool).await;
-                }
-            });
+            spawn_event_handler(supervisor, events_rx_arc, db_pool, container);
         } else {
-            println!("MainDb state not found, won't handle event");
+            error!("MainDb, P2PandaContainer or Supervisor state not found, won't handle events");
         }
     }
 }
@@ -1,25 +1,304 @@
+use anyhow::{anyhow, Context, Result};
 use sqlx::Sqlite;
+use tracing::{debug, error, info, warn, Instrument, Span};
 
 use crate::{
-    panda_comms::lores_events::{LoResEvent, LoResEventPayload},
-    repos::{entities::Node, nodes::NodesRepo},
+    infra::time::unix_now,
+    panda_comms::{
+        container::{build_hash_from_hex, P2PandaContainer},
+        identity,
+        lores_events::{validate_alert_rule, BackupRequested, BackupStored, DirectMessageSent, LoResEvent, LoResEventHeader, LoResEventPayload},
+        sealed_box,
+    },
+    repos::{
+        alerts::AlertsRepo,
+        buddy_backups::{BuddyBackupsError, BuddyBackupsRepo, HELD_BACKUPS_QUOTA_BYTES, MAX_HELD_BACKUP_BYTES},
+        direct_messages::DirectMessagesRepo,
+        entities::{AlertRule, BackupOffer, DirectMessage, HeldBackup, Node, NodeHealth, Notice},
+        nodes::NodesRepo,
+        notices::NoticesRepo,
+    },
 };
 
-pub async fn handle_event(event: LoResEvent, pool: &sqlx::Pool<Sqlite>) {
+/// Names the backup transfers spawned off by events are supervised under, followed by the node
+/// they are for.
+pub const HOLD_BACKUP_TASK: &str = "hold_backup";
+pub const OFFER_BACKUP_TASK: &str = "offer_backup";
+
+/// Handles an event from the region. Work that is spawned off, like fetching a backup, reports
+/// its own failures.
+pub async fn handle_event(event: LoResEvent, pool: &sqlx::Pool<Sqlite>, container: &P2PandaContainer) -> Result<()> {
     let header = event.header;
 
     match event.payload {
         LoResEventPayload::NodeAnnounced(payload) => {
             let repo = NodesRepo::init();
 
-            println!("Node announced: {:?}", payload);
+            info!(name = payload.name, "Node announced");
 
             let node: Node = Node {
                 id: header.author_node_id.clone(),
                 name: payload.name.clone(),
             };
 
-            repo.upsert(pool, node).await.unwrap();
+            repo.upsert(pool, node)
+                .await
+                .context("Failed to store node")?;
+        }
+        LoResEventPayload::NodeLeft(_) => {
+            info!("Node left");
+
+            NodesRepo::init()
+                .remove(pool, &header.author_node_id)
+                .await
+                .context("Failed to remove node")?;
+
+            // a node that left won't hold our backups anymore
+            match BuddyBackupsRepo::init()
+                .remove_buddy(pool, &header.author_node_id)
+                .await
+            {
+                Ok(()) => info!("Removed backup buddy that left"),
+                Err(BuddyBackupsError::NotFound(_)) => {}
+                Err(e) => return Err(e).context("Failed to remove backup buddy"),
+            }
+        }
+        LoResEventPayload::NodeHealthReported(payload) => {
+            debug!(open_alerts = ?payload.open_alerts, "Node health reported");
+
+            NodesRepo::init()
+                .record_health(pool, &header.author_node_id, NodeHealth::from(payload))
+                .await
+                .context("Failed to store node health")?;
+        }
+        LoResEventPayload::AlertRuleSaved(payload) => {
+            if let Err(reason) = validate_alert_rule(&payload.name, &payload.condition) {
+                warn!(rule_id = payload.id, reason, "Ignoring invalid alert rule");
+                return Ok(());
+            }
+
+            info!(rule_id = payload.id, name = payload.name, "Alert rule saved");
+
+            let rule = AlertRule {
+                id: payload.id,
+                name: payload.name,
+                condition: payload.condition,
+                author_node_id: header.author_node_id,
+                updated_at: payload.updated_at,
+            };
+            AlertsRepo::init()
+                .save_rule(pool, rule)
+                .await
+                .context("Failed to store alert rule")?;
+        }
+        LoResEventPayload::AlertRuleDeleted(payload) => {
+            info!(rule_id = payload.id, "Alert rule deleted");
+
+            AlertsRepo::init()
+                .delete_rule(pool, &payload.id, &header.author_node_id, payload.deleted_at)
+                .await
+                .context("Failed to delete alert rule")?;
+        }
+        LoResEventPayload::NoticePosted(payload) => {
+            let repo = NoticesRepo::init();
+
+            info!(title = payload.title, "Notice posted");
+
+            let notice = Notice {
+                id: payload.id,
+                author_node_id: header.author_node_id.clone(),
+                title: payload.title,
+                body: payload.body,
+                priority: payload.priority.as_str().to_string(),
+                posted_at: payload.posted_at,
+                expires_at: payload.expires_at,
+            };
+
+            repo.insert(pool, notice)
+                .await
+                .context("Failed to store notice")?;
+        }
+        LoResEventPayload::KeyRotated(payload) => {
+            if !identity::verify_rotation(&header.author_node_id, &payload.new_node_id, &payload.proof_hex) {
+                warn!("Ignoring key rotation with an invalid proof");
+                return Ok(());
+            }
+
+            info!(new_node_id = payload.new_node_id, "Key rotated");
+
+            let repo = NodesRepo::init();
+            repo.rotate_key(pool, &header.author_node_id, &payload.new_node_id)
+                .await
+                .context("Failed to record key rotation")?;
+        }
+        LoResEventPayload::DirectMessageSent(payload) => {
+            receive_direct_message(&header, payload, pool, container)
+                .await
+                .context("Failed to receive direct message")?;
+        }
+        LoResEventPayload::BackupStored(payload) => {
+            if Some(&payload.buddy_node_id) != own_node_id(container).await.as_ref() {
+                return Ok(());
+            }
+
+            // downloading can take a while, don't hold up the other events meanwhile. A newer
+            // backup from the same node replaces a download still going
+            let name = format!("{}/{}", HOLD_BACKUP_TASK, header.author_node_id);
+            let (pool, task_container, span) = (pool.clone(), container.clone(), Span::current());
+            container.supervisor().spawn(&name, move || {
+                let (header, payload) = (header.clone(), payload.clone());
+                let (pool, container) = (pool.clone(), task_container.clone());
+
+                // not retried, the node pushes another backup on its next schedule
+                async move {
+                    if let Err(err) = hold_backup(header, payload, &pool, &container).await {
+                        error!(error = %err, "Failed to hold backup");
+                    }
+                    Ok(())
+                }
+                .instrument(span.clone())
+            });
         }
+        LoResEventPayload::BackupRequested(payload) => {
+            // publishing from here would feed our own event queue while we are draining it
+            let name = format!("{}/{}", OFFER_BACKUP_TASK, header.author_node_id);
+            let (pool, task_container, span) = (pool.clone(), container.clone(), Span::current());
+            container.supervisor().spawn(&name, move || {
+                let (header, payload) = (header.clone(), payload.clone());
+                let (pool, container) = (pool.clone(), task_container.clone());
+
+                // not retried, the lost node can ask again
+                async move {
+                    if let Err(err) = offer_backup(header, payload, &pool, &container).await {
+                        error!(error = %err, "Failed to offer backup");
+                    }
+                    Ok(())
+                }
+                .instrument(span.clone())
+            });
+        }
+        LoResEventPayload::BackupOffered(payload) => {
+            if Some(&payload.requested_by) != own_node_id(container).await.as_ref() {
+                return Ok(());
+            }
+
+            info!(owner_node_id = payload.owner_node_id, "Backup offered");
+
+            let offer = BackupOffer {
+                blob_hash: payload.blob_hash,
+                owner_node_id: payload.owner_node_id,
+                buddy_node_id: header.author_node_id,
+                created_at: payload.created_at,
+                offered_at: unix_now(),
+            };
+
+            BuddyBackupsRepo::init()
+                .insert_offer(pool, offer)
+                .await
+                .context("Failed to store backup offer")?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn own_node_id(container: &P2PandaContainer) -> Option<String> {
+    container
+        .get_params()
+        .await
+        .private_key
+        .map(|private_key| private_key.public_key().to_hex())
+}
+
+/// Fetches a backup a buddy asked us to keep for it. Only the nodes we chose as buddies ourselves
+/// get to store backups with us, within a quota.
+async fn hold_backup(header: LoResEventHeader, payload: BackupStored, pool: &sqlx::Pool<Sqlite>, container: &P2PandaContainer) -> anyhow::Result<()> {
+    let repo = BuddyBackupsRepo::init();
+    if !repo
+        .is_buddy(pool, &header.author_node_id)
+        .await?
+    {
+        info!("Ignoring backup from a node that isn't our buddy");
+        return Ok(());
     }
+
+    let hash = build_hash_from_hex(payload.blob_hash.clone()).ok_or(anyhow::Error::msg("Invalid blob hash"))?;
+    let archive = container
+        .fetch_blob(hash, MAX_HELD_BACKUP_BYTES)
+        .await?;
+
+    let held_backup = HeldBackup {
+        blob_hash: payload.blob_hash,
+        owner_node_id: header.author_node_id,
+        created_at: payload.created_at,
+    };
+    if !repo
+        .hold_backup(pool, held_backup, archive, HELD_BACKUPS_QUOTA_BYTES)
+        .await?
+    {
+        return Err(anyhow!("Held backups would take more than {} bytes", HELD_BACKUPS_QUOTA_BYTES));
+    }
+
+    info!("Holding backup");
+
+    Ok(())
+}
+
+/// Makes the newest backup we hold for the requested node available again, our blob store might
+/// have been emptied by a restart since we fetched it.
+async fn offer_backup(
+    header: LoResEventHeader,
+    payload: BackupRequested,
+    pool: &sqlx::Pool<Sqlite>,
+    container: &P2PandaContainer,
+) -> anyhow::Result<()> {
+    let held = BuddyBackupsRepo::init()
+        .latest_held_backup(pool, &payload.owner_node_id)
+        .await?;
+
+    let Some((held_backup, archive)) = held else {
+        return Ok(());
+    };
+
+    let blob_hash = container.add_blob(archive).await?.to_hex();
+    container
+        .offer_backup(held_backup.owner_node_id, header.author_node_id, blob_hash, held_backup.created_at)
+        .await
+}
+
+/// Stores a direct message if it is addressed to this node, otherwise ignores it.
+async fn receive_direct_message(
+    header: &LoResEventHeader,
+    payload: DirectMessageSent,
+    pool: &sqlx::Pool<Sqlite>,
+    container: &P2PandaContainer,
+) -> anyhow::Result<()> {
+    let private_key = container
+        .get_params()
+        .await
+        .private_key
+        .ok_or(anyhow::Error::msg("No private key"))?;
+
+    if payload.recipient_node_id != private_key.public_key().to_hex() {
+        return Ok(());
+    }
+
+    let sealed_body = hex::decode(&payload.sealed_body_hex)?;
+    let body = String::from_utf8(sealed_box::open(&private_key, &sealed_body)?)?;
+
+    info!("Direct message received");
+
+    let message = DirectMessage {
+        id: payload.id,
+        sender_node_id: header.author_node_id.clone(),
+        body,
+        sent_at: payload.sent_at,
+        received_at: unix_now(),
+        read_at: None,
+    };
+    DirectMessagesRepo::init()
+        .insert(pool, message)
+        .await?;
+
+    Ok(())
 }
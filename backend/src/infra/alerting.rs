@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::tokio::time::sleep;
+use rocket::{Orbit, Rocket};
+use rocket_db_pools::Database;
+use sqlx::Sqlite;
+use tracing::{error, info};
+
+use super::db::MainDb;
+use super::notifications::{AlertNotification, Notifier, Notify};
+use super::settings::Settings;
+use super::supervisor::Supervisor;
+use super::time::unix_now;
+use crate::panda_comms::container::{P2PandaContainer, SyncFailure};
+use crate::panda_comms::lores_events::AlertCondition;
+use crate::repos::alerts::{AlertsError, AlertsRepo};
+use crate::repos::entities::{AlertRule, Node, NodeHealth};
+use crate::repos::nodes::NodesRepo;
+
+/// How often the rules are checked.
+pub const ALERT_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Name the rule checks are supervised under.
+pub const ALERT_CHECKS_TASK: &str = "alert_checks";
+
+/// How far back `SyncFailing` counts failures.
+const SYNC_FAILURE_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// What this node knows of the region when checking the rules.
+pub struct RegionView {
+    pub nodes: Vec<(Node, Option<NodeHealth>)>,
+    /// When each node was last heard from, by its heartbeats.
+    pub last_heartbeats: HashMap<String, i64>,
+    pub sync_failures: Vec<SyncFailure>,
+    pub now: i64,
+}
+
+impl RegionView {
+    pub async fn read(pool: &sqlx::Pool<Sqlite>, container: &P2PandaContainer) -> Result<Self, AlertsError> {
+        let mut connection = pool
+            .acquire()
+            .await
+            .map_err(|_| AlertsError::InternalServerError("Database error".to_string()))?;
+        let nodes = NodesRepo::init()
+            .all_with_health(&mut connection)
+            .await
+            .map_err(|e| AlertsError::InternalServerError(e.to_string()))?;
+
+        Ok(RegionView {
+            nodes,
+            last_heartbeats: container
+                .presence()
+                .all()
+                .await
+                .into_iter()
+                .map(|presence| (presence.node_id, presence.last_seen_at))
+                .collect(),
+            sync_failures: container.recent_sync_failures().await,
+            now: unix_now(),
+        })
+    }
+
+    fn node_name(&self, node_id: &str) -> String {
+        self.nodes
+            .iter()
+            .find(|(node, _)| node.id == node_id)
+            .map(|(node, _)| node.name.clone())
+            .unwrap_or_else(|| node_id.chars().take(8).collect())
+    }
+}
+
+/// A rule firing for a node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Firing {
+    pub rule_id: String,
+    pub node_id: String,
+    pub message: String,
+}
+
+/// Every node each rule fires for.
+pub fn evaluate(rules: &[AlertRule], view: &RegionView) -> Vec<Firing> {
+    let mut firings = vec![];
+
+    for rule in rules {
+        let mut fire = |node_id: &str, message: String| {
+            firings.push(Firing {
+                rule_id: rule.id.clone(),
+                node_id: node_id.to_string(),
+                message,
+            })
+        };
+
+        match &rule.condition {
+            AlertCondition::NodeNotSeen { hours } => {
+                for (node, health) in &view.nodes {
+                    let reported_at = health.as_ref().map(|health| health.reported_at);
+                    let heartbeat_at = view.last_heartbeats.get(&node.id).copied();
+
+                    // a node never heard from can't be told apart from one that never existed
+                    let Some(last_seen) = reported_at.max(heartbeat_at) else {
+                        continue;
+                    };
+                    if view.now - last_seen > *hours as i64 * 60 * 60 {
+                        fire(&node.id, format!("{} not seen for more than {} hours", node.name, hours));
+                    }
+                }
+            }
+            AlertCondition::DiskAbove { percent } => {
+                for (node, health) in &view.nodes {
+                    let used = health
+                        .as_ref()
+                        .and_then(|health| health.disk_used_percent);
+
+                    if let Some(used) = used.filter(|used| used > percent) {
+                        fire(&node.id, format!("{} disk is {:.0}% full", node.name, used));
+                    }
+                }
+            }
+            AlertCondition::NodeUnhealthy => {
+                for (node, health) in &view.nodes {
+                    let open_alerts = health
+                        .as_ref()
+                        .map(|health| health.open_alerts.clone())
+                        .unwrap_or_default();
+
+                    if !open_alerts.is_empty() {
+                        fire(&node.id, format!("{} reports {}", node.name, open_alerts.join(", ").replace('_', " ")));
+                    }
+                }
+            }
+            AlertCondition::SyncFailing { failures } => {
+                let since = view.now - SYNC_FAILURE_WINDOW.as_secs() as i64;
+                let mut counts: HashMap<&str, u32> = HashMap::new();
+                for failure in view
+                    .sync_failures
+                    .iter()
+                    .filter(|failure| failure.failed_at >= since)
+                {
+                    *counts.entry(failure.peer.as_str()).or_default() += 1;
+                }
+
+                let mut failing: Vec<(&str, u32)> = counts
+                    .into_iter()
+                    .filter(|(_, count)| count >= failures)
+                    .collect();
+                failing.sort();
+
+                for (peer, count) in failing {
+                    fire(
+                        peer,
+                        format!("Syncing with {} failed {} times in the last hour", view.node_name(peer), count),
+                    );
+                }
+            }
+        }
+    }
+
+    firings
+}
+
+/// Checks the rules against the region, opens an alert for every new firing and resolves the
+/// alerts that stopped firing, notifying about both.
+pub async fn check_rules(pool: &sqlx::Pool<Sqlite>, container: &P2PandaContainer, notifier: &impl Notify) -> Result<(), AlertsError> {
+    let repo = AlertsRepo::init();
+    let rules = repo.rules(pool).await?;
+    let view = RegionView::read(pool, container).await?;
+
+    let firings = evaluate(&rules, &view);
+    let active = repo.active_alerts(pool).await?;
+    let rule_name = |rule_id: &str| {
+        rules
+            .iter()
+            .find(|rule| rule.id == rule_id)
+            .map(|rule| rule.name.clone())
+            .unwrap_or_default()
+    };
+
+    for firing in &firings {
+        let is_active = active
+            .iter()
+            .any(|alert| alert.rule_id == firing.rule_id && alert.node_id == firing.node_id);
+        if is_active {
+            continue;
+        }
+
+        let alert = repo
+            .open_alert(pool, &firing.rule_id, &firing.node_id, &firing.message)
+            .await?;
+        info!(alert_id = alert.id, message = alert.message, "Alert opened");
+
+        notifier
+            .notify(AlertNotification {
+                event: "opened",
+                rule_name: rule_name(&alert.rule_id),
+                node_name: view.node_name(&alert.node_id),
+                alert,
+            })
+            .await;
+    }
+
+    // also resolves the alerts of deleted rules
+    for alert in active {
+        let still_firing = firings
+            .iter()
+            .any(|firing| alert.rule_id == firing.rule_id && alert.node_id == firing.node_id);
+        if still_firing {
+            continue;
+        }
+
+        let alert = repo.resolve(pool, &alert.id).await?;
+        info!(alert_id = alert.id, message = alert.message, "Alert resolved");
+
+        notifier
+            .notify(AlertNotification {
+                event: "resolved",
+                rule_name: rule_name(&alert.rule_id),
+                node_name: view.node_name(&alert.node_id),
+                alert,
+            })
+            .await;
+    }
+
+    Ok(())
+}
+
+/// Checks the alerting rules every `ALERT_CHECK_INTERVAL`.
+#[derive(Default)]
+pub struct AlertingFairing {}
+
+#[rocket::async_trait]
+impl Fairing for AlertingFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "AlertingFairing",
+            kind: Kind::Liftoff | Kind::Singleton,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let container = rocket.state::<P2PandaContainer>().cloned();
+        let settings = rocket.state::<Settings>();
+        let supervisor = rocket.state::<Supervisor>();
+
+        let (Some(db), Some(container), Some(settings), Some(supervisor)) = (MainDb::fetch(rocket), container, settings, supervisor) else {
+            error!("MainDb, P2PandaContainer, Settings or Supervisor state not found, won't check alerting rules");
+            return;
+        };
+        let db_pool = db.sqlite_pool().clone();
+        let notifier = Notifier::from_settings(settings);
+
+        supervisor.spawn(ALERT_CHECKS_TASK, move || {
+            let db_pool = db_pool.clone();
+            let container = container.clone();
+            let notifier = notifier.clone();
+
+            async move {
+                loop {
+                    sleep(ALERT_CHECK_INTERVAL).await;
+
+                    if let Err(e) = check_rules(&db_pool, &container, &notifier).await {
+                        error!(error = %e, "Failed to check alerting rules");
+                    }
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,229 @@
+use rocket::http::Status;
+use rocket::request::Request;
+use rocket::response::{self, Responder, Response};
+use rocket::serde::json::Json;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::Catcher;
+use thiserror::Error;
+use tracing::error;
+use utoipa::ToSchema;
+
+use super::backup::BackupError;
+use super::logging::request_span;
+use crate::panda_comms::container::ContainerError;
+use crate::panda_comms::regions::RegionsError;
+use crate::repos::alerts::AlertsError;
+use crate::repos::buddy_backups::BuddyBackupsError;
+use crate::repos::direct_messages::DirectMessagesError;
+use crate::repos::nodes::NodesError;
+use crate::repos::notices::NoticesError;
+use crate::repos::system::SystemError;
+use crate::repos::this_node::ThisNodeRepoError;
+use crate::repos::this_p2panda_node::ThisP2PandaNodeRepoError;
+
+/// Machine-readable reason for a failed API call, sent as `code` in the error body.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum ErrorCode {
+    BadRequest,
+    NotFound,
+    ValidationFailed,
+    Conflict,
+    KeyLocked,
+    NetworkNotStarted,
+    InternalError,
+}
+
+impl ErrorCode {
+    pub fn status(&self) -> Status {
+        match self {
+            ErrorCode::BadRequest => Status::BadRequest,
+            ErrorCode::NotFound => Status::NotFound,
+            ErrorCode::ValidationFailed => Status::UnprocessableEntity,
+            ErrorCode::Conflict => Status::Conflict,
+            ErrorCode::KeyLocked => Status::Locked,
+            ErrorCode::NetworkNotStarted => Status::ServiceUnavailable,
+            ErrorCode::InternalError => Status::InternalServerError,
+        }
+    }
+}
+
+/// The error every API route responds with: the status follows from the code, and the body is
+/// `{"code": "...", "message": "..."}`.
+#[derive(Serialize, Deserialize, Debug, Error, ToSchema)]
+#[serde(crate = "rocket::serde")]
+#[error("{code:?}: {message}")]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        ApiError {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotFound, message)
+    }
+
+    pub fn validation_failed(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::ValidationFailed, message)
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Conflict, message)
+    }
+
+    pub fn key_locked() -> Self {
+        Self::new(ErrorCode::KeyLocked, "Private key is locked, unlock it to start the network")
+    }
+
+    pub fn internal(error: impl std::fmt::Display) -> Self {
+        Self::new(ErrorCode::InternalError, error.to_string())
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        if self.code == ErrorCode::InternalError {
+            request_span(request).in_scope(|| error!(message = self.message, "Internal error"));
+        }
+
+        let status = self.code.status();
+
+        Response::build_from(Json(self).respond_to(request)?)
+            .status(status)
+            .ok()
+    }
+}
+
+/// Errors from the P2Panda container. Calling it in the wrong state is something the caller can act
+/// on, anything else is internal.
+impl From<anyhow::Error> for ApiError {
+    fn from(error: anyhow::Error) -> Self {
+        match error.downcast_ref::<ContainerError>() {
+            Some(ContainerError::NotRunning) => ApiError::new(ErrorCode::NetworkNotStarted, error.to_string()),
+            Some(ContainerError::KeyLocked) => ApiError::key_locked(),
+            Some(ContainerError::AlreadyRunning | ContainerError::Busy(_)) => ApiError::conflict(error.to_string()),
+            None => ApiError::internal(error),
+        }
+    }
+}
+
+impl From<NodesError> for ApiError {
+    fn from(error: NodesError) -> Self {
+        match error {
+            NodesError::InternalServerError(message) => ApiError::internal(message),
+        }
+    }
+}
+
+impl From<NoticesError> for ApiError {
+    fn from(error: NoticesError) -> Self {
+        match error {
+            NoticesError::InternalServerError(message) => ApiError::internal(message),
+        }
+    }
+}
+
+impl From<DirectMessagesError> for ApiError {
+    fn from(error: DirectMessagesError) -> Self {
+        match error {
+            DirectMessagesError::InternalServerError(message) => ApiError::internal(message),
+            DirectMessagesError::NotFound(message) => ApiError::not_found(message),
+        }
+    }
+}
+
+impl From<ThisNodeRepoError> for ApiError {
+    fn from(error: ThisNodeRepoError) -> Self {
+        match error {
+            ThisNodeRepoError::InternalServerError(message) => ApiError::internal(message),
+            ThisNodeRepoError::NotFound(message) => ApiError::not_found(message),
+        }
+    }
+}
+
+impl From<ThisP2PandaNodeRepoError> for ApiError {
+    fn from(error: ThisP2PandaNodeRepoError) -> Self {
+        match error {
+            ThisP2PandaNodeRepoError::InternalServerError(message) => ApiError::internal(message),
+            ThisP2PandaNodeRepoError::Invalid(message) => ApiError::validation_failed(message),
+        }
+    }
+}
+
+impl From<AlertsError> for ApiError {
+    fn from(error: AlertsError) -> Self {
+        match error {
+            AlertsError::InternalServerError(message) => ApiError::internal(message),
+            AlertsError::NotFound(message) => ApiError::not_found(message),
+        }
+    }
+}
+
+impl From<BuddyBackupsError> for ApiError {
+    fn from(error: BuddyBackupsError) -> Self {
+        match error {
+            BuddyBackupsError::InternalServerError(message) => ApiError::internal(message),
+            BuddyBackupsError::NotFound(message) => ApiError::not_found(message),
+        }
+    }
+}
+
+impl From<SystemError> for ApiError {
+    fn from(error: SystemError) -> Self {
+        match error {
+            SystemError::InternalServerError(message) => ApiError::internal(message),
+        }
+    }
+}
+
+impl From<BackupError> for ApiError {
+    fn from(error: BackupError) -> Self {
+        match error {
+            BackupError::InternalServerError(message) => ApiError::internal(message),
+            BackupError::Invalid(message) => ApiError::validation_failed(message),
+        }
+    }
+}
+
+impl From<RegionsError> for ApiError {
+    fn from(error: RegionsError) -> Self {
+        match error {
+            RegionsError::InternalServerError(message) => ApiError::internal(message),
+            RegionsError::NotFound(message) => ApiError::not_found(message),
+            RegionsError::Conflict(message) => ApiError::conflict(message),
+        }
+    }
+}
+
+#[catch(400)]
+fn bad_request() -> ApiError {
+    ApiError::new(ErrorCode::BadRequest, "The request could not be understood")
+}
+
+#[catch(404)]
+fn not_found(request: &Request) -> ApiError {
+    ApiError::not_found(format!("Nothing found at {}", request.uri()))
+}
+
+#[catch(422)]
+fn unprocessable_entity() -> ApiError {
+    ApiError::validation_failed("The request body is not valid")
+}
+
+#[catch(500)]
+fn internal_server_error() -> ApiError {
+    ApiError::internal("Something went wrong")
+}
+
+/// Catchers for requests that fail before reaching a route, e.g. unknown paths or bodies that
+/// don't parse, so these get the same JSON error body too.
+pub fn catchers() -> Vec<Catcher> {
+    catchers![bad_request, not_found, unprocessable_entity, internal_server_error]
+}
@@ -0,0 +1,373 @@
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::tokio::task::spawn_blocking;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::{Connection, Sqlite, SqliteConnection};
+use thiserror::Error;
+use tracing::info;
+use utoipa::ToSchema;
+
+use super::db::{migrate, MainDb};
+use super::time::unix_now;
+use crate::panda_comms::container::P2PandaContainer;
+use crate::panda_comms::fairing::load_node_params;
+use crate::panda_comms::identity::{key_passphrase_from_env, PrivateKeyStatus};
+
+const FORMAT_VERSION: u8 = 1;
+const MANIFEST_FILE: &str = "manifest.json";
+const DATABASE_FILE: &str = "database.sqlite";
+const CONFIG_FILE: &str = "config.json";
+
+#[derive(Debug, Error)]
+pub enum BackupError {
+    #[error("Internal server error: {0}")]
+    InternalServerError(String),
+
+    #[error("Invalid: {0}")]
+    Invalid(String),
+}
+
+impl BackupError {
+    fn internal(error: impl std::fmt::Display) -> Self {
+        BackupError::InternalServerError(error.to_string())
+    }
+
+    fn invalid(error: impl std::fmt::Display) -> Self {
+        BackupError::Invalid(error.to_string())
+    }
+}
+
+/// Describes a backup archive, stored in it as `manifest.json`.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct BackupManifest {
+    pub format_version: u8,
+    pub app_version: String,
+    pub created_at: i64,
+    pub node_id: Option<String>,
+}
+
+impl BackupManifest {
+    /// What the archive is called when it is downloaded or kept on disk.
+    pub fn file_name(&self) -> String {
+        format!("lores-node-backup-{}.tar.gz", self.created_at)
+    }
+}
+
+/// The app configuration the node was running with. The same values are in the database, this
+/// copy is there so an operator can read them without opening it.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct BackupConfig {
+    network_name: Option<String>,
+    bootstrap_node_id: Option<String>,
+    relay_url: Option<String>,
+}
+
+/// What goes into a backup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackupContents {
+    /// Everything in the database.
+    Full,
+    /// Leaves out the backups this node holds for its buddies, so pushing a backup to a buddy
+    /// doesn't nest the buddy's own backups inside it.
+    OwnState,
+}
+
+/// Creates a gzipped tar archive with a consistent snapshot of the database, which also holds the
+/// private key (still encrypted if it is encrypted at rest), and the app configuration. The
+/// archive is built off the async runtime, compressing a large database takes a while.
+///
+/// The p2panda store and blobs are kept in memory only, so there is nothing to save for them: a
+/// restored node gets them back by syncing with its region.
+pub async fn create_backup(
+    pool: &sqlx::Pool<Sqlite>,
+    container: &P2PandaContainer,
+    contents: BackupContents,
+) -> Result<(BackupManifest, Vec<u8>), BackupError> {
+    let dir = tempfile::tempdir().map_err(BackupError::internal)?;
+    let snapshot_path = dir.path().join(DATABASE_FILE);
+
+    // unlike copying the file, VACUUM INTO gives a consistent snapshot while the node keeps writing
+    sqlx::query("VACUUM INTO ?")
+        .bind(snapshot_path.to_string_lossy().to_string())
+        .execute(pool)
+        .await
+        .map_err(BackupError::internal)?;
+
+    if contents == BackupContents::OwnState {
+        strip_buddy_backups(&snapshot_path).await?;
+    }
+
+    let params = container.get_params().await;
+    let manifest = BackupManifest {
+        format_version: FORMAT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: unix_now(),
+        node_id: params
+            .private_key
+            .as_ref()
+            .map(|key| key.public_key().to_hex()),
+    };
+    let config = BackupConfig {
+        network_name: params.network_name,
+        bootstrap_node_id: params.bootstrap_node_id.map(|key| key.to_hex()),
+        relay_url: params.relay_url.map(|url| url.to_string()),
+    };
+
+    let archive_manifest = manifest.clone();
+    let bytes = spawn_blocking(move || {
+        // the snapshot is removed along with `dir`, once it is archived
+        let _dir = dir;
+        build_archive(&archive_manifest, &config, &snapshot_path)
+    })
+    .await
+    .map_err(BackupError::internal)??;
+
+    Ok((manifest, bytes))
+}
+
+/// Replaces the state of this node with the one in `archive`. The archive is checked (and its
+/// database migrated) in a temporary directory first, so a bad archive leaves the node untouched.
+/// The network is left stopped, see `start_restored`.
+pub async fn restore_backup(db: &MainDb, container: &P2PandaContainer, archive: Vec<u8>) -> Result<BackupManifest, BackupError> {
+    let dir = tempfile::tempdir().map_err(BackupError::internal)?;
+    let unpack_dir = dir.path().to_path_buf();
+    let manifest = spawn_blocking(move || unpack(&archive, &unpack_dir))
+        .await
+        .map_err(BackupError::internal)??;
+    let database_path = dir.path().join(DATABASE_FILE);
+
+    check_database(&database_path).await?;
+
+    info!(created_at = manifest.created_at, "Restoring backup");
+
+    container
+        .shutdown()
+        .await
+        .map_err(BackupError::internal)?;
+    replace_database(db.sqlite_pool(), &database_path).await?;
+
+    // the restored key may be encrypted with the passphrase this node runs with
+    let passphrase = match container.get_params().await.key_passphrase {
+        Some(passphrase) => Some(passphrase),
+        None => key_passphrase_from_env().unwrap_or(None),
+    };
+    container.clear_params().await;
+    load_node_params(db, container, passphrase).await;
+
+    Ok(manifest)
+}
+
+/// Starts the network on a restored node, unless its key came back locked.
+pub async fn start_restored(container: &P2PandaContainer) -> Result<(), BackupError> {
+    if container.get_params().await.key_status == PrivateKeyStatus::Locked {
+        return Ok(());
+    }
+
+    container
+        .start()
+        .await
+        .map_err(BackupError::internal)
+}
+
+async fn strip_buddy_backups(path: &Path) -> Result<(), BackupError> {
+    let options = SqliteConnectOptions::new().filename(path);
+    let mut connection = SqliteConnection::connect_with(&options)
+        .await
+        .map_err(BackupError::internal)?;
+
+    for statement in ["DELETE FROM held_backups", "DELETE FROM backup_offers", "VACUUM"] {
+        sqlx::query(statement)
+            .execute(&mut connection)
+            .await
+            .map_err(BackupError::internal)?;
+    }
+
+    connection
+        .close()
+        .await
+        .map_err(BackupError::internal)
+}
+
+fn build_archive(manifest: &BackupManifest, config: &BackupConfig, snapshot_path: &Path) -> Result<Vec<u8>, BackupError> {
+    let mut archive = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    append_json(&mut archive, MANIFEST_FILE, manifest)?;
+    append_json(&mut archive, CONFIG_FILE, config)?;
+    archive
+        .append_path_with_name(snapshot_path, DATABASE_FILE)
+        .map_err(BackupError::internal)?;
+
+    let encoder = archive
+        .into_inner()
+        .map_err(BackupError::internal)?;
+    encoder.finish().map_err(BackupError::internal)
+}
+
+fn append_json<T: Serialize>(archive: &mut tar::Builder<GzEncoder<Vec<u8>>>, name: &str, value: &T) -> Result<(), BackupError> {
+    let data = serde_json::to_vec_pretty(value).map_err(BackupError::internal)?;
+
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o600);
+    header.set_mtime(unix_now() as u64);
+    header.set_cksum();
+
+    archive
+        .append_data(&mut header, name, data.as_slice())
+        .map_err(BackupError::internal)
+}
+
+/// Extracts the known files of the archive into `dir` and returns its manifest.
+fn unpack(archive: &[u8], dir: &Path) -> Result<BackupManifest, BackupError> {
+    let mut archive = tar::Archive::new(GzDecoder::new(archive));
+    let entries = archive.entries().map_err(BackupError::invalid)?;
+
+    for entry in entries {
+        let mut entry = entry.map_err(BackupError::invalid)?;
+        let path = entry
+            .path()
+            .map_err(BackupError::invalid)?
+            .to_path_buf();
+
+        // only take files we know by name, never paths chosen by the archive
+        match path.to_str() {
+            Some(name @ (MANIFEST_FILE | DATABASE_FILE | CONFIG_FILE)) => {
+                entry
+                    .unpack(dir.join(name))
+                    .map_err(BackupError::invalid)?;
+            }
+            _ => return Err(BackupError::Invalid(format!("Unexpected file in backup: {}", path.display()))),
+        }
+    }
+
+    let manifest = std::fs::read(dir.join(MANIFEST_FILE)).map_err(|_| BackupError::Invalid("Backup has no manifest".to_string()))?;
+    let manifest: BackupManifest = serde_json::from_slice(&manifest).map_err(BackupError::invalid)?;
+
+    if manifest.format_version != FORMAT_VERSION {
+        return Err(BackupError::Invalid(format!(
+            "Unsupported backup format version {}",
+            manifest.format_version
+        )));
+    }
+
+    if !dir.join(DATABASE_FILE).exists() {
+        return Err(BackupError::Invalid("Backup has no database".to_string()));
+    }
+
+    Ok(manifest)
+}
+
+/// Makes sure the restored database is intact and one of ours, and migrates it to the current
+/// schema so its tables line up with the live database.
+async fn check_database(path: &Path) -> Result<(), BackupError> {
+    let options = SqliteConnectOptions::new().filename(path);
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(options)
+        .await
+        .map_err(BackupError::invalid)?;
+
+    let integrity: String = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_one(&pool)
+        .await
+        .map_err(BackupError::invalid)?;
+    if integrity != "ok" {
+        return Err(BackupError::Invalid(format!("Backup database is corrupted: {}", integrity)));
+    }
+
+    let migrated: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'")
+        .fetch_one(&pool)
+        .await
+        .map_err(BackupError::invalid)?;
+    if migrated == 0 {
+        return Err(BackupError::Invalid("Backup database is not a lores-node database".to_string()));
+    }
+
+    // fails for a backup made by a newer version, which has migrations we don't know
+    migrate(&pool)
+        .await
+        .map_err(|e| BackupError::Invalid(format!("Backup database can't be migrated: {}", e)))?;
+
+    pool.close().await;
+
+    Ok(())
+}
+
+/// Swaps the content of every table in the live database for the one in the restored database, in
+/// a single transaction.
+pub(crate) async fn replace_database(pool: &sqlx::Pool<Sqlite>, path: &Path) -> Result<(), BackupError> {
+    let mut connection = pool
+        .acquire()
+        .await
+        .map_err(BackupError::internal)?;
+
+    // rows are copied table by table, so references are only consistent once all are in
+    sqlx::query("PRAGMA foreign_keys = OFF")
+        .execute(&mut *connection)
+        .await
+        .map_err(BackupError::internal)?;
+
+    let result = copy_restored_database(&mut connection, path).await;
+
+    // the connection goes back to the pool, so the checks have to be on again whatever happened
+    if let Err(e) = sqlx::query("PRAGMA foreign_keys = ON")
+        .execute(&mut *connection)
+        .await
+    {
+        // drop the connection rather than hand it out without the checks
+        drop(connection.detach());
+        return result.and(Err(BackupError::internal(e)));
+    }
+
+    result
+}
+
+async fn copy_restored_database(connection: &mut sqlx::SqliteConnection, path: &Path) -> Result<(), BackupError> {
+    sqlx::query("ATTACH DATABASE ? AS restored")
+        .bind(path.to_string_lossy().to_string())
+        .execute(&mut *connection)
+        .await
+        .map_err(BackupError::internal)?;
+
+    let result = copy_tables(&mut *connection).await;
+
+    let detached = sqlx::query("DETACH DATABASE restored")
+        .execute(&mut *connection)
+        .await
+        .map_err(BackupError::internal);
+
+    result.and(detached.map(|_| ()))
+}
+
+async fn copy_tables(connection: &mut sqlx::SqliteConnection) -> Result<(), BackupError> {
+    let tables: Vec<String> = sqlx::query_scalar("SELECT name FROM main.sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'")
+        .fetch_all(&mut *connection)
+        .await
+        .map_err(BackupError::internal)?;
+
+    let mut transaction = connection
+        .begin()
+        .await
+        .map_err(BackupError::internal)?;
+
+    for table in tables {
+        sqlx::query(&format!("DELETE FROM main.\"{}\"", table))
+            .execute(&mut *transaction)
+            .await
+            .map_err(BackupError::internal)?;
+        sqlx::query(&format!("INSERT INTO main.\"{0}\" SELECT * FROM restored.\"{0}\"", table))
+            .execute(&mut *transaction)
+            .await
+            .map_err(BackupError::internal)?;
+    }
+
+    transaction
+        .commit()
+        .await
+        .map_err(BackupError::internal)
+}
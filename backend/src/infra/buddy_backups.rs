@@ -0,0 +1,162 @@
+use std::time::Duration;
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::tokio::time::sleep;
+use rocket::{Orbit, Rocket};
+use rocket_db_pools::Database;
+use sqlx::Sqlite;
+use tracing::{error, info};
+
+use super::backup::{create_backup, restore_backup, start_restored, BackupContents, BackupError, BackupManifest};
+use super::crypto::{decrypt_with_passphrase, encrypt_with_passphrase, EncryptedBlob};
+use super::db::MainDb;
+use super::supervisor::Supervisor;
+use super::time::unix_now;
+use crate::panda_comms::container::{build_hash_from_hex, P2PandaContainer};
+use crate::repos::buddy_backups::{BuddyBackupsRepo, MAX_HELD_BACKUP_BYTES};
+
+/// How often a backup is pushed to the buddies.
+pub const BACKUP_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long after start an overdue backup is pushed, giving the node time to reach its buddies.
+pub const OVERDUE_BACKUP_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// Name the scheduled backups are supervised under.
+pub const BUDDY_BACKUPS_TASK: &str = "buddy_backups";
+
+/// Env var holding the passphrase backups for buddies are encrypted with. Scheduled backups only
+/// run while it is set. Keep it somewhere safe off the node, it is needed to restore.
+pub const BACKUP_PASSPHRASE_ENV: &str = "LORES_BACKUP_PASSPHRASE";
+
+pub fn backup_passphrase_from_env() -> Option<String> {
+    std::env::var(BACKUP_PASSPHRASE_ENV).ok()
+}
+
+/// Encrypts a backup of this node's own state, adds it as a blob and asks every buddy to keep it.
+/// Returns the hash of the blob.
+pub async fn push_backup(pool: &sqlx::Pool<Sqlite>, container: &P2PandaContainer, passphrase: &str) -> Result<String, BackupError> {
+    let buddies = BuddyBackupsRepo::init()
+        .buddies(pool)
+        .await
+        .map_err(|e| BackupError::InternalServerError(e.to_string()))?;
+
+    if buddies.is_empty() {
+        return Err(BackupError::Invalid("No backup buddies chosen".to_string()));
+    }
+
+    let (manifest, archive) = create_backup(pool, container, BackupContents::OwnState).await?;
+    let encrypted = encrypt_with_passphrase(passphrase, &archive).map_err(|e| BackupError::InternalServerError(e.to_string()))?;
+    let data = serde_json::to_vec(&encrypted).map_err(|e| BackupError::InternalServerError(e.to_string()))?;
+
+    let blob_hash = container
+        .add_blob(data)
+        .await
+        .map_err(|e| BackupError::InternalServerError(e.to_string()))?
+        .to_hex();
+
+    for buddy in buddies {
+        container
+            .store_backup_with(buddy.node_id, blob_hash.clone(), manifest.created_at)
+            .await
+            .map_err(|e| BackupError::InternalServerError(e.to_string()))?;
+    }
+
+    BuddyBackupsRepo::init()
+        .record_push(pool, &blob_hash)
+        .await
+        .map_err(|e| BackupError::InternalServerError(e.to_string()))?;
+
+    info!(blob_hash, "Pushed backup to buddies");
+
+    Ok(blob_hash)
+}
+
+/// Downloads a backup that a buddy offered, decrypts it and restores it onto this node.
+pub async fn restore_from_buddy(
+    db: &MainDb,
+    container: &P2PandaContainer,
+    blob_hash: String,
+    passphrase: &str,
+) -> Result<BackupManifest, BackupError> {
+    let hash = build_hash_from_hex(blob_hash).ok_or(BackupError::Invalid("Invalid blob hash".to_string()))?;
+
+    let data = container
+        .fetch_blob(hash, MAX_HELD_BACKUP_BYTES)
+        .await
+        .map_err(|e| BackupError::InternalServerError(e.to_string()))?;
+    let encrypted: EncryptedBlob = serde_json::from_slice(&data).map_err(|e| BackupError::Invalid(e.to_string()))?;
+    let archive = decrypt_with_passphrase(passphrase, &encrypted).map_err(|e| BackupError::Invalid(e.to_string()))?;
+
+    let manifest = restore_backup(db, container, archive).await?;
+    start_restored(container).await?;
+
+    Ok(manifest)
+}
+
+/// How long to wait before the next scheduled push, given when the last one was made.
+pub fn next_push_in(last_pushed_at: Option<i64>, now: i64) -> Duration {
+    let Some(last_pushed_at) = last_pushed_at else {
+        return OVERDUE_BACKUP_DELAY;
+    };
+
+    let due_at = last_pushed_at.saturating_add(BACKUP_INTERVAL.as_secs() as i64);
+    let due_in = Duration::from_secs(due_at.saturating_sub(now).max(0) as u64);
+
+    due_in.max(OVERDUE_BACKUP_DELAY)
+}
+
+/// Pushes a backup to the buddies every `BACKUP_INTERVAL`, counted from the last push so that
+/// restarting the node doesn't put the next one off.
+#[derive(Default)]
+pub struct BuddyBackupFairing {}
+
+#[rocket::async_trait]
+impl Fairing for BuddyBackupFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "BuddyBackupFairing",
+            kind: Kind::Liftoff | Kind::Singleton,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let container = rocket.state::<P2PandaContainer>().cloned();
+        let supervisor = rocket.state::<Supervisor>();
+
+        if let (Some(db), Some(container), Some(supervisor)) = (MainDb::fetch(rocket), container, supervisor) {
+            let db_pool = db.sqlite_pool().clone();
+
+            supervisor.spawn(BUDDY_BACKUPS_TASK, move || {
+                let db_pool = db_pool.clone();
+                let container = container.clone();
+
+                async move {
+                    // read again after a crash, so a restart doesn't put the next push off
+                    let last_pushed_at = BuddyBackupsRepo::init()
+                        .last_pushed_at(&db_pool)
+                        .await
+                        .unwrap_or_else(|e| {
+                            error!(error = %e, "Failed to read when the last backup was pushed");
+                            None
+                        });
+                    let mut delay = next_push_in(last_pushed_at, unix_now());
+
+                    loop {
+                        sleep(delay).await;
+                        delay = BACKUP_INTERVAL;
+
+                        let Some(passphrase) = backup_passphrase_from_env() else {
+                            continue;
+                        };
+
+                        if let Err(e) = push_backup(&db_pool, &container, &passphrase).await {
+                            error!(error = %e, "Scheduled backup failed");
+                        }
+                    }
+                }
+            });
+        } else {
+            error!("MainDb, P2PandaContainer or Supervisor state not found, won't schedule backups");
+        }
+    }
+}
@@ -15,6 +15,8 @@ pub fn cors_fairing() -> rocket_cors::Cors {
             "Accept",
             "Content-type",
         ]))
+        // lets browser clients see that a route is deprecated, see `DeprecationFairing`
+        .expose_headers(["Deprecation", "Link"].iter().map(ToString::to_string).collect())
         .allow_credentials(true);
 
     cors.to_cors().unwrap()
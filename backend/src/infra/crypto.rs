@@ -0,0 +1,61 @@
+use anyhow::{anyhow, Result};
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand::RngCore;
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+const SALT_LEN: usize = 16;
+
+/// Data encrypted with a key derived from a passphrase (Argon2id + XChaCha20-Poly1305). Every
+/// field is hex so it can be stored in the DB or sent as JSON as is.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct EncryptedBlob {
+    pub salt_hex: String,
+    pub nonce_hex: String,
+    pub ciphertext_hex: String,
+}
+
+pub fn encrypt_with_passphrase(passphrase: &str, plaintext: &[u8]) -> Result<EncryptedBlob> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+
+    let cipher = cipher_for(passphrase, &salt)?;
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| anyhow!("Failed to encrypt"))?;
+
+    Ok(EncryptedBlob {
+        salt_hex: hex::encode(salt),
+        nonce_hex: hex::encode(nonce),
+        ciphertext_hex: hex::encode(ciphertext),
+    })
+}
+
+/// Fails both for a wrong passphrase and for tampered data, without telling the two apart.
+pub fn decrypt_with_passphrase(passphrase: &str, blob: &EncryptedBlob) -> Result<Vec<u8>> {
+    let salt = hex::decode(&blob.salt_hex)?;
+    let nonce = hex::decode(&blob.nonce_hex)?;
+    let ciphertext = hex::decode(&blob.ciphertext_hex)?;
+
+    if nonce.len() != 24 {
+        return Err(anyhow!("Invalid nonce"));
+    }
+
+    let cipher = cipher_for(passphrase, &salt)?;
+    cipher
+        .decrypt(XNonce::from_slice(&nonce), ciphertext.as_ref())
+        .map_err(|_| anyhow!("Wrong passphrase or corrupted data"))
+}
+
+fn cipher_for(passphrase: &str, salt: &[u8]) -> Result<XChaCha20Poly1305> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Failed to derive key: {}", e))?;
+
+    Ok(XChaCha20Poly1305::new(&key.into()))
+}
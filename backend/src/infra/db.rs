@@ -1,6 +1,13 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use rocket::serde::Serialize;
 use rocket::{fairing, Build, Rocket};
 use rocket_db_pools::Database;
+use sqlx::migrate::{Migrate, MigrateError};
 use sqlx::Sqlite;
+use tracing::info;
+use utoipa::ToSchema;
 
 #[derive(Database)]
 #[database("main_db")]
@@ -14,11 +21,9 @@ impl MainDb {
 
 pub async fn run_migrations(rocket: Rocket<Build>) -> fairing::Result {
     if let Some(db) = MainDb::fetch(&rocket) {
-        println!("Running DB migrations");
+        info!("Running DB migrations");
 
-        // run migrations using `db`. get the inner type with &db.0.
-        sqlx::migrate!("./migrations")
-            .run(&db.0)
+        migrate(db.sqlite_pool())
             .await
             .expect("Error running DB migrations");
 
@@ -27,3 +32,67 @@ pub async fn run_migrations(rocket: Rocket<Build>) -> fairing::Result {
         Err(rocket)
     }
 }
+
+/// The file of the SQLite database at `database_url`.
+pub fn database_path(database_url: &str) -> PathBuf {
+    let path = database_url
+        .trim_start_matches("sqlite:")
+        .trim_start_matches("//");
+    // drop query options like `?mode=rwc`
+    PathBuf::from(path.split('?').next().unwrap_or(path))
+}
+
+/// Where the projections of a region other than the first are kept: next to the main database,
+/// e.g. `/data/site-manager.region-2.db` for `sqlite:/data/site-manager.db`.
+pub fn region_database_path(database_url: &str, region_id: i64) -> PathBuf {
+    let path = database_path(database_url);
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("lores-node");
+    let file_name = match path
+        .extension()
+        .and_then(|extension| extension.to_str())
+    {
+        Some(extension) => format!("{}.region-{}.{}", stem, region_id, extension),
+        None => format!("{}.region-{}", stem, region_id),
+    };
+
+    path.parent()
+        .unwrap_or(Path::new(""))
+        .join(file_name)
+}
+
+/// Brings a database up to the current schema. Also used on a restored backup before it replaces
+/// the live database.
+pub async fn migrate(pool: &sqlx::Pool<Sqlite>) -> Result<(), MigrateError> {
+    sqlx::migrate!("./migrations").run(pool).await
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+}
+
+/// Every migration this build knows about, and whether it has been applied to the database.
+pub async fn migration_status(pool: &sqlx::Pool<Sqlite>) -> Result<Vec<MigrationStatus>, MigrateError> {
+    let mut connection = pool.acquire().await?;
+    let applied: HashSet<i64> = connection
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect();
+
+    Ok(sqlx::migrate!("./migrations")
+        .iter()
+        .map(|migration| MigrationStatus {
+            version: migration.version,
+            description: migration.description.to_string(),
+            applied: applied.contains(&migration.version),
+        })
+        .collect())
+}
@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::{Request, Response};
+
+/// Adds a `Deprecation` header to responses from deprecated routes, with a `Link` to the same
+/// path in the version that replaces them, so clients find out before the routes go away.
+#[derive(Default)]
+pub struct DeprecationFairing {
+    /// Successor mount point for each deprecated one.
+    successors: HashMap<String, String>,
+}
+
+impl DeprecationFairing {
+    /// Marks the routes mounted at `base` as deprecated in favour of those at `successor`.
+    pub fn deprecate(&mut self, base: String, successor: String) {
+        self.successors.insert(base, successor);
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for DeprecationFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "DeprecationFairing",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let Some(route) = request.route() else {
+            return;
+        };
+        let base = route.uri.base();
+        let Some(successor) = self.successors.get(base) else {
+            return;
+        };
+
+        let path = request.uri().path();
+        let successor_path = format!("{}{}", successor, path.as_str().strip_prefix(base).unwrap_or(""));
+
+        response.set_raw_header("Deprecation", "true");
+        response.set_raw_header("Link", format!("<{}>; rel=\"successor-version\"", successor_path));
+    }
+}
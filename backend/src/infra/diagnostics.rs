@@ -0,0 +1,115 @@
+use p2panda_net::NodeAddress;
+use rocket::serde::Serialize;
+use sqlx::Sqlite;
+use tracing::warn;
+use utoipa::ToSchema;
+
+use super::buddy_backups::backup_passphrase_from_env;
+use super::db::{migration_status, MigrationStatus};
+use super::logging::{log_buffer, LogQuery, LogRecord};
+use super::settings::Settings;
+use super::time::unix_now;
+use crate::panda_comms::container::{ContainerState, P2PandaContainer, SyncFailure};
+use crate::panda_comms::identity::{key_passphrase_from_env, PrivateKeyStatus};
+use crate::panda_comms::presence::NodePresence;
+use crate::routes::api_versions;
+
+/// How many of the most recent warnings and errors go into a bundle.
+const PROBLEMS_KEPT: usize = 200;
+
+/// What someone helping an operator needs to know about a node. Holds no secrets: no keys, no
+/// passphrases, only whether they are set.
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct Diagnostics {
+    pub generated_at: i64,
+    pub versions: Versions,
+    /// With the webhook URL redacted.
+    pub settings: Settings,
+    pub secrets: SecretsSet,
+    pub status: NodeDiagnostics,
+    #[schema(value_type = Vec<Object>)]
+    pub peers: Vec<NodeAddress>,
+    pub presence: Vec<NodePresence>,
+    pub recent_sync_failures: Vec<SyncFailure>,
+    pub migrations: Vec<MigrationStatus>,
+    /// The most recent warnings and errors logged, oldest first.
+    pub recent_problems: Vec<LogRecord>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct Versions {
+    pub node: String,
+    pub api: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct SecretsSet {
+    pub key_passphrase: bool,
+    pub backup_passphrase: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct NodeDiagnostics {
+    pub state: ContainerState,
+    pub key_status: PrivateKeyStatus,
+    pub node_id: Option<String>,
+    pub network_name: Option<String>,
+    pub bootstrap_node_id: Option<String>,
+}
+
+/// Gathers the diagnostics of the node. Parts that can't be read are left empty, as a bundle is
+/// most needed when something is broken.
+pub async fn collect(pool: &sqlx::Pool<Sqlite>, container: &P2PandaContainer, settings: &Settings) -> Diagnostics {
+    let params = container.get_params().await;
+
+    // there are none while the network is not running, which the status already says
+    let peers = container.known_peers().await.unwrap_or_default();
+
+    let migrations = migration_status(pool).await.unwrap_or_else(|e| {
+        warn!(error = %e, "Failed to read migrations for diagnostics");
+        vec![]
+    });
+
+    let problems = LogQuery {
+        level: None,
+        target: None,
+        contains: None,
+        limit: Some(PROBLEMS_KEPT),
+    };
+
+    Diagnostics {
+        generated_at: unix_now(),
+        versions: Versions {
+            node: env!("CARGO_PKG_VERSION").to_string(),
+            api: api_versions()
+                .iter()
+                .map(|version| version.name.to_string())
+                .collect(),
+        },
+        settings: settings.redacted(),
+        secrets: SecretsSet {
+            key_passphrase: key_passphrase_from_env().is_ok_and(|passphrase| passphrase.is_some()),
+            backup_passphrase: backup_passphrase_from_env().is_some(),
+        },
+        status: NodeDiagnostics {
+            state: container.state().await,
+            key_status: params.key_status,
+            node_id: params
+                .private_key
+                .map(|private_key| private_key.public_key().to_hex()),
+            network_name: params.network_name,
+            bootstrap_node_id: params
+                .bootstrap_node_id
+                .map(|node_id| node_id.to_hex()),
+        },
+        peers,
+        presence: container.presence().all().await,
+        recent_sync_failures: container.recent_sync_failures().await,
+        migrations,
+        recent_problems: log_buffer().records(&problems, Some(tracing::Level::WARN)),
+    }
+}
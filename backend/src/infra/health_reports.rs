@@ -0,0 +1,164 @@
+use std::time::Duration;
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::tokio::time::sleep;
+use rocket::{Orbit, Rocket};
+use rocket_db_pools::Database;
+use sqlx::{Sqlite, SqliteConnection};
+use tracing::{debug, error, warn};
+
+use super::db::MainDb;
+use super::supervisor::Supervisor;
+use super::time::unix_now;
+use crate::panda_comms::container::{ContainerState, P2PandaContainer};
+use crate::panda_comms::lores_events::NodeHealthReported;
+use crate::repos::entities::{NodeHealth, RegionNode};
+use crate::repos::nodes::{NodesError, NodesRepo};
+use crate::repos::system::{SystemError, SystemRepo};
+
+/// How often each node reports its health to the region.
+pub const HEALTH_REPORT_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Name the reporting loop is supervised under.
+pub const HEALTH_REPORTS_TASK: &str = "health_reports";
+
+/// How long after the node joins a region, or starts in one, its first report is sent. Leaves the
+/// system monitor time to take a sample.
+const FIRST_REPORT_DELAY: Duration = Duration::from_secs(60);
+
+/// A node needs attention once it missed this many reports in a row.
+const MISSED_REPORTS: u64 = 3;
+
+/// The report of this node right now, from its latest system sample and open alerts.
+pub async fn current_report(pool: &sqlx::Pool<Sqlite>) -> Result<NodeHealthReported, SystemError> {
+    let repo = SystemRepo::init();
+    let now = unix_now();
+
+    // a sample older than that means the monitor can't read the host anymore
+    let sample = repo
+        .latest_sample(pool, now - HEALTH_REPORT_INTERVAL.as_secs() as i64)
+        .await?;
+    let open_alerts = repo
+        .open_alerts(pool)
+        .await?
+        .into_iter()
+        .map(|alert| alert.kind)
+        .collect();
+
+    Ok(NodeHealthReported {
+        reported_at: now,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        uptime_secs: sample.as_ref().map(|sample| sample.uptime_secs),
+        disk_free_bytes: sample
+            .as_ref()
+            .map(|sample| sample.disk_free_bytes),
+        disk_used_percent: sample
+            .as_ref()
+            .map(|sample| sample.disk_used_percent),
+        load_1m: sample.as_ref().map(|sample| sample.load_1m),
+        open_alerts,
+    })
+}
+
+impl From<NodeHealthReported> for NodeHealth {
+    fn from(report: NodeHealthReported) -> Self {
+        NodeHealth {
+            reported_at: report.reported_at,
+            version: report.version,
+            uptime_secs: report.uptime_secs,
+            disk_free_bytes: report.disk_free_bytes,
+            disk_used_percent: report.disk_used_percent,
+            load_1m: report.load_1m,
+            open_alerts: report.open_alerts,
+        }
+    }
+}
+
+/// Why the site of a node needs attention at `now`: it stopped reporting, or reported open alerts.
+pub fn attention(health: Option<&NodeHealth>, now: i64) -> Vec<String> {
+    let Some(health) = health else {
+        return vec!["no_report".to_string()];
+    };
+
+    let mut reasons = vec![];
+    if now - health.reported_at > (MISSED_REPORTS * HEALTH_REPORT_INTERVAL.as_secs()) as i64 {
+        reasons.push("no_recent_report".to_string());
+    }
+    reasons.extend(health.open_alerts.iter().cloned());
+
+    reasons
+}
+
+/// The nodes of a region with their latest health report, from the database its events are
+/// projected into.
+pub async fn region_nodes(connection: &mut SqliteConnection) -> Result<Vec<RegionNode>, NodesError> {
+    let now = unix_now();
+
+    let nodes = NodesRepo::init()
+        .all_with_health(connection)
+        .await?
+        .into_iter()
+        .map(|(node, health)| RegionNode {
+            attention: attention(health.as_ref(), now),
+            id: node.id,
+            name: node.name,
+            health,
+        })
+        .collect();
+
+    Ok(nodes)
+}
+
+/// Publishes a health report every `HEALTH_REPORT_INTERVAL` while the node is in a region.
+#[derive(Default)]
+pub struct HealthReportFairing {}
+
+#[rocket::async_trait]
+impl Fairing for HealthReportFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "HealthReportFairing",
+            kind: Kind::Liftoff | Kind::Singleton,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let container = rocket.state::<P2PandaContainer>().cloned();
+        let supervisor = rocket.state::<Supervisor>();
+
+        let (Some(db), Some(container), Some(supervisor)) = (MainDb::fetch(rocket), container, supervisor) else {
+            error!("MainDb, P2PandaContainer or Supervisor state not found, won't report health");
+            return;
+        };
+        let db_pool = db.sqlite_pool().clone();
+
+        supervisor.spawn(HEALTH_REPORTS_TASK, move || {
+            let db_pool = db_pool.clone();
+            let container = container.clone();
+
+            async move {
+                let mut delay = FIRST_REPORT_DELAY;
+
+                loop {
+                    sleep(delay).await;
+
+                    if container.state().await != ContainerState::Running {
+                        debug!("Not in a region, skipping health report");
+                        delay = FIRST_REPORT_DELAY;
+                        continue;
+                    }
+                    delay = HEALTH_REPORT_INTERVAL;
+
+                    let result = match current_report(&db_pool).await {
+                        Ok(report) => container.report_health(report).await,
+                        Err(e) => Err(e.into()),
+                    };
+
+                    if let Err(e) = result {
+                        warn!(error = %e, "Failed to report health");
+                    }
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,356 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{LazyLock, Mutex};
+use std::time::Instant;
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::route::{self, Handler};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::tokio::sync::broadcast;
+use rocket::{Data, Request, Response, Route};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{info, info_span, Event, Instrument, Level, Span, Subscriber};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt as log_fmt, EnvFilter, Layer};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use super::settings::{LogFormat, Settings};
+use super::time::unix_now;
+
+/// How many log records the node keeps for `/admin/logs`.
+const LOG_BUFFER_SIZE: usize = 2000;
+
+/// Sends the logs of the node, and of Rocket and the libraries it uses, to stdout as set in the
+/// settings, and keeps the most recent ones in the [`log_buffer`].
+pub fn init(settings: &Settings) {
+    // the filter is validated with the other settings
+    let filter = EnvFilter::new(&settings.log_filter);
+    let text = (settings.log_format == LogFormat::Text).then(log_fmt::layer);
+    let json = (settings.log_format == LogFormat::Json).then(|| log_fmt::layer().json());
+
+    let result = tracing_subscriber::registry()
+        .with(filter)
+        .with(text)
+        .with(json)
+        .with(LogBufferLayer)
+        .try_init();
+
+    if let Err(e) = result {
+        eprintln!("Logging was set up already: {}", e);
+    }
+}
+
+/// A log record as kept in the [`LogBuffer`].
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct LogRecord {
+    /// Increases by one with every record, so a client can tell whether it missed some.
+    pub id: u64,
+    pub timestamp: i64,
+    pub level: String,
+    /// Module the record comes from, e.g. `lores_node::panda_comms::container`.
+    pub target: String,
+    /// Names of the spans the record was logged in, outermost first.
+    pub spans: Vec<String>,
+    pub message: String,
+    /// Fields of the record and of the spans it was logged in, e.g. the `request_id`.
+    pub fields: BTreeMap<String, String>,
+}
+
+/// Which records to return, all of them by default.
+#[derive(FromForm, Default, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LogQuery {
+    /// Least severe level to include, e.g. `warn` for warnings and errors.
+    pub level: Option<String>,
+    /// Only records from modules starting with this, e.g. `lores_node::events`.
+    pub target: Option<String>,
+    /// Only records whose message or fields contain this.
+    pub contains: Option<String>,
+    /// Only the most recent records, up to this many.
+    pub limit: Option<usize>,
+}
+
+impl LogQuery {
+    /// The least severe level to include, or an error naming the unknown level.
+    pub fn min_level(&self) -> Result<Option<Level>, String> {
+        self.level
+            .as_deref()
+            .map(|level| Level::from_str(level).map_err(|_| format!("Unknown log level {}", level)))
+            .transpose()
+    }
+
+    pub fn matches(&self, min_level: Option<Level>, record: &LogRecord) -> bool {
+        // more verbose levels compare as greater
+        let level_matches = match (min_level, Level::from_str(&record.level)) {
+            (Some(min_level), Ok(level)) => level <= min_level,
+            _ => true,
+        };
+        let target_matches = self
+            .target
+            .as_ref()
+            .is_none_or(|target| record.target.starts_with(target));
+        let text_matches = self.contains.as_ref().is_none_or(|text| {
+            record.message.contains(text)
+                || record
+                    .fields
+                    .values()
+                    .any(|value| value.contains(text))
+        });
+
+        level_matches && target_matches && text_matches
+    }
+}
+
+/// The most recent log records, so they can be read without access to the container's output.
+pub struct LogBuffer {
+    kept: Mutex<KeptRecords>,
+    live: broadcast::Sender<LogRecord>,
+}
+
+struct KeptRecords {
+    records: VecDeque<LogRecord>,
+    next_id: u64,
+}
+
+static LOG_BUFFER: LazyLock<LogBuffer> = LazyLock::new(|| LogBuffer {
+    kept: Mutex::new(KeptRecords {
+        records: VecDeque::with_capacity(LOG_BUFFER_SIZE),
+        next_id: 0,
+    }),
+    live: broadcast::channel(256).0,
+});
+
+pub fn log_buffer() -> &'static LogBuffer {
+    &LOG_BUFFER
+}
+
+impl LogBuffer {
+    fn push(&self, mut record: LogRecord) {
+        // a poisoned lock only means another thread panicked while logging
+        let mut kept = self
+            .kept
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        record.id = kept.next_id;
+        kept.next_id += 1;
+
+        if kept.records.len() == LOG_BUFFER_SIZE {
+            kept.records.pop_front();
+        }
+        kept.records.push_back(record.clone());
+
+        // sent under the lock, so live records arrive in order. Nobody may be listening.
+        let _ = self.live.send(record);
+    }
+
+    /// The kept records matching `query`, oldest first.
+    pub fn records(&self, query: &LogQuery, min_level: Option<Level>) -> Vec<LogRecord> {
+        let kept = self
+            .kept
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let mut matching: Vec<LogRecord> = kept
+            .records
+            .iter()
+            .filter(|record| query.matches(min_level, record))
+            .cloned()
+            .collect();
+
+        if let Some(limit) = query.limit {
+            matching.drain(..matching.len().saturating_sub(limit));
+        }
+        matching
+    }
+
+    /// Records as they are logged from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<LogRecord> {
+        self.live.subscribe()
+    }
+}
+
+/// Copies every log record that passes the filter into the [`LogBuffer`].
+pub(crate) struct LogBufferLayer;
+
+/// Fields a span was opened with, kept to add them to the records logged in it.
+struct SpanFields(BTreeMap<String, String>);
+
+impl<S> Layer<S> for LogBufferLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        attrs.record(&mut visitor);
+
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut()
+                .insert(SpanFields(visitor.fields));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        let mut spans = vec![];
+
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                spans.push(span.name().to_string());
+                if let Some(SpanFields(fields)) = span.extensions().get::<SpanFields>() {
+                    visitor.fields.extend(fields.clone());
+                }
+            }
+        }
+        // the record's own fields win over those of its spans
+        event.record(&mut visitor);
+
+        log_buffer().push(LogRecord {
+            id: 0,
+            timestamp: unix_now(),
+            level: event.metadata().level().to_string(),
+            target: event.metadata().target().to_string(),
+            spans,
+            message: visitor.message,
+            fields: visitor.fields,
+        });
+    }
+}
+
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    fields: BTreeMap<String, String>,
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record(field, format!("{:?}", value));
+    }
+}
+
+impl FieldVisitor {
+    fn record(&mut self, field: &Field, value: String) {
+        if field.name() == "message" {
+            self.message = value;
+        } else {
+            self.fields
+                .insert(field.name().to_string(), value);
+        }
+    }
+}
+
+/// Replaces a secret in logs, e.g. `passphrase = ?Redacted`.
+#[derive(Debug)]
+pub struct Redacted;
+
+/// Response header holding the id of the request, as logged in its span.
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+struct RequestSpan {
+    span: Span,
+    request_id: String,
+    started_at: Instant,
+}
+
+impl RequestSpan {
+    /// For a request the fairing didn't see.
+    fn none() -> Self {
+        RequestSpan {
+            span: Span::none(),
+            request_id: String::new(),
+            started_at: Instant::now(),
+        }
+    }
+}
+
+/// The span of the request being handled, so what is logged about it can be found together.
+pub fn request_span(request: &Request<'_>) -> Span {
+    request
+        .local_cache(RequestSpan::none)
+        .span
+        .clone()
+}
+
+/// Runs the handlers of `routes` in the span of their request, so what they log carries it. The
+/// span is only opened by the [`RequestSpanFairing`].
+pub fn in_request_span(routes: Vec<Route>) -> Vec<Route> {
+    routes
+        .into_iter()
+        .map(|mut route| {
+            route.handler = Box::new(InRequestSpan(route.handler));
+            route
+        })
+        .collect()
+}
+
+#[derive(Clone)]
+struct InRequestSpan(Box<dyn Handler>);
+
+#[rocket::async_trait]
+impl Handler for InRequestSpan {
+    async fn handle<'r>(&self, request: &'r Request<'_>, data: Data<'r>) -> route::Outcome<'r> {
+        self.0
+            .handle(request, data)
+            .instrument(request_span(request))
+            .await
+    }
+}
+
+/// Opens a span for every HTTP request, with an id also sent back as `X-Request-Id`, and logs how
+/// it was answered.
+pub struct RequestSpanFairing;
+
+#[rocket::async_trait]
+impl Fairing for RequestSpanFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "RequestSpanFairing",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let request_id = Uuid::new_v4().to_string();
+        let span = info_span!("request", request_id, method = %request.method(), path = %request.uri().path());
+
+        request.local_cache(|| RequestSpan {
+            span,
+            request_id,
+            started_at: Instant::now(),
+        });
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let RequestSpan {
+            span,
+            request_id,
+            started_at,
+        } = request.local_cache(RequestSpan::none);
+        if !request_id.is_empty() {
+            response.set_header(Header::new(REQUEST_ID_HEADER, request_id.clone()));
+        }
+        let route = request
+            .route()
+            .map(|route| route.uri.as_str())
+            .unwrap_or("unmatched");
+
+        span.in_scope(|| {
+            info!(
+                route,
+                status = response.status().code,
+                elapsed_ms = started_at.elapsed().as_millis() as u64,
+                "Answered request"
+            )
+        });
+    }
+}
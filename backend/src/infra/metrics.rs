@@ -0,0 +1,221 @@
+use std::sync::LazyLock;
+
+use prometheus::{Encoder, HistogramOpts, HistogramTimer, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::ContentType;
+use rocket::{Request, Response, Route, State};
+use tracing::error;
+
+use crate::panda_comms::container::{ContainerState, P2PandaContainer};
+use crate::panda_comms::regions::Regions;
+
+const CONTAINER_STATES: [&str; 5] = ["unconfigured", "starting", "running", "stopping", "failed"];
+
+/// Everything `/metrics` reports. There is one set per process, as there is one node per process
+/// outside of the tests. The gauges of the P2Panda node of each region are labelled with the
+/// region id.
+pub struct Metrics {
+    registry: Registry,
+    container_state: IntGaugeVec,
+    peers: IntGauge,
+    gossip_neighbours: IntGaugeVec,
+    syncs: IntCounterVec,
+    events: IntCounterVec,
+    event_queue_depth: IntGauge,
+    db_query_seconds: HistogramVec,
+    http_requests: IntCounterVec,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("lores".to_string()), None).expect("metrics registry");
+
+        let metrics = Metrics {
+            container_state: IntGaugeVec::new(
+                Opts::new("container_state", "1 for the state the P2Panda node of a region is in, 0 for the others"),
+                &["region", "state"],
+            )
+            .expect("metric"),
+            peers: IntGauge::new("peers", "Peers known to the network").expect("metric"),
+            gossip_neighbours: IntGaugeVec::new(Opts::new("gossip_neighbours", "Peers we are gossiping with directly"), &["region"]).expect("metric"),
+            syncs: IntCounterVec::new(Opts::new("syncs_total", "Log syncs with a peer, by outcome"), &["peer", "outcome"]).expect("metric"),
+            events: IntCounterVec::new(Opts::new("events_total", "Region events, by type and outcome"), &["event", "outcome"]).expect("metric"),
+            event_queue_depth: IntGauge::new("event_queue_depth", "Received events waiting to be handled").expect("metric"),
+            db_query_seconds: HistogramVec::new(
+                HistogramOpts::new("db_query_duration_seconds", "Time taken by database operations")
+                    .buckets(vec![0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0]),
+                &["query"],
+            )
+            .expect("metric"),
+            http_requests: IntCounterVec::new(
+                Opts::new("http_requests_total", "HTTP requests, by route and status"),
+                &["method", "route", "status"],
+            )
+            .expect("metric"),
+            registry,
+        };
+
+        metrics.register();
+        metrics
+    }
+
+    fn register(&self) {
+        let collectors: [Box<dyn prometheus::core::Collector>; 8] = [
+            Box::new(self.container_state.clone()),
+            Box::new(self.peers.clone()),
+            Box::new(self.gossip_neighbours.clone()),
+            Box::new(self.syncs.clone()),
+            Box::new(self.events.clone()),
+            Box::new(self.event_queue_depth.clone()),
+            Box::new(self.db_query_seconds.clone()),
+            Box::new(self.http_requests.clone()),
+        ];
+
+        for collector in collectors {
+            self.registry
+                .register(collector)
+                .expect("unique metric");
+        }
+    }
+
+    pub fn set_gossip_neighbours(&self, region_id: i64, count: usize) {
+        self.gossip_neighbours
+            .with_label_values(&[&region_id.to_string()])
+            .set(count as i64);
+    }
+
+    pub fn gossip_neighbour_up(&self, region_id: i64) {
+        self.gossip_neighbours
+            .with_label_values(&[&region_id.to_string()])
+            .inc();
+    }
+
+    pub fn gossip_neighbour_down(&self, region_id: i64) {
+        self.gossip_neighbours
+            .with_label_values(&[&region_id.to_string()])
+            .dec();
+    }
+
+    /// Drops the gauges of a region this node left.
+    pub fn forget_region(&self, region_id: i64) {
+        let region = region_id.to_string();
+        // a region whose node never got going has no gauges to remove
+        let _ = self
+            .gossip_neighbours
+            .remove_label_values(&[&region]);
+        for state in CONTAINER_STATES {
+            let _ = self
+                .container_state
+                .remove_label_values(&[&region, state]);
+        }
+    }
+
+    /// Counts a sync with `peer`, `outcome` being "started", "done" or "failed".
+    pub fn sync(&self, peer: &str, outcome: &str) {
+        self.syncs
+            .with_label_values(&[peer, outcome])
+            .inc();
+    }
+
+    /// Counts an event, `outcome` being "received", "processed" or "failed".
+    pub fn event(&self, event: &str, outcome: &str) {
+        self.events
+            .with_label_values(&[event, outcome])
+            .inc();
+    }
+
+    /// Times a database operation until the returned timer is dropped.
+    pub fn time_query(&self, query: &str) -> HistogramTimer {
+        self.db_query_seconds
+            .with_label_values(&[query])
+            .start_timer()
+    }
+
+    /// Reads what the containers know right now, `first` being the one of the first region, and
+    /// renders all metrics in the Prometheus text format.
+    async fn render(&self, first: &P2PandaContainer, others: &[P2PandaContainer]) -> String {
+        for container in std::iter::once(first).chain(others) {
+            let region = container.region_id().to_string();
+            let state = container.state().await;
+            for name in CONTAINER_STATES {
+                self.container_state
+                    .with_label_values(&[&region, name])
+                    .set((state_name(&state) == name) as i64);
+            }
+        }
+
+        let peers = first
+            .known_peers()
+            .await
+            .map(|peers| peers.len())
+            .unwrap_or(0);
+        self.peers.set(peers as i64);
+        self.event_queue_depth
+            .set(first.event_queue_depth() as i64);
+
+        let mut buffer = Vec::new();
+        if let Err(err) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            error!(error = %err, "Failed to encode metrics");
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+fn state_name(state: &ContainerState) -> &'static str {
+    match state {
+        ContainerState::Unconfigured => "unconfigured",
+        ContainerState::Starting => "starting",
+        ContainerState::Running => "running",
+        ContainerState::Stopping => "stopping",
+        ContainerState::Failed(_) => "failed",
+    }
+}
+
+/// Counts every HTTP request by the route that handled it, so the label stays the same for
+/// e.g. `/notices/<id>` whatever the id.
+pub struct MetricsFairing;
+
+#[rocket::async_trait]
+impl Fairing for MetricsFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "MetricsFairing",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let route = request
+            .route()
+            .map(|route| route.uri.as_str())
+            .unwrap_or("unmatched");
+
+        metrics()
+            .http_requests
+            .with_label_values(&[request.method().as_str(), route, &response.status().code.to_string()])
+            .inc();
+    }
+}
+
+#[get("/")]
+async fn scrape(container: &State<P2PandaContainer>, regions: &State<Regions>) -> (ContentType, String) {
+    let content_type = ContentType::parse_flexible(prometheus::TEXT_FORMAT).unwrap_or(ContentType::Plain);
+    let others: Vec<P2PandaContainer> = regions
+        .others()
+        .await
+        .into_iter()
+        .map(|membership| membership.container)
+        .collect();
+
+    (content_type, metrics().render(container, &others).await)
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![scrape]
+}
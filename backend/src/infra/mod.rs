@@ -1,3 +1,20 @@
+pub mod alerting;
+pub mod api_error;
+pub mod backup;
+pub mod buddy_backups;
 pub mod cors;
+pub mod crypto;
 pub mod db;
+pub mod deprecation;
+pub mod diagnostics;
+pub mod health_reports;
+pub mod logging;
+pub mod metrics;
+pub mod notifications;
+pub mod openapi;
+pub mod probes;
+pub mod settings;
 pub mod spa_server;
+pub mod supervisor;
+pub mod system_monitor;
+pub mod time;
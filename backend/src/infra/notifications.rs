@@ -0,0 +1,265 @@
+use std::future::Future;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use rocket::serde::Serialize;
+use rocket::tokio::task::spawn_blocking;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::settings::Settings;
+use super::time::unix_now;
+use crate::repos::entities::Alert;
+
+/// How long a webhook or mail server gets to answer.
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What happened to an alert, as sent to the webhook.
+#[derive(Serialize, Clone, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct AlertNotification {
+    /// `opened` or `resolved`.
+    pub event: &'static str,
+    pub alert: Alert,
+    pub rule_name: String,
+    pub node_name: String,
+}
+
+impl AlertNotification {
+    fn subject(&self) -> String {
+        format!("[LoRes {}] {}: {}", self.event, self.rule_name, self.alert.message)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct EmailTarget {
+    smtp_host: String,
+    smtp_port: u16,
+    from: String,
+    to: Vec<String>,
+}
+
+/// Where alert notifications go. Outside of tests that is a [`Notifier`].
+pub trait Notify {
+    fn notify(&self, notification: AlertNotification) -> impl Future<Output = ()> + Send;
+}
+
+impl Notify for Notifier {
+    fn notify(&self, notification: AlertNotification) -> impl Future<Output = ()> + Send {
+        Notifier::notify(self, notification)
+    }
+}
+
+/// Sends alert notifications to where this node's settings say. Other nodes notify on their own,
+/// so only the nodes an operator configured do.
+#[derive(Clone, Debug, Default)]
+pub struct Notifier {
+    webhook_url: Option<String>,
+    email: Option<EmailTarget>,
+}
+
+impl Notifier {
+    pub fn from_settings(settings: &Settings) -> Self {
+        let webhook_url = (!settings.alert_webhook_url.is_empty()).then(|| settings.alert_webhook_url.clone());
+        let email = (!settings.smtp_host.is_empty()).then(|| EmailTarget {
+            smtp_host: settings.smtp_host.clone(),
+            smtp_port: settings.smtp_port,
+            from: settings.alert_email_from.clone(),
+            to: settings
+                .alert_email_to
+                .split(',')
+                .map(|address| address.trim().to_string())
+                .filter(|address| !address.is_empty())
+                .collect(),
+        });
+
+        Notifier { webhook_url, email }
+    }
+
+    /// Sends `notification` everywhere configured. Failures are logged, an unreachable webhook
+    /// shouldn't keep the email from going out.
+    pub async fn notify(&self, notification: AlertNotification) {
+        let alert_id = notification.alert.id.clone();
+
+        if let Some(url) = self.webhook_url.clone() {
+            let notification = notification.clone();
+            let result = spawn_blocking(move || post_webhook(&url, &notification)).await;
+
+            match result
+                .map_err(anyhow::Error::from)
+                .and_then(|result| result)
+            {
+                Ok(()) => info!(alert_id, "Sent alert to webhook"),
+                Err(e) => warn!(error = %e, alert_id, "Failed to send alert to webhook"),
+            }
+        }
+
+        if let Some(email) = self.email.clone() {
+            let result = spawn_blocking(move || send_email(&email, &notification)).await;
+
+            match result
+                .map_err(anyhow::Error::from)
+                .and_then(|result| result)
+            {
+                Ok(()) => info!(alert_id, "Sent alert email"),
+                Err(e) => warn!(error = %e, alert_id, "Failed to send alert email"),
+            }
+        }
+    }
+}
+
+/// Errors leave out the URL, which may hold a token and ends up in the logs.
+fn post_webhook(url: &str, notification: &AlertNotification) -> Result<()> {
+    match ureq::post(url)
+        .timeout(NOTIFY_TIMEOUT)
+        .send_json(notification)
+    {
+        Ok(_) => Ok(()),
+        Err(ureq::Error::Status(status, _)) => Err(anyhow!("Webhook answered with status {}", status)),
+        Err(ureq::Error::Transport(transport)) => Err(anyhow!("Webhook can't be reached ({})", transport.kind())),
+    }
+}
+
+/// Hands the email to an SMTP server that relays without authentication, usually one running on
+/// the node's host or LAN.
+fn send_email(target: &EmailTarget, notification: &AlertNotification) -> Result<()> {
+    let stream = connect(&target.smtp_host, target.smtp_port)?;
+    stream.set_read_timeout(Some(NOTIFY_TIMEOUT))?;
+    stream.set_write_timeout(Some(NOTIFY_TIMEOUT))?;
+    let mut smtp = SmtpSession {
+        reader: BufReader::new(stream.try_clone()?),
+        writer: stream,
+    };
+
+    smtp.expect(&[220])?;
+    smtp.command("HELO lores-node", &[250])?;
+    smtp.command(&format!("MAIL FROM:<{}>", single_line(&target.from)), &[250])?;
+    for to in &target.to {
+        // 251: not local, but the server forwards it
+        smtp.command(&format!("RCPT TO:<{}>", single_line(to)), &[250, 251])?;
+    }
+    smtp.command("DATA", &[354])?;
+    smtp.command(&format!("{}\r\n.", email_message(target, notification, unix_now())), &[250])?;
+    smtp.command("QUIT", &[221])?;
+
+    Ok(())
+}
+
+/// The headers and body of the email, ready to send after `DATA`. Rule names and alert messages
+/// come from any node in the region, so they can't add headers or end the data early.
+fn email_message(target: &EmailTarget, notification: &AlertNotification, now: i64) -> String {
+    let to = target
+        .to
+        .iter()
+        .map(|to| format!("<{}>", single_line(to)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let headers = [
+        format!("From: <{}>", single_line(&target.from)),
+        format!("To: {}", to),
+        format!("Subject: {}", single_line(&notification.subject())),
+        format!("Date: {}", rfc2822_date(now)),
+        format!("Message-ID: <{}@lores-node>", Uuid::new_v4()),
+    ];
+    let body = format!(
+        "{}\n\nNode: {} ({})\nOpened at: {}\n",
+        notification.alert.message, notification.node_name, notification.alert.node_id, notification.alert.opened_at
+    );
+
+    // a line with a single dot ends the data, so dots starting a line are doubled
+    let body: Vec<String> = body
+        .replace('\r', "")
+        .lines()
+        .map(|line| if line.starts_with('.') { format!(".{}", line) } else { line.to_string() })
+        .collect();
+
+    format!("{}\r\n\r\n{}", headers.join("\r\n"), body.join("\r\n"))
+}
+
+/// `value` with line breaks turned into spaces, so it stays within its header or command.
+fn single_line(value: &str) -> String {
+    value.replace(['\r', '\n'], " ")
+}
+
+/// `Tue, 1 Jul 2003 10:52:37 +0000`, the date format of email headers.
+pub(crate) fn rfc2822_date(unix_time: i64) -> String {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+    let days = unix_time.div_euclid(86400);
+    let secs = unix_time.rem_euclid(86400);
+
+    // civil date from days since 1970-01-01, after Howard Hinnant's `civil_from_days`
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{}, {} {} {} {:02}:{:02}:{:02} +0000",
+        WEEKDAYS[days.rem_euclid(7) as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+/// Connects to the first address of `host` that answers within [`NOTIFY_TIMEOUT`].
+fn connect(host: &str, port: u16) -> Result<TcpStream> {
+    let mut last_error = None;
+    for address in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, NOTIFY_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    Err(match last_error {
+        Some(e) => e.into(),
+        None => anyhow!("Mail server {} has no address", host),
+    })
+}
+
+struct SmtpSession {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl SmtpSession {
+    fn command(&mut self, line: &str, expected: &[u16]) -> Result<()> {
+        self.writer
+            .write_all(format!("{}\r\n", line).as_bytes())?;
+        self.expect(expected)
+    }
+
+    /// Reads a reply, which can span several lines like `250-...` ending with `250 ...`.
+    fn expect(&mut self, expected: &[u16]) -> Result<()> {
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(anyhow!("Mail server closed the connection"));
+            }
+
+            let code: u16 = line
+                .get(..3)
+                .and_then(|code| code.parse().ok())
+                .ok_or_else(|| anyhow!("Unexpected reply from mail server: {}", line.trim()))?;
+            if !expected.contains(&code) {
+                return Err(anyhow!("Mail server answered {}", line.trim()));
+            }
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok(());
+            }
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use utoipa::openapi::path::PathItem;
+use utoipa::openapi::Deprecated;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+
+use super::api_error::ApiError;
+use crate::routes::ApiVersion;
+
+pub const SPEC_PATH: &str = "/api/openapi.json";
+const VIEWER_PATH: &str = "/api/docs/<_..>";
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "LoRes Node API", description = "Errors are returned as an `ApiError` body."),
+    components(schemas(ApiError))
+)]
+struct ApiDoc;
+
+/// The OpenAPI document for every route in `versions`, each tagged with the module it is in.
+/// Routes of deprecated versions are marked as such.
+pub fn document(versions: &[ApiVersion]) -> utoipa::openapi::OpenApi {
+    let mut document = ApiDoc::openapi();
+
+    for version in versions {
+        for module in &version.modules {
+            let mut openapi = module.openapi.clone();
+            let tag = module.path.trim_start_matches('/');
+
+            for path_item in openapi.paths.paths.values_mut() {
+                for operation in operations(path_item) {
+                    operation.tags = Some(vec![tag.to_string()]);
+                    if version.deprecated {
+                        operation.deprecated = Some(Deprecated::True);
+                    }
+                }
+            }
+
+            document = document.nest(format!("{}{}", version.base(), module.path), openapi);
+        }
+    }
+
+    document
+}
+
+fn operations(path_item: &mut PathItem) -> impl Iterator<Item = &mut utoipa::openapi::path::Operation> {
+    [
+        &mut path_item.get,
+        &mut path_item.put,
+        &mut path_item.post,
+        &mut path_item.delete,
+        &mut path_item.options,
+        &mut path_item.head,
+        &mut path_item.patch,
+        &mut path_item.trace,
+    ]
+    .into_iter()
+    .flatten()
+}
+
+/// Serves `document` at `SPEC_PATH`, with a Swagger UI for it at `/api/docs`. The viewer is
+/// bundled in the binary, so it works on nodes without internet access.
+pub fn viewer(document: utoipa::openapi::OpenApi) -> SwaggerUi {
+    SwaggerUi::new(VIEWER_PATH).url(SPEC_PATH, document)
+}
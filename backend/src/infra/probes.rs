@@ -0,0 +1,132 @@
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::serde::Serialize;
+use rocket::{Route, State};
+use sqlx::Sqlite;
+
+use super::db::{migration_status, MainDb, MigrationStatus};
+use super::supervisor::{Supervisor, TaskState, TaskStatus};
+use crate::events::fairing::EVENT_HANDLER_TASK;
+use crate::panda_comms::container::{ContainerState, P2PandaContainer};
+
+/// One of the conditions `/readyz` checks.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct Check {
+    pub name: String,
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, ok: bool, detail: impl Into<String>) -> Self {
+        Check {
+            name: name.into(),
+            ok,
+            detail: detail.into(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct Readiness {
+    pub ready: bool,
+    pub checks: Vec<Check>,
+    /// The background tasks, with their crashes.
+    pub tasks: Vec<TaskStatus>,
+}
+
+impl Readiness {
+    pub fn new(mut checks: Vec<Check>, tasks: Vec<TaskStatus>) -> Self {
+        checks.extend(task_checks(&tasks));
+
+        Readiness {
+            ready: checks.iter().all(|check| check.ok),
+            checks,
+            tasks,
+        }
+    }
+}
+
+async fn database_check(pool: &sqlx::Pool<Sqlite>) -> Check {
+    match sqlx::query("SELECT 1").execute(pool).await {
+        Ok(_) => Check::new("database", true, "reachable"),
+        Err(e) => Check::new("database", false, e.to_string()),
+    }
+}
+
+pub fn migrations_check(migrations: &[MigrationStatus]) -> Check {
+    let pending: Vec<String> = migrations
+        .iter()
+        .filter(|migration| !migration.applied)
+        .map(|migration| migration.version.to_string())
+        .collect();
+
+    if pending.is_empty() {
+        Check::new("migrations", true, format!("{} applied", migrations.len()))
+    } else {
+        Check::new("migrations", false, format!("not applied: {}", pending.join(", ")))
+    }
+}
+
+/// A node without a region or key is ready: it is waiting for its operator, and restarting it
+/// wouldn't change that.
+pub fn container_check(state: &ContainerState) -> Check {
+    match state {
+        ContainerState::Running | ContainerState::Unconfigured => Check::new("p2panda", true, state.to_string()),
+        _ => Check::new("p2panda", false, state.to_string()),
+    }
+}
+
+/// A check per background task, failing while it waits to restart after a crash. The event
+/// handler has to be running.
+pub fn task_checks(tasks: &[TaskStatus]) -> Vec<Check> {
+    let mut checks: Vec<Check> = tasks
+        .iter()
+        .map(|task| match (&task.state, &task.last_crash) {
+            (TaskState::Restarting, Some(crash)) => Check::new(task.name.clone(), false, format!("restarting after: {}", crash.reason)),
+            (TaskState::Restarting, None) => Check::new(task.name.clone(), false, "restarting"),
+            (TaskState::Running, _) => Check::new(task.name.clone(), true, "running"),
+            (TaskState::Stopped, _) => Check::new(task.name.clone(), task.name != EVENT_HANDLER_TASK, "stopped"),
+        })
+        .collect();
+
+    if !tasks
+        .iter()
+        .any(|task| task.name == EVENT_HANDLER_TASK)
+    {
+        checks.push(Check::new(EVENT_HANDLER_TASK, false, "not started"));
+    }
+
+    checks
+}
+
+/// The process is up and serving requests.
+#[get("/healthz")]
+fn healthz() -> Json<&'static str> {
+    Json("ok")
+}
+
+/// Whether the node works: `200` when every check passes, `503` otherwise, with the checks in
+/// the body either way.
+#[get("/readyz")]
+async fn readyz(db: &State<MainDb>, container: &State<P2PandaContainer>, supervisor: &State<Supervisor>) -> (Status, Json<Readiness>) {
+    let pool = db.sqlite_pool();
+    let migrations = match migration_status(pool).await {
+        Ok(migrations) => migrations_check(&migrations),
+        Err(e) => Check::new("migrations", false, e.to_string()),
+    };
+
+    let readiness = Readiness::new(
+        vec![database_check(pool).await, migrations, container_check(&container.state().await)],
+        supervisor.statuses(),
+    );
+    let status = if readiness.ready { Status::Ok } else { Status::ServiceUnavailable };
+
+    (status, Json(readiness))
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![healthz, readyz]
+}
@@ -0,0 +1,256 @@
+use p2panda_net::RelayUrl;
+use rocket::figment::providers::{Env, Format, Toml};
+use rocket::figment::Figment;
+use rocket::serde::{Deserialize, Serialize};
+use tracing_subscriber::EnvFilter;
+use utoipa::ToSchema;
+
+use crate::panda_comms::container::{MeshTopic, NetworkOptions};
+
+/// Path of an optional TOML file with settings, laid out like `Rocket.toml`.
+pub const CONFIG_FILE_ENV: &str = "LORES_CONFIG_FILE";
+
+/// Settings that can also be given as `LORES_<NAME>` env vars, e.g. `LORES_RELAY_URL`.
+const ENV_KEYS: [&str; 23] = [
+    "frontend_asset_path",
+    "topic_name",
+    "log_id",
+    "relay_url",
+    "iroh_port_v4",
+    "iroh_port_v6",
+    "mdns_discovery",
+    "event_channel_size",
+    "log_format",
+    "log_filter",
+    "proc_path",
+    "sys_path",
+    "data_path",
+    "system_sample_interval_secs",
+    "alert_cpu_percent",
+    "alert_memory_percent",
+    "alert_disk_percent",
+    "alert_temperature_c",
+    "alert_webhook_url",
+    "smtp_host",
+    "smtp_port",
+    "alert_email_from",
+    "alert_email_to",
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum SettingsError {
+    #[error("Invalid settings: {0}")]
+    Extract(#[from] Box<rocket::figment::Error>),
+
+    #[error("Invalid setting {key}: {reason}")]
+    Invalid { key: &'static str, reason: String },
+}
+
+/// The tunables of a node. Each one is read, last wins, from: the defaults below, `Rocket.toml`,
+/// `ROCKET_<NAME>` env vars, the file at `LORES_CONFIG_FILE` and `LORES_<NAME>` env vars.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+#[serde(crate = "rocket::serde", default)]
+pub struct Settings {
+    /// Directory of the built frontend, empty to serve the API only.
+    pub frontend_asset_path: String,
+    /// Gossip topic the region's events are published on. Nodes only hear each other when it
+    /// matches.
+    pub topic_name: String,
+    /// Log our own events are written to.
+    pub log_id: String,
+    /// Relay used to reach nodes beyond the local network, empty to go without.
+    pub relay_url: String,
+    /// UDP ports the network binds, 0 for any free one. Nodes in regions besides the first
+    /// always take free ones.
+    pub iroh_port_v4: u16,
+    pub iroh_port_v6: u16,
+    /// Find nodes on the local network with mDNS, without a bootstrap node or relay.
+    pub mdns_discovery: bool,
+    /// How many received events can wait to be handled before the network backs off.
+    pub event_channel_size: usize,
+    pub log_format: LogFormat,
+    /// Log levels, overall and per module, e.g. `info,lores_node::panda_comms=debug`.
+    pub log_filter: String,
+    /// Where the host's `/proc` and `/sys` are read from, for the system monitor.
+    pub proc_path: String,
+    pub sys_path: String,
+    /// Volume whose disk usage is monitored, empty for the one holding the database.
+    pub data_path: String,
+    pub system_sample_interval_secs: u64,
+    /// Above these the system monitor raises an alert.
+    pub alert_cpu_percent: f64,
+    pub alert_memory_percent: f64,
+    pub alert_disk_percent: f64,
+    pub alert_temperature_c: f64,
+    /// Alerts are posted here as JSON when they open and resolve, empty for no webhook.
+    pub alert_webhook_url: String,
+    /// Mail server alerts are emailed through, empty for no emails. It must relay without
+    /// authentication, like one on the host or LAN.
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub alert_email_from: String,
+    /// Comma separated addresses.
+    pub alert_email_to: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, ToSchema)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line, for log collectors.
+    Json,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            frontend_asset_path: "".to_string(),
+            topic_name: "lores_mesh".to_string(),
+            log_id: "lores_mesh".to_string(),
+            relay_url: "https://staging-euw1-1.relay.iroh.network/".to_string(),
+            // the ports compose.yml publishes
+            iroh_port_v4: 2022,
+            iroh_port_v6: 2023,
+            mdns_discovery: true,
+            event_channel_size: 32,
+            log_format: LogFormat::Text,
+            // Rocket logs every request itself, ours carry the same in a span
+            log_filter: "info,rocket::server=warn,_=warn".to_string(),
+            proc_path: "/proc".to_string(),
+            sys_path: "/sys".to_string(),
+            data_path: "".to_string(),
+            system_sample_interval_secs: 60,
+            alert_cpu_percent: 90.0,
+            alert_memory_percent: 90.0,
+            alert_disk_percent: 90.0,
+            // Raspberry Pis throttle at 80°C
+            alert_temperature_c: 75.0,
+            alert_webhook_url: "".to_string(),
+            smtp_host: "".to_string(),
+            smtp_port: 25,
+            alert_email_from: "".to_string(),
+            alert_email_to: "".to_string(),
+        }
+    }
+}
+
+impl Settings {
+    /// Reads and validates the settings held by `figment`, see [`figment`].
+    pub fn extract(figment: &Figment) -> Result<Self, SettingsError> {
+        let settings: Settings = figment.extract().map_err(Box::new)?;
+        settings.validate()?;
+
+        Ok(settings)
+    }
+
+    fn validate(&self) -> Result<(), SettingsError> {
+        if self.topic_name.trim().is_empty() {
+            return Err(invalid("topic_name", "must not be empty"));
+        }
+        if self.log_id.trim().is_empty() {
+            return Err(invalid("log_id", "must not be empty"));
+        }
+        if self.event_channel_size == 0 {
+            return Err(invalid("event_channel_size", "must be at least 1"));
+        }
+        if self.system_sample_interval_secs == 0 {
+            return Err(invalid("system_sample_interval_secs", "must be at least 1"));
+        }
+        for (key, percent) in [
+            ("alert_cpu_percent", self.alert_cpu_percent),
+            ("alert_memory_percent", self.alert_memory_percent),
+            ("alert_disk_percent", self.alert_disk_percent),
+        ] {
+            if !(0.0..=100.0).contains(&percent) {
+                return Err(invalid(key, "must be between 0 and 100"));
+            }
+        }
+        if !(0.0..=150.0).contains(&self.alert_temperature_c) {
+            return Err(invalid("alert_temperature_c", "must be between 0 and 150"));
+        }
+        if !self.alert_webhook_url.is_empty() && !self.alert_webhook_url.starts_with("http") {
+            return Err(invalid("alert_webhook_url", "must be an http or https URL"));
+        }
+        if !self.smtp_host.is_empty() && (self.alert_email_from.is_empty() || self.alert_email_to.trim().is_empty()) {
+            return Err(invalid("smtp_host", "needs alert_email_from and alert_email_to"));
+        }
+        if let Err(e) = EnvFilter::try_new(&self.log_filter) {
+            return Err(invalid("log_filter", &e.to_string()));
+        }
+        if self.iroh_port_v4 != 0 && self.iroh_port_v4 == self.iroh_port_v6 {
+            return Err(invalid("iroh_port_v6", "must differ from iroh_port_v4"));
+        }
+        if !self.relay_url.is_empty() {
+            self.relay_url
+                .parse::<RelayUrl>()
+                .map_err(|e| invalid("relay_url", &format!("{} is not a URL ({})", self.relay_url, e)))?;
+        }
+
+        Ok(())
+    }
+
+    /// The settings as they can be shown or logged: the webhook URL often holds a token, so only
+    /// its scheme and host are kept.
+    pub fn redacted(&self) -> Settings {
+        Settings {
+            alert_webhook_url: redact_url(&self.alert_webhook_url),
+            ..self.clone()
+        }
+    }
+
+    pub fn relay_url(&self) -> Option<RelayUrl> {
+        // validated on extraction
+        self.relay_url.parse().ok()
+    }
+
+    pub fn network_options(&self) -> NetworkOptions {
+        NetworkOptions {
+            port_v4: self.iroh_port_v4,
+            port_v6: self.iroh_port_v6,
+            mdns_discovery: self.mdns_discovery,
+        }
+    }
+
+    pub fn mesh_topic(&self) -> MeshTopic {
+        MeshTopic {
+            name: self.topic_name.clone(),
+            log_id: self.log_id.clone(),
+        }
+    }
+}
+
+fn redact_url(url: &str) -> String {
+    if url.is_empty() {
+        return String::new();
+    }
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return "[redacted]".to_string();
+    };
+    let authority = rest
+        .split(['/', '?', '#'])
+        .next()
+        .unwrap_or_default();
+    // credentials may come before the host too
+    let host = authority.rsplit('@').next().unwrap_or_default();
+
+    format!("{}://{}/[redacted]", scheme, host)
+}
+
+fn invalid(key: &'static str, reason: &str) -> SettingsError {
+    SettingsError::Invalid {
+        key,
+        reason: reason.to_string(),
+    }
+}
+
+/// Rocket's own figment, with the settings file and `LORES_` env vars merged in on top.
+pub fn figment() -> Figment {
+    let mut figment = rocket::Config::figment();
+
+    if let Ok(path) = std::env::var(CONFIG_FILE_ENV) {
+        figment = figment.merge(Toml::file_exact(path).nested());
+    }
+
+    figment.merge(Env::prefixed("LORES_").only(&ENV_KEYS))
+}
@@ -6,6 +6,7 @@ use rocket::response::Responder;
 use rocket::route::{Handler, Outcome};
 use rocket::{figment, yansi, Data, Route};
 use std::path::{Path, PathBuf};
+use tracing::error;
 
 /// This is a custom handler for serving single page applications (SPA).
 /// Heavily based on the SpaServer handler from Rocket.
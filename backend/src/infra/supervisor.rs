@@ -0,0 +1,198 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use futures_util::FutureExt;
+use rocket::serde::Serialize;
+use rocket::tokio::{self, task::JoinHandle, time::sleep};
+use tracing::{error, info};
+
+use super::time::unix_now;
+
+/// How long a crashed task waits before its first restart, doubled after each crash in a row.
+const FIRST_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+/// A task that ran this long before crashing wasn't crash looping, so it restarts without the
+/// backoff built up by earlier crashes.
+const STABLE_RUN: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum TaskState {
+    Running,
+    /// Crashed, and waiting out its backoff before starting again.
+    Restarting,
+    /// Finished or stopped, and not restarted.
+    Stopped,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct TaskCrash {
+    pub crashed_at: i64,
+    pub reason: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct TaskStatus {
+    pub name: String,
+    pub state: TaskState,
+    /// Crashes since the node started, kept when the task is spawned again.
+    pub crashes: u32,
+    pub last_crash: Option<TaskCrash>,
+}
+
+struct Task {
+    status: TaskStatus,
+    handle: Option<JoinHandle<()>>,
+}
+
+/// Runs background tasks by name, restarting the ones that panic or fail with a growing backoff.
+/// A task that returns `Ok` is done and isn't restarted.
+#[derive(Clone)]
+pub struct Supervisor {
+    tasks: Arc<Mutex<BTreeMap<String, Task>>>,
+    /// Put before the names of the tasks spawned through this supervisor.
+    prefix: String,
+    first_backoff: Duration,
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Supervisor::with_first_backoff(FIRST_BACKOFF)
+    }
+}
+
+impl Supervisor {
+    /// A supervisor whose crashed tasks wait `first_backoff` before their first restart, instead
+    /// of [`FIRST_BACKOFF`]. Lets tests watch restarts without waiting seconds for them.
+    pub fn with_first_backoff(first_backoff: Duration) -> Supervisor {
+        Supervisor {
+            tasks: Arc::default(),
+            prefix: String::new(),
+            first_backoff,
+        }
+    }
+
+    /// The same supervisor, naming its tasks `<scope>/<name>`. Lets several owners of tasks with
+    /// the same names, like the node of each region, share one supervisor.
+    pub fn scoped(&self, scope: &str) -> Supervisor {
+        Supervisor {
+            tasks: self.tasks.clone(),
+            prefix: format!("{}{}/", self.prefix, scope),
+            first_backoff: self.first_backoff,
+        }
+    }
+
+    /// Runs the future made by `task`, and makes a new one each time it crashes. Spawning a name
+    /// already in use replaces the task running under it.
+    pub fn spawn<F, Fut>(&self, name: &str, task: F)
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let name = format!("{}{}", self.prefix, name);
+        let supervisor = self.clone();
+        let mut tasks = self.tasks.lock().expect("tasks lock");
+        let entry = tasks.entry(name.clone()).or_insert_with(|| Task {
+            status: TaskStatus {
+                name: name.clone(),
+                state: TaskState::Running,
+                crashes: 0,
+                last_crash: None,
+            },
+            handle: None,
+        });
+
+        // the new task can't update its status before the lock is released
+        let handle = tokio::spawn(supervisor.supervise(name, task));
+        if let Some(previous) = entry.handle.replace(handle) {
+            previous.abort();
+        }
+    }
+
+    /// Stops the task running under `name`, if any.
+    pub fn stop(&self, name: &str) {
+        let mut tasks = self.tasks.lock().expect("tasks lock");
+
+        if let Some(task) = tasks.get_mut(&format!("{}{}", self.prefix, name)) {
+            if let Some(handle) = task.handle.take() {
+                handle.abort();
+            }
+            task.status.state = TaskState::Stopped;
+        }
+    }
+
+    /// Every task spawned so far through any scope, by name.
+    pub fn statuses(&self) -> Vec<TaskStatus> {
+        let tasks = self.tasks.lock().expect("tasks lock");
+        tasks
+            .values()
+            .map(|task| task.status.clone())
+            .collect()
+    }
+
+    async fn supervise<F, Fut>(self, name: String, task: F)
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let mut backoff = self.first_backoff;
+
+        loop {
+            self.update(&name, |status| status.state = TaskState::Running);
+            let started = Instant::now();
+
+            let reason = match AssertUnwindSafe(task()).catch_unwind().await {
+                Ok(Ok(())) => {
+                    info!(task = %name, "Background task finished");
+                    self.update(&name, |status| status.state = TaskState::Stopped);
+                    return;
+                }
+                Ok(Err(e)) => format!("{:#}", e),
+                Err(panic) => format!("panicked: {}", panic_message(panic.as_ref())),
+            };
+
+            if started.elapsed() >= STABLE_RUN {
+                backoff = self.first_backoff;
+            }
+            error!(task = %name, reason, restart_in_secs = backoff.as_secs(), "Background task crashed");
+
+            self.update(&name, |status| {
+                status.state = TaskState::Restarting;
+                status.crashes += 1;
+                status.last_crash = Some(TaskCrash {
+                    crashed_at: unix_now(),
+                    reason,
+                });
+            });
+
+            sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    fn update(&self, name: &str, change: impl FnOnce(&mut TaskStatus)) {
+        let mut tasks = self.tasks.lock().expect("tasks lock");
+
+        if let Some(task) = tasks.get_mut(name) {
+            change(&mut task.status);
+        }
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown reason".to_string()
+    }
+}
@@ -0,0 +1,403 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::tokio::time::sleep;
+use rocket::{Orbit, Rocket};
+use rocket_db_pools::Database;
+use sqlx::Sqlite;
+use thiserror::Error;
+use tracing::{error, info, warn};
+use utoipa::ToSchema;
+
+use super::db::{database_path, MainDb};
+use super::settings::Settings;
+use super::supervisor::Supervisor;
+use super::time::unix_now;
+use crate::repos::entities::{InterfaceStats, SystemAlert, SystemSample};
+use crate::repos::system::{SystemError, SystemRepo};
+
+/// How long samples, and alerts resolved since, are kept.
+pub const SYSTEM_HISTORY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Name the sampling loop is supervised under.
+pub const SYSTEM_MONITOR_TASK: &str = "system_monitor";
+
+#[derive(Debug, Error)]
+pub enum SystemMonitorError {
+    #[error("Failed to read {path}: {reason}")]
+    Read { path: PathBuf, reason: String },
+
+    #[error("Unexpected contents in {path}")]
+    Parse { path: PathBuf },
+}
+
+/// Where the host is read from. Outside of the tests these are `/proc`, `/sys` and the volume
+/// holding the database.
+#[derive(Clone, Debug)]
+pub struct SystemSources {
+    pub proc_path: PathBuf,
+    pub sys_path: PathBuf,
+    pub data_path: PathBuf,
+}
+
+impl SystemSources {
+    /// Takes the data volume from the settings, or else from the directory of `database_url`.
+    pub fn new(settings: &Settings, database_url: &str) -> Self {
+        let data_path = if settings.data_path.is_empty() {
+            database_dir(database_url)
+        } else {
+            PathBuf::from(&settings.data_path)
+        };
+
+        SystemSources {
+            proc_path: PathBuf::from(&settings.proc_path),
+            sys_path: PathBuf::from(&settings.sys_path),
+            data_path,
+        }
+    }
+
+    /// Reads the state of the host right now.
+    pub fn read(&self) -> Result<SystemReading, SystemMonitorError> {
+        let (memory_total_kib, memory_available_kib) = parse_meminfo(&self.read_proc("meminfo")?).ok_or_else(|| self.parse_error("meminfo"))?;
+        let (disk_total_bytes, disk_available_bytes) = disk_usage(&self.data_path)?;
+
+        Ok(SystemReading {
+            cpu: parse_cpu_times(&self.read_proc("stat")?).ok_or_else(|| self.parse_error("stat"))?,
+            memory_total_kib,
+            memory_available_kib,
+            disk_total_bytes,
+            disk_available_bytes,
+            temperature_c: self.read_temperature(),
+            uptime_secs: parse_uptime(&self.read_proc("uptime")?).ok_or_else(|| self.parse_error("uptime"))?,
+            load_1m: parse_load(&self.read_proc("loadavg")?).ok_or_else(|| self.parse_error("loadavg"))?,
+            interfaces: parse_net_dev(&self.read_proc("net/dev")?),
+        })
+    }
+
+    fn read_proc(&self, file: &str) -> Result<String, SystemMonitorError> {
+        let path = self.proc_path.join(file);
+        fs::read_to_string(&path).map_err(|e| SystemMonitorError::Read { path, reason: e.to_string() })
+    }
+
+    fn parse_error(&self, file: &str) -> SystemMonitorError {
+        SystemMonitorError::Parse {
+            path: self.proc_path.join(file),
+        }
+    }
+
+    /// The hottest thermal zone, if the host reports any. Many VMs and containers don't.
+    fn read_temperature(&self) -> Option<f64> {
+        let zones = fs::read_dir(self.sys_path.join("class/thermal")).ok()?;
+
+        zones
+            .filter_map(|zone| zone.ok())
+            .filter(|zone| {
+                zone.file_name()
+                    .to_string_lossy()
+                    .starts_with("thermal_zone")
+            })
+            .filter_map(|zone| fs::read_to_string(zone.path().join("temp")).ok())
+            .filter_map(|millidegrees| millidegrees.trim().parse::<i64>().ok())
+            .map(|millidegrees| millidegrees as f64 / 1000.0)
+            .reduce(f64::max)
+    }
+}
+
+fn database_dir(database_url: &str) -> PathBuf {
+    match database_path(database_url).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+fn disk_usage(path: &Path) -> Result<(u64, u64), SystemMonitorError> {
+    let stats = rustix::fs::statvfs(path).map_err(|e| SystemMonitorError::Read {
+        path: path.to_path_buf(),
+        reason: e.to_string(),
+    })?;
+
+    Ok((stats.f_blocks * stats.f_frsize, stats.f_bavail * stats.f_frsize))
+}
+
+/// Time the CPUs spent since boot, in clock ticks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CpuTimes {
+    pub busy: u64,
+    pub total: u64,
+}
+
+impl CpuTimes {
+    /// How busy the CPUs were between `earlier` and now, as a percentage.
+    pub fn percent_since(&self, earlier: &CpuTimes) -> Option<f64> {
+        let total = self.total.checked_sub(earlier.total)?;
+        let busy = self.busy.checked_sub(earlier.busy)?;
+
+        (total > 0).then(|| busy as f64 * 100.0 / total as f64)
+    }
+}
+
+/// The state of the host at one point in time, as read from `/proc` and `/sys`.
+#[derive(Clone, Debug)]
+pub struct SystemReading {
+    pub cpu: CpuTimes,
+    pub memory_total_kib: u64,
+    pub memory_available_kib: u64,
+    pub disk_total_bytes: u64,
+    pub disk_available_bytes: u64,
+    pub temperature_c: Option<f64>,
+    pub uptime_secs: i64,
+    pub load_1m: f64,
+    pub interfaces: Vec<InterfaceStats>,
+}
+
+impl SystemReading {
+    /// The sample to keep, with the CPU usage since the `previous` reading if there was one.
+    pub fn sample(&self, previous: Option<&SystemReading>, sampled_at: i64) -> SystemSample {
+        SystemSample {
+            sampled_at,
+            cpu_percent: previous.and_then(|previous| self.cpu.percent_since(&previous.cpu)),
+            memory_used_percent: used_percent(self.memory_total_kib, self.memory_available_kib),
+            disk_used_percent: used_percent(self.disk_total_bytes, self.disk_available_bytes),
+            disk_free_bytes: self.disk_available_bytes as i64,
+            temperature_c: self.temperature_c,
+            uptime_secs: self.uptime_secs,
+            load_1m: self.load_1m,
+            interfaces: self.interfaces.clone(),
+        }
+    }
+}
+
+fn used_percent(total: u64, available: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    total.saturating_sub(available) as f64 * 100.0 / total as f64
+}
+
+/// Reads the first, aggregate, `cpu` line of `/proc/stat`.
+pub fn parse_cpu_times(stat: &str) -> Option<CpuTimes> {
+    let line = stat
+        .lines()
+        .find(|line| line.starts_with("cpu "))?;
+    let ticks: Vec<u64> = line
+        .split_whitespace()
+        .skip(1)
+        .map(|ticks| ticks.parse().ok())
+        .collect::<Option<_>>()?;
+
+    // user nice system idle iowait irq softirq steal, guest time is counted in user already
+    let total: u64 = ticks.iter().take(8).sum();
+    let idle = ticks.get(3)? + ticks.get(4).unwrap_or(&0);
+
+    Some(CpuTimes { busy: total - idle, total })
+}
+
+/// Reads total and available memory, in KiB, from `/proc/meminfo`.
+pub fn parse_meminfo(meminfo: &str) -> Option<(u64, u64)> {
+    let field = |name: &str| {
+        meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .and_then(|value| {
+                value
+                    .split_whitespace()
+                    .next()?
+                    .parse::<u64>()
+                    .ok()
+            })
+    };
+
+    Some((field("MemTotal")?, field("MemAvailable")?))
+}
+
+pub fn parse_uptime(uptime: &str) -> Option<i64> {
+    let secs: f64 = uptime.split_whitespace().next()?.parse().ok()?;
+    Some(secs as i64)
+}
+
+pub fn parse_load(loadavg: &str) -> Option<f64> {
+    loadavg.split_whitespace().next()?.parse().ok()
+}
+
+/// Reads the byte and error counters of every interface but loopback from `/proc/net/dev`.
+pub fn parse_net_dev(net_dev: &str) -> Vec<InterfaceStats> {
+    net_dev
+        .lines()
+        // the first two lines are headers
+        .skip(2)
+        .filter_map(|line| {
+            let (name, counters) = line.split_once(':')?;
+            let counters: Vec<i64> = counters
+                .split_whitespace()
+                .map(|counter| counter.parse().ok())
+                .collect::<Option<_>>()?;
+
+            Some(InterfaceStats {
+                name: name.trim().to_string(),
+                rx_bytes: *counters.first()?,
+                rx_errors: *counters.get(2)?,
+                tx_bytes: *counters.get(8)?,
+                tx_errors: *counters.get(10)?,
+            })
+        })
+        .filter(|interface| interface.name != "lo")
+        .collect()
+}
+
+/// What a sample is checked against. An alert is raised when a value goes above its threshold,
+/// and resolved when it is back under.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct SystemThresholds {
+    pub cpu_percent: f64,
+    pub memory_percent: f64,
+    pub disk_percent: f64,
+    pub temperature_c: f64,
+}
+
+impl SystemThresholds {
+    pub fn from_settings(settings: &Settings) -> Self {
+        SystemThresholds {
+            cpu_percent: settings.alert_cpu_percent,
+            memory_percent: settings.alert_memory_percent,
+            disk_percent: settings.alert_disk_percent,
+            temperature_c: settings.alert_temperature_c,
+        }
+    }
+
+    /// The value of each alert kind in `sample`, with its threshold. Kinds the sample has no
+    /// value for are left out, so their alerts stay as they are.
+    pub fn check(&self, sample: &SystemSample) -> Vec<(SystemAlertKind, f64, f64)> {
+        [
+            (SystemAlertKind::HighCpu, sample.cpu_percent, self.cpu_percent),
+            (SystemAlertKind::HighMemory, Some(sample.memory_used_percent), self.memory_percent),
+            (SystemAlertKind::DiskFull, Some(sample.disk_used_percent), self.disk_percent),
+            (SystemAlertKind::HighTemperature, sample.temperature_c, self.temperature_c),
+        ]
+        .into_iter()
+        .filter_map(|(kind, value, threshold)| Some((kind, value?, threshold)))
+        .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum SystemAlertKind {
+    HighCpu,
+    HighMemory,
+    DiskFull,
+    HighTemperature,
+}
+
+impl SystemAlertKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SystemAlertKind::HighCpu => "high_cpu",
+            SystemAlertKind::HighMemory => "high_memory",
+            SystemAlertKind::DiskFull => "disk_full",
+            SystemAlertKind::HighTemperature => "high_temperature",
+        }
+    }
+}
+
+/// Stores a sample and raises or resolves the alerts it crosses the thresholds of.
+pub async fn record_sample(pool: &sqlx::Pool<Sqlite>, sample: &SystemSample, thresholds: &SystemThresholds) -> Result<(), SystemError> {
+    let repo = SystemRepo::init();
+    repo.add_sample(pool, sample).await?;
+
+    let open_alerts = repo.open_alerts(pool).await?;
+
+    for (kind, value, threshold) in thresholds.check(sample) {
+        let open_alert: Option<&SystemAlert> = open_alerts
+            .iter()
+            .find(|alert| alert.kind == kind.as_str());
+
+        match (value > threshold, open_alert) {
+            (true, None) => {
+                warn!(alert = kind.as_str(), value, threshold, "System alert raised");
+                repo.raise_alert(pool, kind, value, threshold, sample.sampled_at)
+                    .await?;
+            }
+            (false, Some(alert)) => {
+                info!(alert = kind.as_str(), value, threshold, "System alert resolved");
+                repo.resolve_alert(pool, &alert.id, sample.sampled_at)
+                    .await?;
+            }
+            _ => {}
+        }
+    }
+
+    repo.prune(pool, sample.sampled_at - SYSTEM_HISTORY.as_secs() as i64)
+        .await
+}
+
+/// Samples the host every `system_sample_interval_secs`.
+pub struct SystemMonitorFairing {
+    sources: SystemSources,
+}
+
+impl SystemMonitorFairing {
+    pub fn new(sources: SystemSources) -> Self {
+        SystemMonitorFairing { sources }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for SystemMonitorFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "SystemMonitorFairing",
+            kind: Kind::Liftoff | Kind::Singleton,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let settings = rocket.state::<Settings>().cloned();
+        let supervisor = rocket.state::<Supervisor>();
+
+        let (Some(db), Some(settings), Some(supervisor)) = (MainDb::fetch(rocket), settings, supervisor) else {
+            error!("MainDb, Settings or Supervisor state not found, won't monitor the system");
+            return;
+        };
+
+        let db_pool = db.sqlite_pool().clone();
+        let sources = self.sources.clone();
+        let interval = Duration::from_secs(settings.system_sample_interval_secs);
+        let thresholds = SystemThresholds::from_settings(&settings);
+
+        supervisor.spawn(SYSTEM_MONITOR_TASK, move || {
+            let db_pool = db_pool.clone();
+            let sources = sources.clone();
+
+            async move {
+                let mut previous: Option<SystemReading> = None;
+                let mut failing = false;
+
+                loop {
+                    match sources.read() {
+                        Ok(reading) => {
+                            failing = false;
+                            let sample = reading.sample(previous.as_ref(), unix_now());
+
+                            if let Err(e) = record_sample(&db_pool, &sample, &thresholds).await {
+                                error!(error = %e, "Failed to record system sample");
+                            }
+                            previous = Some(reading);
+                        }
+                        // logged once, not every interval, e.g. on hosts without /proc
+                        Err(e) if !failing => {
+                            failing = true;
+                            warn!(error = %e, "Failed to read the system");
+                        }
+                        Err(_) => {}
+                    }
+
+                    sleep(interval).await;
+                }
+            }
+        });
+    }
+}
@@ -1,17 +1,31 @@
+use clap::Parser;
+use cli::Cli;
 use events::fairing::EventHandlerFairing;
+use infra::alerting::AlertingFairing;
+use infra::buddy_backups::BuddyBackupFairing;
 use infra::db::{run_migrations, MainDb};
+use infra::deprecation::DeprecationFairing;
+use infra::health_reports::HealthReportFairing;
+use infra::logging::{in_request_span, RequestSpanFairing};
+use infra::metrics::MetricsFairing;
+use infra::settings::{Settings, SettingsError};
 use infra::spa_server::SpaServer;
+use infra::supervisor::Supervisor;
+use infra::system_monitor::{SystemMonitorFairing, SystemSources};
 use panda_comms::container::P2PandaContainer;
 use panda_comms::fairing::P2PandaCommsFairing;
 use panda_comms::lores_events::LoResEvent;
+use panda_comms::regions::Regions;
+use repos::helpers::NETWORK_CONFIG_ID;
 use rocket::fairing::AdHoc;
+use rocket::figment::Figment;
 use rocket::fs::{FileServer, Options};
 use rocket::response::Redirect;
-use rocket::serde::Deserialize;
-use rocket::tokio;
+use rocket::{tokio, Build, Rocket};
 use std::env;
 use tokio::sync::mpsc;
 
+mod cli;
 mod events;
 mod infra;
 mod panda_comms;
@@ -36,52 +50,130 @@ fn admin_redirect() -> Redirect {
     Redirect::to("/admin")
 }
 
-#[derive(Deserialize)]
-#[serde(crate = "rocket::serde")]
-struct Config {
-    frontend_asset_path: String,
+#[rocket::main]
+async fn main() {
+    let cli = Cli::parse();
+
+    if !cli.serves() {
+        if let Err(e) = cli::run(cli).await {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let rocket = match rocket() {
+        Ok(rocket) => rocket,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(e) = rocket.launch().await {
+        tracing::error!(error = %e, "Failed to launch");
+        std::process::exit(1);
+    }
 }
 
-#[launch]
-#[rocket::main]
-async fn rocket() -> _ {
-    let figment = rocket::Config::figment().merge(("databases.main_db.url", env::var("DATABASE_URL").expect("DATABASE_URL must be set")));
+fn rocket() -> Result<Rocket<Build>, SettingsError> {
+    let figment = infra::settings::figment().merge(("databases.main_db.url", env::var("DATABASE_URL").expect("DATABASE_URL must be set")));
+    let settings = Settings::extract(&figment)?;
+    infra::logging::init(&settings);
+    let (channel_tx, channel_rx): (mpsc::Sender<LoResEvent>, mpsc::Receiver<LoResEvent>) = mpsc::channel(settings.event_channel_size);
 
-    let mut rocket = rocket::custom(figment);
+    Ok(build_rocket(figment, settings, channel_tx, channel_rx))
+}
 
-    let config: Config = rocket.figment().extract().expect("config");
+/// Builds the node from a figment and the settings read from it, so that several nodes can be
+/// assembled in one process (see the mesh tests) with their own database and without the public
+/// relay. Events received by the container are sent to `channel_tx` and handled from
+/// `channel_rx`, which lets tests sit in between the two.
+fn build_rocket(figment: Figment, settings: Settings, channel_tx: mpsc::Sender<LoResEvent>, channel_rx: mpsc::Receiver<LoResEvent>) -> Rocket<Build> {
+    let database_url: String = figment
+        .extract_inner("databases.main_db.url")
+        .unwrap_or_default();
+    let system_sources = SystemSources::new(&settings, &database_url);
+    let mut rocket = rocket::custom(figment);
 
-    // log the config
-    println!("Config static_asset_path: {:?}", config.frontend_asset_path);
+    tracing::info!(settings = ?settings.redacted(), "Loaded settings");
 
     // state
-    let (channel_tx, channel_rx): (mpsc::Sender<LoResEvent>, mpsc::Receiver<LoResEvent>) = mpsc::channel(32);
-    rocket = rocket.manage(P2PandaContainer::new(channel_tx));
+    let supervisor = Supervisor::default();
+    let container = P2PandaContainer::new(
+        channel_tx,
+        settings.relay_url(),
+        settings.network_options(),
+        settings.mesh_topic(),
+        supervisor.clone(),
+        NETWORK_CONFIG_ID as i64,
+    );
+    rocket = rocket
+        .manage(Regions::new(container.clone(), database_url, settings.clone(), supervisor.clone()))
+        .manage(container)
+        .manage(supervisor)
+        .manage(settings.clone());
 
     // fairings
     rocket = rocket
+        .attach(RequestSpanFairing)
         .attach(infra::cors::cors_fairing())
         .attach(MainDb::init())
         .attach(AdHoc::try_on_ignite("DB Migrations", run_migrations))
         .attach(EventHandlerFairing::new(channel_rx))
-        .attach(P2PandaCommsFairing::default());
+        .attach(P2PandaCommsFairing::default())
+        .attach(BuddyBackupFairing::default())
+        .attach(SystemMonitorFairing::new(system_sources))
+        .attach(HealthReportFairing::default())
+        .attach(AlertingFairing::default())
+        .attach(MetricsFairing);
 
     // frontend
-    if !config.frontend_asset_path.is_empty() {
+    if !settings.frontend_asset_path.is_empty() {
         rocket = rocket
-            .mount("/admin/assets", FileServer::from(config.frontend_asset_path.clone() + "/assets").rank(3))
+            .mount(
+                "/admin/assets",
+                FileServer::from(settings.frontend_asset_path.clone() + "/assets").rank(3),
+            )
             .mount(
                 "/admin",
-                SpaServer::new(config.frontend_asset_path.clone() + "/index.html", Options::IndexFile),
+                SpaServer::new(settings.frontend_asset_path.clone() + "/index.html", Options::IndexFile),
             )
     }
 
     // routes
-    rocket
+    let api_versions = routes::api_versions();
+    let current_version = api_versions
+        .last()
+        .expect("an API version")
+        .base();
+    let mut deprecation = DeprecationFairing::default();
+
+    rocket = rocket
+        .register("/api", infra::api_error::catchers())
         .mount("/", routes![admin_redirect])
         .mount("/hello", routes![hello])
-        .mount("/api/this_node", routes::this_node::routes())
-        .mount("/api/this_region", routes::this_region::routes())
-        .mount("/api/this_p2panda_node", routes::this_p2panda_node::routes())
-        .mount("/api/apps", routes::apps::routes())
+        .mount("/metrics", infra::metrics::routes())
+        .mount("/", infra::probes::routes())
+        .mount("/", infra::openapi::viewer(infra::openapi::document(&api_versions)));
+
+    for version in api_versions {
+        let base = version.base();
+
+        for module in version.modules {
+            let mount_point = format!("{}{}", base, module.path);
+            if version.deprecated {
+                deprecation.deprecate(mount_point.clone(), format!("{}{}", current_version, module.path));
+            }
+            rocket = rocket.mount(mount_point, in_request_span(module.routes));
+        }
+    }
+
+    for module in routes::unversioned_modules() {
+        let mount_point = format!("/api{}", module.path);
+        deprecation.deprecate(mount_point.clone(), format!("{}{}", current_version, module.path));
+        rocket = rocket.mount(mount_point, in_request_span(module.routes));
+    }
+
+    rocket.attach(deprecation)
 }
@@ -1,7 +1,9 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use futures_util::StreamExt;
 use iroh::NodeAddr;
-use p2panda_core::identity::PUBLIC_KEY_LEN;
-use p2panda_core::{PrivateKey, PublicKey};
+use p2panda_blobs::{Blobs, DownloadBlobEvent, ImportBlobEvent};
+use p2panda_core::identity::{PRIVATE_KEY_LEN, PUBLIC_KEY_LEN};
+use p2panda_core::{Hash, PrivateKey, PublicKey};
 use p2panda_net::{NodeAddress, RelayUrl, SystemEvent};
 use p2panda_node::api::NodeApi;
 use p2panda_node::extensions::{LogId, NodeExtensions};
@@ -9,20 +11,135 @@ use p2panda_node::node::Node;
 use p2panda_node::stream::{EventData, StreamEvent};
 use p2panda_node::topic::{Topic, TopicMap};
 use p2panda_store::MemoryStore;
+use rocket::serde::{Deserialize, Serialize};
 use rocket::tokio::{self};
+use std::collections::VecDeque;
+use std::fmt;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
 use tokio::sync::{broadcast, mpsc, Mutex};
+use tracing::{debug, error, info, info_span, warn, Instrument};
+use utoipa::ToSchema;
+use uuid::Uuid;
 
-use super::lores_events::{LoResEvent, LoResEventHeader, LoResEventPayload, NodeAnnounced};
+use super::identity::{self, PrivateKeyStatus};
+use super::lores_events::{
+    AlertCondition, AlertRuleDeleted, AlertRuleSaved, BackupOffered, BackupRequested, BackupStored, DirectMessageSent, KeyRotated, LoResEvent,
+    LoResEventHeader, LoResEventPayload, NodeAnnounced, NodeHealthReported, NodeLeft, NoticePosted, NoticePriority,
+};
+use super::presence::{EphemeralMessage, EphemeralMessagePayload, Heartbeat, PresenceTable, HEARTBEAT_INTERVAL};
+use super::sealed_box;
+use crate::infra::logging::Redacted;
+use crate::infra::metrics::metrics;
+use crate::infra::supervisor::Supervisor;
+use crate::infra::time::unix_now;
 
-const RELAY_URL: &str = "https://staging-euw1-1.relay.iroh.network/";
-const TOPIC_NAME: &str = "lores_mesh";
-const LOG_ID: &str = "lores_mesh";
+/// The gossip topic the region's events travel on, and the log our own events are written to.
+#[derive(Clone, Debug)]
+pub struct MeshTopic {
+    pub name: String,
+    pub log_id: String,
+}
+
+/// Where the node is in its lifecycle.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+#[serde(crate = "rocket::serde", tag = "state", content = "reason", rename_all = "snake_case")]
+pub enum ContainerState {
+    /// Not running: there is no region or private key yet, the key is locked, or the node was
+    /// shut down.
+    Unconfigured,
+    Starting,
+    Running,
+    Stopping,
+    /// Starting or stopping went wrong, with the reason.
+    Failed(String),
+}
+
+impl fmt::Display for ContainerState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContainerState::Unconfigured => write!(f, "unconfigured"),
+            ContainerState::Starting => write!(f, "starting"),
+            ContainerState::Running => write!(f, "running"),
+            ContainerState::Stopping => write!(f, "stopping"),
+            ContainerState::Failed(reason) => write!(f, "failed ({})", reason),
+        }
+    }
+}
+
+/// Returned by container operations called in a state that doesn't allow them.
+#[derive(Debug, thiserror::Error)]
+pub enum ContainerError {
+    /// The operation needs the network while it isn't running, e.g. before joining a region.
+    #[error("Network not started")]
+    NotRunning,
+
+    #[error("Network already running")]
+    AlreadyRunning,
+
+    #[error("Private key is locked, unlock it to start the network")]
+    KeyLocked,
+
+    #[error("Node is {0}, try again once it is done")]
+    Busy(ContainerState),
+}
+
+/// Names the listeners of a running node are supervised under.
+pub const NETWORK_EVENTS_TASK: &str = "p2panda_network_events";
+pub const MESSAGES_TASK: &str = "p2panda_messages";
+pub const HEARTBEATS_TASK: &str = "p2panda_heartbeats";
+/// Name a restart asked for by a closed stream runs under, outside the listener it stops.
+pub const RESTART_TASK: &str = "p2panda_restart";
+
+/// How long a leaving node stays online after saying so, for the region to pick up `NodeLeft`.
+const LEAVE_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+/// How many sync failures are kept for diagnostics.
+const SYNC_FAILURES_KEPT: usize = 50;
+
+/// A sync with a peer that failed, kept for diagnostics.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct SyncFailure {
+    pub peer: String,
+    pub failed_at: i64,
+}
 
+/// Cheap to clone, every clone controls the same node.
+#[derive(Clone)]
 pub struct P2PandaContainer {
     params: Arc<Mutex<NodeParams>>,
+    state: Arc<Mutex<ContainerState>>,
     node_api: Arc<Mutex<Option<NodeApi<NodeExtensions>>>>,
     events_tx: mpsc::Sender<LoResEvent>,
+    network_options: NetworkOptions,
+    topic: MeshTopic,
+    presence: PresenceTable,
+    started_at: Instant,
+    sync_failures: Arc<Mutex<VecDeque<SyncFailure>>>,
+    supervisor: Supervisor,
+    region_id: i64,
+}
+
+/// How the network of a node is set up, from the settings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NetworkOptions {
+    /// UDP ports to bind, 0 for any free one.
+    pub port_v4: u16,
+    pub port_v6: u16,
+    pub mdns_discovery: bool,
+}
+
+impl NetworkOptions {
+    /// The same options on ports that are free, for a node sharing the host with another one.
+    pub fn on_free_ports(self) -> Self {
+        NetworkOptions {
+            port_v4: 0,
+            port_v6: 0,
+            ..self
+        }
+    }
 }
 
 #[derive(Default, Clone)]
@@ -30,14 +147,74 @@ pub struct NodeParams {
     pub private_key: Option<PrivateKey>,
     pub network_name: Option<String>,
     pub bootstrap_node_id: Option<PublicKey>,
+    pub relay_url: Option<RelayUrl>,
+    pub key_status: PrivateKeyStatus,
+    /// Kept while unlocked, so a replaced key can be stored encrypted again.
+    pub key_passphrase: Option<String>,
+}
+
+/// Shows the node id in place of the private key, and hides the passphrase.
+impl fmt::Debug for NodeParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NodeParams")
+            .field(
+                "node_id",
+                &self
+                    .private_key
+                    .as_ref()
+                    .map(|key| key.public_key().to_hex()),
+            )
+            .field("network_name", &self.network_name)
+            .field("bootstrap_node_id", &self.bootstrap_node_id.map(|key| key.to_hex()))
+            .field("relay_url", &self.relay_url)
+            .field("key_status", &self.key_status)
+            .field("key_passphrase", &self.key_passphrase.as_ref().map(|_| Redacted))
+            .finish()
+    }
 }
 
 impl P2PandaContainer {
-    pub fn new(events_tx: mpsc::Sender<LoResEvent>) -> Self {
-        let params = Arc::new(Mutex::new(NodeParams::default()));
+    pub fn new(
+        events_tx: mpsc::Sender<LoResEvent>,
+        relay_url: Option<RelayUrl>,
+        network_options: NetworkOptions,
+        topic: MeshTopic,
+        supervisor: Supervisor,
+        region_id: i64,
+    ) -> Self {
+        let params = Arc::new(Mutex::new(NodeParams {
+            relay_url,
+            ..Default::default()
+        }));
         let node_api = Arc::new(Mutex::new(None));
 
-        P2PandaContainer { params, node_api, events_tx }
+        P2PandaContainer {
+            params,
+            state: Arc::new(Mutex::new(ContainerState::Unconfigured)),
+            node_api,
+            events_tx,
+            network_options,
+            topic,
+            presence: PresenceTable::default(),
+            started_at: Instant::now(),
+            sync_failures: Arc::new(Mutex::new(VecDeque::new())),
+            supervisor,
+            region_id,
+        }
+    }
+
+    /// The region this node takes part in, `NETWORK_CONFIG_ID` for the first one.
+    pub fn region_id(&self) -> i64 {
+        self.region_id
+    }
+
+    pub async fn state(&self) -> ContainerState {
+        self.state.lock().await.clone()
+    }
+
+    async fn set_state(&self, state: ContainerState) {
+        info!(%state, "P2Panda node state changed");
+        *self.state.lock().await = state;
     }
 
     pub async fn get_params(&self) -> NodeParams {
@@ -45,6 +222,20 @@ impl P2PandaContainer {
         params_lock.clone()
     }
 
+    /// Runs the background work of this node's region, like fetching a backup for a buddy.
+    pub fn supervisor(&self) -> &Supervisor {
+        &self.supervisor
+    }
+
+    /// Forgets everything loaded from the DB, keeping only how to reach the network.
+    pub async fn clear_params(&self) {
+        let mut params_lock = self.params.lock().await;
+        *params_lock = NodeParams {
+            relay_url: params_lock.relay_url.clone(),
+            ..Default::default()
+        };
+    }
+
     pub async fn set_network_name(&self, network_name: String) {
         let mut params_lock = self.params.lock().await;
         params_lock.network_name = Some(network_name);
@@ -55,77 +246,146 @@ impl P2PandaContainer {
         params_lock.private_key = Some(private_key);
     }
 
+    /// Sets the key as it was loaded from the DB, `None` while it is locked.
+    pub async fn set_loaded_private_key(&self, private_key: Option<PrivateKey>, key_status: PrivateKeyStatus, key_passphrase: Option<String>) {
+        let mut params_lock = self.params.lock().await;
+        params_lock.private_key = private_key;
+        params_lock.key_status = key_status;
+        params_lock.key_passphrase = key_passphrase;
+    }
+
     pub async fn set_bootstrap_node_id(&self, bootstrap_node_id: Option<PublicKey>) {
         let mut params_lock = self.params.lock().await;
         params_lock.bootstrap_node_id = bootstrap_node_id;
     }
 
     pub async fn restart(&self) -> Result<()> {
-        println!("Restarting node: shutting down");
+        info!("Restarting node");
         self.shutdown().await?;
-        println!("Restarting node: starting");
         self.start().await?;
-        println!("Restarting node: done");
 
         Ok(())
     }
 
     pub async fn shutdown(&self) -> Result<()> {
-        // let node = self.node.lock().await;
-        // let node = node
-        //     .as_ref()
-        //     .ok_or(anyhow::Error::msg("Network not started"))?;
+        let was_running = {
+            let mut state = self.state.lock().await;
+            match &*state {
+                ContainerState::Starting | ContainerState::Stopping => return Err(ContainerError::Busy(state.clone()).into()),
+                ContainerState::Running => {
+                    *state = ContainerState::Stopping;
+                    true
+                }
+                ContainerState::Unconfigured | ContainerState::Failed(_) => false,
+            }
+        };
 
-        // node.shutdown().await?;
-        // self.set_node(None).await;
+        self.supervisor.stop(HEARTBEATS_TASK);
+        self.supervisor.stop(NETWORK_EVENTS_TASK);
+        self.supervisor.stop(MESSAGES_TASK);
 
-        Ok(())
+        let node_api = self.node_api.lock().await.take();
+
+        // nothing to do if the network was never started
+        let result = match node_api {
+            Some(node_api) => node_api.node.shutdown().await,
+            None => Ok(()),
+        };
+
+        if was_running {
+            match &result {
+                Ok(()) => self.set_state(ContainerState::Unconfigured).await,
+                Err(e) => {
+                    self.set_state(ContainerState::Failed(e.to_string()))
+                        .await
+                }
+            }
+        }
+
+        result
     }
 
     pub async fn start(&self) -> Result<()> {
-        println!("Starting client");
+        debug!("Starting node");
+
+        {
+            let mut state = self.state.lock().await;
+            match &*state {
+                ContainerState::Running => return Err(ContainerError::AlreadyRunning.into()),
+                ContainerState::Starting | ContainerState::Stopping => return Err(ContainerError::Busy(state.clone()).into()),
+                ContainerState::Unconfigured | ContainerState::Failed(_) => (),
+            }
+            // claimed before looking at the params, so two concurrent starts can't both go ahead
+            *state = ContainerState::Starting;
+        }
 
         let params = self.get_params().await;
+        debug!(?params, "Node params");
 
-        let private_key: Option<PrivateKey> = params.private_key;
-        let network_name: Option<String> = params.network_name;
         let boostrap_node_id: Option<PublicKey> = params.bootstrap_node_id;
+        let relay_url: Option<RelayUrl> = params.relay_url;
 
-        if private_key.is_none() {
-            println!("P2Panda: No private key found, not starting network");
-            return Ok(());
+        if params.key_status == PrivateKeyStatus::Locked {
+            self.set_state(ContainerState::Unconfigured).await;
+            return Err(ContainerError::KeyLocked.into());
         }
 
-        if network_name.is_none() {
-            println!("P2Panda: No network name found, not starting network");
+        let Some(private_key) = params.private_key else {
+            info!("No private key found, not starting network");
+            self.set_state(ContainerState::Unconfigured).await;
             return Ok(());
-        }
+        };
 
-        let private_key = private_key.unwrap();
-        let network_name = network_name.unwrap();
+        let Some(network_name) = params.network_name else {
+            info!("No network name found, not starting network");
+            self.set_state(ContainerState::Unconfigured).await;
+            return Ok(());
+        };
 
-        self.start_for(private_key, network_name, boostrap_node_id)
+        match self
+            .start_for(private_key, network_name, boostrap_node_id, relay_url)
             .await
+        {
+            Ok(()) => {
+                self.set_state(ContainerState::Running).await;
+                Ok(())
+            }
+            Err(e) => {
+                self.set_state(ContainerState::Failed(e.to_string()))
+                    .await;
+                Err(e)
+            }
+        }
     }
 
-    async fn start_for(&self, private_key: PrivateKey, network_name: String, boostrap_node_id: Option<PublicKey>) -> Result<()> {
-        let relay_url: RelayUrl = RELAY_URL.parse().unwrap();
-        let temp_blobs_root_dir = tempfile::tempdir().expect("temp dir");
+    async fn start_for(
+        &self,
+        private_key: PrivateKey,
+        network_name: String,
+        boostrap_node_id: Option<PublicKey>,
+        relay_url: Option<RelayUrl>,
+    ) -> Result<()> {
+        let temp_blobs_root_dir = tempfile::tempdir()?;
 
         let store = MemoryStore::<LogId, NodeExtensions>::new();
         let topic_map = TopicMap::new();
 
-        println!(
-            "Starting node. Network name: {}, Bootstrap ID: {:?}",
+        info!(
             network_name,
-            boostrap_node_id.map(|key| key.to_string())
+            bootstrap_node_id = boostrap_node_id.map(|key| key.to_hex()),
+            node_id = %private_key.public_key().to_hex(),
+            network_options = ?self.network_options,
+            "Starting node"
         );
 
         let (node, stream_rx, network_events_rx) = Node::new(
             network_name,
             private_key.clone(),
             boostrap_node_id,
-            Some(relay_url),
+            relay_url,
+            self.network_options.port_v4,
+            self.network_options.port_v6,
+            self.network_options.mdns_discovery,
             store,
             temp_blobs_root_dir.into_path(),
             topic_map.clone(),
@@ -137,176 +397,644 @@ impl P2PandaContainer {
         let public_key = private_key.public_key();
 
         node_api
-            .add_topic_log(&public_key, TOPIC_NAME, LOG_ID)
+            .add_topic_log(&public_key, &self.topic.name, &self.topic.log_id)
             .await?;
 
         // subscribe to main topic
-        node_api.subscribe_persisted(TOPIC_NAME).await?;
+        node_api
+            .subscribe_persisted(&self.topic.name)
+            .await?;
 
         // put the node in the container
         self.set_node_api(Some(node_api)).await;
 
-        self.listen_for_messages(stream_rx, network_events_rx);
+        self.listen_for_messages(stream_rx, network_events_rx, private_key.clone());
+        self.send_heartbeats(private_key);
 
         Ok(())
     }
 
-    pub async fn get_public_key(&self) -> Result<String, Box<dyn std::error::Error>> {
+    pub async fn get_public_key(&self) -> Result<String> {
         let node_api = self.node_api.lock().await;
-        let node_api = node_api.as_ref().ok_or("Network not started")?;
+        let node_api = node_api
+            .as_ref()
+            .ok_or(ContainerError::NotRunning)?;
 
         let node_id = node_api.node.network.node_id();
         Ok(node_id.to_string())
     }
 
-    pub async fn get_node_addr(&self) -> NodeAddr {
+    pub async fn get_node_addr(&self) -> Result<NodeAddr> {
         let node_api = self.node_api.lock().await;
-        let node_api = node_api.as_ref().unwrap();
+        let node_api = node_api
+            .as_ref()
+            .ok_or(ContainerError::NotRunning)?;
         let network = &node_api.node.network;
         let endpoint = network.endpoint();
-        endpoint.node_addr().await.unwrap()
+        endpoint.node_addr().await
     }
 
     pub async fn known_peers(&self) -> Result<Vec<NodeAddress>> {
         let node_api = self.node_api.lock().await;
-        let node_api = node_api.as_ref().unwrap();
+        let node_api = node_api
+            .as_ref()
+            .ok_or(ContainerError::NotRunning)?;
         node_api.node.network.known_peers().await
     }
 
+    /// Tells the node where to reach a peer, on top of the peers it discovers itself. Tests use it
+    /// to put nodes behind a proxy.
+    #[cfg(test)]
+    pub async fn add_peer(&self, peer: NodeAddress) -> Result<()> {
+        let node_api = self.node_api.lock().await;
+        let node_api = node_api
+            .as_ref()
+            .ok_or(ContainerError::NotRunning)?;
+        node_api.node.network.add_peer(peer).await
+    }
+
+    /// Received events waiting for the event handler.
+    pub fn event_queue_depth(&self) -> usize {
+        self.events_tx.max_capacity() - self.events_tx.capacity()
+    }
+
+    /// The most recent failed syncs, oldest first.
+    pub async fn recent_sync_failures(&self) -> Vec<SyncFailure> {
+        self.sync_failures
+            .lock()
+            .await
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    pub fn presence(&self) -> &PresenceTable {
+        &self.presence
+    }
+
     async fn set_node_api(&self, maybe_node_api: Option<NodeApi<NodeExtensions>>) {
         let mut node_api_lock = self.node_api.lock().await;
         *node_api_lock = maybe_node_api;
     }
 
     pub async fn announce_node(&self, node_name: String) -> Result<()> {
-        let mut node_api = self.node_api.lock().await;
+        let node_announced = NodeAnnounced { name: node_name.clone() };
+
+        self.publish_event(LoResEventPayload::NodeAnnounced(node_announced))
+            .await?;
+
+        info!(node_name, "Announced node");
+
+        Ok(())
+    }
+
+    /// Tells the region this node leaves it, then stops the node and forgets the region. A node
+    /// that isn't running can't tell anyone, and just stops.
+    pub async fn leave(&self) -> Result<()> {
+        let node_left = NodeLeft { left_at: unix_now() };
+
+        match self
+            .publish_event(LoResEventPayload::NodeLeft(node_left))
+            .await
+        {
+            Ok(()) => {
+                info!("Announced leaving the region");
+                tokio::time::sleep(LEAVE_GRACE_PERIOD).await;
+            }
+            Err(e) => warn!(error = %e, "Failed to announce leaving the region"),
+        }
+
+        self.shutdown().await?;
+
+        {
+            let mut params_lock = self.params.lock().await;
+            params_lock.network_name = None;
+            params_lock.bootstrap_node_id = None;
+        }
+        self.presence.clear().await;
+        self.sync_failures.lock().await.clear();
+        self.set_state(ContainerState::Unconfigured).await;
+
+        Ok(())
+    }
+
+    pub async fn post_notice(&self, title: String, body: String, priority: NoticePriority, expires_at: Option<i64>) -> Result<NoticePosted> {
+        let notice_posted = NoticePosted {
+            id: Uuid::new_v4().to_string(),
+            title,
+            body,
+            priority,
+            posted_at: unix_now(),
+            expires_at,
+        };
+
+        self.publish_event(LoResEventPayload::NoticePosted(notice_posted.clone()))
+            .await?;
+
+        Ok(notice_posted)
+    }
+
+    pub async fn send_direct_message(&self, recipient_node_id: String, body: String) -> Result<DirectMessageSent> {
+        let recipient = build_public_key_from_hex(recipient_node_id.clone()).ok_or(anyhow::Error::msg("Invalid recipient node id"))?;
+        let sealed_body = sealed_box::seal(&recipient, body.as_bytes())?;
+
+        let direct_message_sent = DirectMessageSent {
+            id: Uuid::new_v4().to_string(),
+            recipient_node_id,
+            sealed_body_hex: hex::encode(sealed_body),
+            sent_at: unix_now(),
+        };
+
+        self.publish_event(LoResEventPayload::DirectMessageSent(direct_message_sent.clone()))
+            .await?;
+
+        Ok(direct_message_sent)
+    }
+
+    /// Tells the region, still using the current key, that this node is moving to `new_private_key`.
+    /// The caller is expected to store the new key and restart the node with it afterwards.
+    pub async fn announce_key_rotation(&self, new_private_key: &PrivateKey) -> Result<()> {
+        let params = self.get_params().await;
+        let old_private_key = params
+            .private_key
+            .ok_or(anyhow::Error::msg("No private key"))?;
+
+        let key_rotated = KeyRotated {
+            new_node_id: new_private_key.public_key().to_hex(),
+            proof_hex: identity::rotation_proof(&old_private_key.public_key().to_hex(), new_private_key),
+        };
+
+        self.publish_event(LoResEventPayload::KeyRotated(key_rotated))
+            .await
+    }
+
+    /// Adds `data` to our blob store, from where other nodes can download it by its hash.
+    pub async fn add_blob(&self, data: Vec<u8>) -> Result<Hash> {
+        let blobs = self.blobs().await?;
+        let mut events = Box::pin(blobs.import_data(data).await);
+
+        match events.next().await {
+            Some(ImportBlobEvent::Done(hash)) => Ok(hash),
+            Some(ImportBlobEvent::Abort(e)) => Err(e),
+            None => Err(anyhow::Error::msg("Blob import ended without a result")),
+        }
+    }
+
+    /// Downloads a blob from whichever node in the region has it, and returns its content unless
+    /// it is larger than `max_bytes`.
+    pub async fn fetch_blob(&self, hash: Hash, max_bytes: u64) -> Result<Vec<u8>> {
+        let blobs = self.blobs().await?;
+        let mut events = Box::pin(blobs.download_blob(hash).await);
+
+        match events.next().await {
+            Some(DownloadBlobEvent::Done) => (),
+            Some(DownloadBlobEvent::Abort(e)) => return Err(e),
+            None => return Err(anyhow::Error::msg("Blob download ended without a result")),
+        }
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(hash.to_hex());
+        blobs.export_blob(hash, &path).await?;
+
+        let size = tokio::fs::metadata(&path).await?.len();
+        if size > max_bytes {
+            return Err(anyhow!("Blob of {} bytes is larger than {} bytes", size, max_bytes));
+        }
+
+        Ok(tokio::fs::read(&path).await?)
+    }
+
+    /// A handle to the blob store, so that long transfers don't hold the node lock.
+    async fn blobs(&self) -> Result<Blobs<Topic, MemoryStore<LogId, NodeExtensions>>> {
+        let node_api = self.node_api.lock().await;
         let node_api = node_api
-            .as_mut()
-            .ok_or(anyhow::Error::msg("Network not started"))?;
+            .as_ref()
+            .ok_or(ContainerError::NotRunning)?;
 
-        let node_announced = NodeAnnounced { name: node_name.clone() };
-        let event_payload = LoResEventPayload::NodeAnnounced(node_announced);
+        Ok(node_api.node.blobs.clone())
+    }
 
-        let payload = serde_json::to_vec(&event_payload)?;
+    pub async fn store_backup_with(&self, buddy_node_id: String, blob_hash: String, created_at: i64) -> Result<()> {
+        let backup_stored = BackupStored {
+            buddy_node_id,
+            blob_hash,
+            created_at,
+        };
 
-        let extensions = NodeExtensions {
-            log_id: Some(LogId(LOG_ID.to_string())),
-            ..Default::default()
+        self.publish_event(LoResEventPayload::BackupStored(backup_stored))
+            .await
+    }
+
+    pub async fn request_backup(&self, owner_node_id: String) -> Result<()> {
+        self.publish_event(LoResEventPayload::BackupRequested(BackupRequested { owner_node_id }))
+            .await
+    }
+
+    pub async fn offer_backup(&self, owner_node_id: String, requested_by: String, blob_hash: String, created_at: i64) -> Result<()> {
+        let backup_offered = BackupOffered {
+            owner_node_id,
+            requested_by,
+            blob_hash,
+            created_at,
         };
 
-        node_api
-            .publish_persisted(TOPIC_NAME, &payload, Some(LOG_ID), Some(extensions))
+        self.publish_event(LoResEventPayload::BackupOffered(backup_offered))
+            .await
+    }
+
+    pub async fn save_alert_rule(&self, id: String, name: String, condition: AlertCondition) -> Result<AlertRuleSaved> {
+        let alert_rule_saved = AlertRuleSaved {
+            id,
+            name,
+            condition,
+            updated_at: unix_now(),
+        };
+
+        self.publish_event(LoResEventPayload::AlertRuleSaved(alert_rule_saved.clone()))
             .await?;
 
-        println!("Announcing node: {}", node_name);
+        Ok(alert_rule_saved)
+    }
+
+    pub async fn delete_alert_rule(&self, id: String) -> Result<()> {
+        let alert_rule_deleted = AlertRuleDeleted { id, deleted_at: unix_now() };
+
+        self.publish_event(LoResEventPayload::AlertRuleDeleted(alert_rule_deleted))
+            .await
+    }
+
+    pub async fn report_health(&self, report: NodeHealthReported) -> Result<()> {
+        self.publish_event(LoResEventPayload::NodeHealthReported(report))
+            .await
+    }
+
+    /// Publishes an event to the region and hands it to our own event handler too, as our own
+    /// operations don't come back to us over the network.
+    async fn publish_event(&self, event_payload: LoResEventPayload) -> Result<()> {
+        let author_node_id = {
+            let mut node_api = self.node_api.lock().await;
+            let node_api = node_api
+                .as_mut()
+                .ok_or(ContainerError::NotRunning)?;
+
+            let payload = serde_json::to_vec(&event_payload)?;
+
+            let extensions = NodeExtensions {
+                log_id: Some(LogId(self.topic.log_id.clone())),
+                ..Default::default()
+            };
+
+            node_api
+                .publish_persisted(&self.topic.name, &payload, Some(&self.topic.log_id), Some(extensions))
+                .await?;
+
+            node_api.node.network.node_id().to_hex()
+        };
+
+        let header = LoResEventHeader { author_node_id };
+        self.events_tx
+            .send(LoResEvent::new(header, event_payload))
+            .await?;
 
         Ok(())
     }
 
+    /// Asks who is online, then keeps publishing our own heartbeat until the node shuts down.
+    fn send_heartbeats(&self, private_key: PrivateKey) {
+        let container = self.clone();
+        self.supervisor.spawn(HEARTBEATS_TASK, move || {
+            let container = container.clone();
+            let private_key = private_key.clone();
+
+            async move {
+                if let Err(err) = publish_who_is_online(&container.node_api, &container.topic, &private_key).await {
+                    warn!(error = %err, "Failed to ask who is online");
+                }
+
+                loop {
+                    let published = publish_heartbeat(
+                        &container.node_api,
+                        &container.topic,
+                        &private_key,
+                        &container.presence,
+                        container.started_at,
+                    )
+                    .await;
+                    if let Err(err) = published {
+                        warn!(error = %err, "Failed to publish heartbeat");
+                    }
+
+                    tokio::time::sleep(HEARTBEAT_INTERVAL).await;
+                }
+            }
+        });
+    }
+
+    /// Handles what the network reports and the operations it receives, each in a task kept
+    /// running by the supervisor. The streams only close when the node shuts down, so when one
+    /// closes while the node is running the node is restarted.
     fn listen_for_messages(
         &self,
-        mut stream_rx: mpsc::Receiver<StreamEvent<NodeExtensions>>,
-        mut network_events_rx: broadcast::Receiver<SystemEvent<Topic>>,
+        stream_rx: mpsc::Receiver<StreamEvent<NodeExtensions>>,
+        network_events_rx: broadcast::Receiver<SystemEvent<Topic>>,
+        private_key: PrivateKey,
     ) {
-        let node_api = self.node_api.clone();
+        let stream_rx = Arc::new(Mutex::new(stream_rx));
+        let network_events_rx = Arc::new(Mutex::new(network_events_rx));
 
         // handle received network events. This exists mainly for debugging
         // at the moment, but the addition of a peer to the topic map on the
         // PeerDiscovered event is important.
-        tokio::spawn(async move {
-            println!("Listening for network events...");
-            while let Ok(event) = network_events_rx.recv().await {
-                let event: SystemEvent<Topic> = event;
-                match event {
-                    SystemEvent::GossipJoined { topic_id, peers } => {
-                        println!("Gossip joined: {:?}", topic_id);
-                        println!(
-                            "Peers: {:?}",
-                            peers
-                                .iter()
-                                .map(|peer| peer.to_hex())
-                                .collect::<Vec<_>>()
-                        );
-                    }
-                    SystemEvent::GossipLeft { topic_id } => {
-                        println!("Gossip left: {:?}", topic_id);
-                    }
-                    SystemEvent::GossipNeighborUp { topic_id: _, peer } => {
-                        println!("Gossip neighbor up: {:?}", peer.to_hex());
-                    }
-                    SystemEvent::GossipNeighborDown { topic_id: _, peer } => {
-                        println!("Gossip neighbor down: {:?}", peer.to_hex());
-                    }
-                    SystemEvent::PeerDiscovered { peer } => {
-                        println!("Peer discovered: {:?}", peer.to_hex());
-                        let mut node_api = node_api.lock().await;
-                        let node_api = node_api.as_mut().unwrap();
-
-                        node_api
-                            .add_topic_log(&peer, TOPIC_NAME, LOG_ID)
-                            .await
-                            .unwrap();
-                    }
-                    SystemEvent::SyncStarted { topic, peer } => {
-                        println!("Sync started: topic {:?}, peer {:?}", topic, peer.to_hex());
-                    }
-                    SystemEvent::SyncDone { topic, peer } => {
-                        println!("Sync done: topic {:?}, peer {:?}", topic, peer.to_hex());
-                    }
-                    SystemEvent::SyncFailed { topic, peer } => {
-                        println!("Sync failed: topic {:?}, peer {:?}", topic, peer.to_hex());
+        let container = self.clone();
+        self.supervisor
+            .spawn(NETWORK_EVENTS_TASK, move || {
+                let network_events_rx = network_events_rx.clone();
+                let container = container.clone();
+
+                async move {
+                    info!("Listening for network events");
+                    let mut network_events_rx = network_events_rx.lock().await;
+
+                    loop {
+                        match network_events_rx.recv().await {
+                            Ok(event) => {
+                                handle_network_event(
+                                    event,
+                                    &container.node_api,
+                                    &container.topic,
+                                    &container.sync_failures,
+                                    container.region_id,
+                                )
+                                .await
+                            }
+                            Err(RecvError::Lagged(missed)) => warn!(missed, "Missed network events"),
+                            Err(RecvError::Closed) => break,
+                        }
                     }
+                    info!("Network events stream closed");
+                    metrics().set_gossip_neighbours(container.region_id, 0);
+
+                    container
+                        .stream_closed("Network events stream closed")
+                        .await
                 }
+            });
+
+        // handle received messages
+        let container = self.clone();
+        self.supervisor.spawn(MESSAGES_TASK, move || {
+            let stream_rx = stream_rx.clone();
+            let container = container.clone();
+            let private_key = private_key.clone();
+
+            async move {
+                info!("Listening for messages");
+                let mut stream_rx = stream_rx.lock().await;
+
+                while let Some(event) = stream_rx.recv().await {
+                    let span = info_span!(
+                        "operation",
+                        author = event
+                            .header
+                            .as_ref()
+                            .map(|header| header.public_key.to_hex()),
+                        seq_num = event.header.as_ref().map(|header| header.seq_num),
+                    );
+
+                    receive_operation(
+                        event,
+                        &container.events_tx,
+                        &container.node_api,
+                        &container.topic,
+                        &private_key,
+                        &container.presence,
+                        container.started_at,
+                    )
+                    .instrument(span)
+                    .await;
+                }
+                info!("Message stream closed");
+
+                container
+                    .stream_closed("Message stream closed")
+                    .await
             }
-            println!("Network events stream closed");
         });
+    }
 
-        let events_tx = self.events_tx.clone();
+    /// Ends a listener whose stream closed: quietly while the node shuts down, otherwise with an
+    /// error after asking for a restart of the node, which replaces the listener.
+    async fn stream_closed(&self, reason: &str) -> Result<()> {
+        if self.state().await != ContainerState::Running {
+            return Ok(());
+        }
 
-        // handle received messages
-        tokio::spawn(async move {
-            println!("Listening for messages...");
-            while let Some(event) = stream_rx.recv().await {
-                println!("Received message: {:?}", event);
-                let data: EventData = event.data;
-
-                match data {
-                    EventData::Application(payload) => {
-                        let lores_event: Result<LoResEventPayload, _> = serde_json::from_slice(&payload);
-                        match lores_event {
-                            Ok(lores_event_payload) => {
-                                println!("  Parsed LoResEvent: {:?}", lores_event_payload);
-
-                                let header = event.header.unwrap();
-
-                                // emit to the event handler
-                                let lores_event_header = LoResEventHeader {
-                                    author_node_id: header.public_key.to_hex(),
-                                };
-                                let event = LoResEvent::new(lores_event_header, lores_event_payload);
-                                let send_result = events_tx.send(event).await;
-
-                                if let Err(err) = send_result {
-                                    println!("  Failed to send event: {:?}", err);
-                                }
-                            }
-                            Err(err) => println!("  Failed to parse LoResEvent: {:?}", err),
-                        }
-                    }
-                    EventData::Ephemeral(payload) => {
-                        let payload: serde_json::Value = serde_json::from_slice(&payload).unwrap();
-                        println!("  Ephemeral Payload: {:?}", payload);
-                    }
-                    EventData::Error(error) => {
-                        println!("  Stream Error: {:?}", error);
-                    }
+        warn!(reason, "Restarting node");
+        let container = self.clone();
+        // restarting stops this task, so it can't be awaited from here
+        self.supervisor.spawn(RESTART_TASK, move || {
+            let container = container.clone();
+
+            // not retried, the node may have been shut down on purpose meanwhile
+            async move {
+                if let Err(e) = container.restart().await {
+                    error!(error = %e, "Failed to restart node");
                 }
+                Ok(())
             }
-            println!("Message stream closed");
         });
+
+        Err(anyhow!("{}", reason))
     }
 }
 
+async fn handle_network_event(
+    event: SystemEvent<Topic>,
+    node_api: &Mutex<Option<NodeApi<NodeExtensions>>>,
+    topic: &MeshTopic,
+    sync_failures: &Mutex<VecDeque<SyncFailure>>,
+    region_id: i64,
+) {
+    match event {
+        SystemEvent::GossipJoined { topic_id, peers } => {
+            info!(
+                topic_id = hex::encode(topic_id),
+                peers = ?peers.iter().map(|peer| peer.to_hex()).collect::<Vec<_>>(),
+                "Gossip joined"
+            );
+            metrics().set_gossip_neighbours(region_id, peers.len());
+        }
+        SystemEvent::GossipLeft { topic_id } => {
+            info!(topic_id = hex::encode(topic_id), "Gossip left");
+            metrics().set_gossip_neighbours(region_id, 0);
+        }
+        SystemEvent::GossipNeighborUp { topic_id: _, peer } => {
+            debug!(peer = %peer.to_hex(), "Gossip neighbour up");
+            metrics().gossip_neighbour_up(region_id);
+        }
+        SystemEvent::GossipNeighborDown { topic_id: _, peer } => {
+            debug!(peer = %peer.to_hex(), "Gossip neighbour down");
+            metrics().gossip_neighbour_down(region_id);
+        }
+        SystemEvent::PeerDiscovered { peer } => {
+            info!(peer = %peer.to_hex(), "Peer discovered");
+            let mut node_api = node_api.lock().await;
+
+            // the node may be shutting down while the event was in flight
+            let Some(node_api) = node_api.as_mut() else {
+                debug!("Network not running, ignoring peer");
+                return;
+            };
+
+            if let Err(err) = node_api
+                .add_topic_log(&peer, &topic.name, &topic.log_id)
+                .await
+            {
+                warn!(peer = %peer.to_hex(), error = %err, "Failed to add topic log for peer");
+            }
+        }
+        SystemEvent::SyncStarted { topic, peer } => {
+            debug!(?topic, peer = %peer.to_hex(), "Sync started");
+            metrics().sync(&peer.to_hex(), "started");
+        }
+        SystemEvent::SyncDone { topic, peer } => {
+            debug!(?topic, peer = %peer.to_hex(), "Sync done");
+            metrics().sync(&peer.to_hex(), "done");
+        }
+        SystemEvent::SyncFailed { topic, peer } => {
+            warn!(?topic, peer = %peer.to_hex(), "Sync failed");
+            metrics().sync(&peer.to_hex(), "failed");
+
+            let mut sync_failures = sync_failures.lock().await;
+            if sync_failures.len() == SYNC_FAILURES_KEPT {
+                sync_failures.pop_front();
+            }
+            sync_failures.push_back(SyncFailure {
+                peer: peer.to_hex(),
+                failed_at: unix_now(),
+            });
+        }
+    }
+}
+
+/// Hands a persisted event on to the event handler, or acts on an ephemeral message.
+async fn receive_operation(
+    event: StreamEvent<NodeExtensions>,
+    events_tx: &mpsc::Sender<LoResEvent>,
+    node_api: &Mutex<Option<NodeApi<NodeExtensions>>>,
+    topic: &MeshTopic,
+    private_key: &PrivateKey,
+    presence: &PresenceTable,
+    started_at: Instant,
+) {
+    match event.data {
+        EventData::Application(payload) => {
+            let lores_event_payload: LoResEventPayload = match serde_json::from_slice(&payload) {
+                Ok(lores_event_payload) => lores_event_payload,
+                Err(err) => {
+                    warn!(error = %err, "Failed to parse LoResEvent");
+                    return;
+                }
+            };
+            debug!(event = lores_event_payload.name(), "Received event");
+
+            let Some(header) = event.header else {
+                warn!("Event has no header, ignoring it");
+                return;
+            };
+
+            // emit to the event handler
+            let lores_event_header = LoResEventHeader {
+                author_node_id: header.public_key.to_hex(),
+            };
+            let event = LoResEvent::new(lores_event_header, lores_event_payload);
+
+            if let Err(err) = events_tx.send(event).await {
+                error!(error = %err, "Failed to send event to the handler");
+            }
+        }
+        EventData::Ephemeral(payload) => {
+            let message: EphemeralMessage = match serde_json::from_slice(&payload) {
+                Ok(message) => message,
+                Err(err) => {
+                    warn!(error = %err, "Failed to parse ephemeral message");
+                    return;
+                }
+            };
+
+            if !message.verify(unix_now()) {
+                warn!(sender = %message.sender_node_id, "Ephemeral message is not signed by its sender or is stale, ignoring it");
+                return;
+            }
+
+            match message.payload {
+                EphemeralMessagePayload::Heartbeat(heartbeat) => {
+                    debug!(sender = %message.sender_node_id, "Received heartbeat");
+                    presence
+                        .record(message.sender_node_id, heartbeat)
+                        .await;
+                }
+                EphemeralMessagePayload::WhoIsOnline => {
+                    debug!(sender = %message.sender_node_id, "Asked who is online");
+                    if let Err(err) = publish_heartbeat(node_api, topic, private_key, presence, started_at).await {
+                        warn!(error = %err, "Failed to answer who is online");
+                    }
+                }
+            }
+        }
+        EventData::Error(error) => {
+            warn!(error = ?error, "Stream error");
+        }
+    }
+}
+
+async fn publish_heartbeat(
+    node_api: &Mutex<Option<NodeApi<NodeExtensions>>>,
+    topic: &MeshTopic,
+    private_key: &PrivateKey,
+    presence: &PresenceTable,
+    started_at: Instant,
+) -> Result<()> {
+    let heartbeat = Heartbeat::current(started_at.elapsed());
+
+    let sender_node_id = publish_ephemeral(node_api, topic, private_key, EphemeralMessagePayload::Heartbeat(heartbeat.clone())).await?;
+
+    // we never hear our own gossip, so record ourselves directly
+    presence.record(sender_node_id, heartbeat).await;
+
+    Ok(())
+}
+
+async fn publish_who_is_online(node_api: &Mutex<Option<NodeApi<NodeExtensions>>>, topic: &MeshTopic, private_key: &PrivateKey) -> Result<()> {
+    publish_ephemeral(node_api, topic, private_key, EphemeralMessagePayload::WhoIsOnline).await?;
+
+    Ok(())
+}
+
+/// Publishes an ephemeral message signed with `private_key` on the main topic and returns the id
+/// of the sending node.
+async fn publish_ephemeral(
+    node_api: &Mutex<Option<NodeApi<NodeExtensions>>>,
+    topic: &MeshTopic,
+    private_key: &PrivateKey,
+    payload: EphemeralMessagePayload,
+) -> Result<String> {
+    let mut node_api = node_api.lock().await;
+    let node_api = node_api
+        .as_mut()
+        .ok_or(ContainerError::NotRunning)?;
+
+    let message = EphemeralMessage::signed(private_key, payload, unix_now())?;
+    let sender_node_id = message.sender_node_id.clone();
+    let payload = serde_json::to_vec(&message)?;
+
+    node_api
+        .publish_ephemeral(&topic.name, &payload)
+        .await?;
+
+    Ok(sender_node_id)
+}
+
 // TODO: This should be in p2panda-core, submit a PR
 pub fn build_public_key_from_hex(key_hex: String) -> Option<PublicKey> {
     let key_bytes = hex::decode(key_hex).ok()?;
@@ -318,3 +1046,17 @@ pub fn build_public_key_from_hex(key_hex: String) -> Option<PublicKey> {
         Err(_) => None,
     }
 }
+
+// TODO: This should be in p2panda-core, submit a PR
+pub fn build_hash_from_hex(hash_hex: String) -> Option<Hash> {
+    let hash_bytes = hex::decode(hash_hex).ok()?;
+    let hash_byte_array: [u8; 32] = hash_bytes.try_into().ok()?;
+
+    Some(Hash::from_bytes(hash_byte_array))
+}
+
+pub fn build_private_key_from_hex(private_key_hex: String) -> Option<PrivateKey> {
+    let private_key_bytes = hex::decode(private_key_hex).ok()?;
+    let private_key_byte_array: [u8; PRIVATE_KEY_LEN] = private_key_bytes.try_into().ok()?;
+    Some(PrivateKey::from_bytes(&private_key_byte_array))
+}
@@ -2,12 +2,15 @@ use p2panda_core::PublicKey;
 use rocket::fairing::{Fairing, Info, Kind};
 use rocket::{Orbit, Rocket};
 use rocket_db_pools::Database;
+use tracing::{error, info, warn};
 
 use crate::infra::db::MainDb;
 use crate::panda_comms::container::P2PandaContainer;
-use crate::repos::this_p2panda_node::ThisP2PandaNodeRepo;
+use crate::repos::this_p2panda_node::{ThisP2PandaNodeRepo, ThisP2PandaNodeRepoError};
 
 use super::container::build_public_key_from_hex;
+use super::identity::{key_passphrase_from_env, PrivateKeyStatus};
+use super::regions::Regions;
 
 #[derive(Default)]
 pub struct P2PandaCommsFairing {}
@@ -23,48 +26,81 @@ impl Fairing for P2PandaCommsFairing {
 
     async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
         if let Some(db) = MainDb::fetch(&rocket) {
-            let repo = ThisP2PandaNodeRepo::init();
-
             if let Some(container) = rocket.state::<P2PandaContainer>() {
-                match repo.get_network_name(db).await {
-                    Ok(network_name) => {
-                        if let Some(network_name) = network_name {
-                            println!("Got network name: {:?}", network_name);
-                            container.set_network_name(network_name).await;
-                        }
-                    }
-                    Err(_) => {
-                        println!("Failed to get network name");
+                let passphrase = match key_passphrase_from_env() {
+                    Ok(passphrase) => passphrase,
+                    Err(e) => {
+                        error!(error = %e, "Failed to read key passphrase");
+                        None
                     }
-                }
-
-                match repo.get_or_create_private_key(db).await {
-                    Ok(private_key) => {
-                        println!("Got private key");
-                        container.set_private_key(private_key).await;
-                    }
-                    Err(_) => {
-                        println!("Failed to get private key");
-                    }
-                }
-
-                let bootstrap_details = repo.get_bootstrap_details(db).await.unwrap();
-                let bootstrap_node_id: Option<PublicKey> = match &bootstrap_details {
-                    Some(details) => build_public_key_from_hex(details.node_id.clone()),
-                    None => None,
                 };
-                container
-                    .set_bootstrap_node_id(bootstrap_node_id)
-                    .await;
+
+                load_node_params(db, container, passphrase).await;
 
                 if let Err(e) = container.start().await {
-                    println!("Failed to start P2PandaContainer on liftoff: {:?}", e);
+                    error!(error = %e, "Failed to start P2PandaContainer on liftoff");
+                }
+
+                match rocket.state::<Regions>() {
+                    Some(regions) => regions.load(db.sqlite_pool()).await,
+                    None => error!("Regions state not found, won't join other regions"),
                 }
             } else {
-                println!("P2PandaContainer state not found.");
+                error!("P2PandaContainer state not found");
             }
         } else {
-            println!("MainDb state not found, wont start Panda node");
+            error!("MainDb state not found, won't start Panda node");
         }
     }
 }
+
+/// Fills the container with the network config and private key stored in the DB, unlocking the
+/// key with `passphrase` if it is encrypted.
+pub async fn load_node_params(db: &MainDb, container: &P2PandaContainer, passphrase: Option<String>) {
+    let repo = ThisP2PandaNodeRepo::init();
+
+    match repo.get_network_name(db).await {
+        Ok(network_name) => {
+            if let Some(network_name) = network_name {
+                info!(network_name, "Loaded network name");
+                container.set_network_name(network_name).await;
+            }
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to get network name");
+        }
+    }
+
+    match repo
+        .load_private_key(db, passphrase.as_deref())
+        .await
+    {
+        Ok((private_key, key_status)) => {
+            info!(?key_status, "Loaded private key");
+            container
+                .set_loaded_private_key(private_key, key_status, passphrase)
+                .await;
+        }
+        Err(ThisP2PandaNodeRepoError::Invalid(e)) => {
+            warn!(error = %e, "Failed to unlock private key");
+            container
+                .set_loaded_private_key(None, PrivateKeyStatus::Locked, None)
+                .await;
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to get private key");
+        }
+    }
+
+    let bootstrap_node_id: Option<PublicKey> = match repo.get_bootstrap_details(db).await {
+        Ok(Some(details)) => build_public_key_from_hex(details.node_id.clone()),
+        Ok(None) => None,
+        Err(e) => {
+            error!(error = %e, "Failed to get bootstrap details");
+            None
+        }
+    };
+    container
+        .set_bootstrap_node_id(bootstrap_node_id)
+        .await;
+}
@@ -0,0 +1,130 @@
+use anyhow::{anyhow, Result};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use p2panda_core::PrivateKey;
+use rocket::serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::container::{build_private_key_from_hex, build_public_key_from_hex};
+use crate::infra::crypto::{decrypt_with_passphrase, encrypt_with_passphrase, EncryptedBlob};
+
+const IDENTITY_BACKUP_VERSION: u8 = 1;
+
+/// A node identity that can be moved to a new install. The private key is encrypted with a
+/// passphrase chosen by the operator, the node id is kept in the clear so the file can be told
+/// apart from others.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct IdentityBackup {
+    pub version: u8,
+    pub node_id: String,
+    pub private_key: EncryptedBlob,
+}
+
+impl IdentityBackup {
+    pub fn create(private_key: &PrivateKey, passphrase: &str) -> Result<Self> {
+        let encrypted = encrypt_with_passphrase(passphrase, private_key.to_hex().as_bytes())?;
+
+        Ok(IdentityBackup {
+            version: IDENTITY_BACKUP_VERSION,
+            node_id: private_key.public_key().to_hex(),
+            private_key: encrypted,
+        })
+    }
+
+    pub fn restore(&self, passphrase: &str) -> Result<PrivateKey> {
+        if self.version != IDENTITY_BACKUP_VERSION {
+            return Err(anyhow!("Unsupported identity backup version {}", self.version));
+        }
+
+        let private_key_hex = String::from_utf8(decrypt_with_passphrase(passphrase, &self.private_key)?)?;
+        let private_key = build_private_key_from_hex(private_key_hex).ok_or(anyhow!("Invalid private key"))?;
+
+        if private_key.public_key().to_hex() != self.node_id {
+            return Err(anyhow!("Private key doesn't match node id"));
+        }
+
+        Ok(private_key)
+    }
+}
+
+/// Signs the move from `old_node_id` to the new key with the new key, proving that whoever
+/// announces the rotation actually holds it.
+pub fn rotation_proof(old_node_id: &str, new_private_key: &PrivateKey) -> String {
+    let new_node_id = new_private_key.public_key().to_hex();
+    let signing_key = SigningKey::from_bytes(new_private_key.as_bytes());
+    let signature = signing_key.sign(rotation_message(old_node_id, &new_node_id).as_bytes());
+
+    hex::encode(signature.to_bytes())
+}
+
+pub fn verify_rotation(old_node_id: &str, new_node_id: &str, proof_hex: &str) -> bool {
+    let Some(new_public_key) = build_public_key_from_hex(new_node_id.to_string()) else {
+        return false;
+    };
+    let Ok(verifying_key) = VerifyingKey::from_bytes(new_public_key.as_bytes()) else {
+        return false;
+    };
+    let Some(signature) = hex::decode(proof_hex)
+        .ok()
+        .and_then(|bytes| Signature::from_slice(&bytes).ok())
+    else {
+        return false;
+    };
+
+    verifying_key
+        .verify(rotation_message(old_node_id, new_node_id).as_bytes(), &signature)
+        .is_ok()
+}
+
+fn rotation_message(old_node_id: &str, new_node_id: &str) -> String {
+    format!("lores-key-rotation:{}:{}", old_node_id, new_node_id)
+}
+
+/// Env var holding the passphrase the node private key is encrypted with at rest.
+pub const KEY_PASSPHRASE_ENV: &str = "LORES_KEY_PASSPHRASE";
+/// Env var pointing to a file holding that passphrase, e.g. a Docker secret.
+pub const KEY_FILE_ENV: &str = "LORES_KEY_FILE";
+
+/// Whether the private key can be used by the network.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, ToSchema)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum PrivateKeyStatus {
+    /// No key loaded yet.
+    #[default]
+    Missing,
+    /// Stored without encryption.
+    Plaintext,
+    /// Stored encrypted and unlocked with the right passphrase.
+    Unlocked,
+    /// Stored encrypted and no (or a wrong) passphrase was given, the network stays down.
+    Locked,
+}
+
+/// The passphrase for the key at rest, taken from `LORES_KEY_PASSPHRASE` or else read from the file
+/// `LORES_KEY_FILE` points to. `None` if neither is set.
+pub fn key_passphrase_from_env() -> Result<Option<String>> {
+    if let Ok(passphrase) = std::env::var(KEY_PASSPHRASE_ENV) {
+        return Ok(Some(passphrase));
+    }
+
+    match std::env::var(KEY_FILE_ENV) {
+        Ok(path) => {
+            let contents = std::fs::read_to_string(&path).map_err(|e| anyhow!("Failed to read key file {}: {}", path, e))?;
+            Ok(Some(
+                contents
+                    .trim_end_matches(['\r', '\n'])
+                    .to_string(),
+            ))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+impl PrivateKeyStatus {
+    pub fn for_passphrase(passphrase: Option<&str>) -> Self {
+        match passphrase {
+            Some(_) => PrivateKeyStatus::Unlocked,
+            None => PrivateKeyStatus::Plaintext,
+        }
+    }
+}
@@ -1,14 +1,189 @@
-#[derive(serde::Deserialize, serde::Serialize, Debug)]
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
 pub struct NodeAnnounced {
     pub name: String,
 }
 
+/// Published by a node on its way out of the region, so the others stop listing it.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+pub struct NodeLeft {
+    pub left_at: i64,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, utoipa::ToSchema, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NoticePriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+    Urgent,
+}
+
+impl NoticePriority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NoticePriority::Low => "low",
+            NoticePriority::Normal => "normal",
+            NoticePriority::High => "high",
+            NoticePriority::Urgent => "urgent",
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+pub struct NoticePosted {
+    pub id: String,
+    pub title: String,
+    pub body: String,
+    pub priority: NoticePriority,
+    pub posted_at: i64,
+    pub expires_at: Option<i64>,
+}
+
+/// A message for a single node. Every node in the region carries it, but only the recipient can
+/// open the sealed body.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+pub struct DirectMessageSent {
+    pub id: String,
+    pub recipient_node_id: String,
+    pub sealed_body_hex: String,
+    pub sent_at: i64,
+}
+
+/// Published with the old key when a node moves to a new one. `proof_hex` is a signature by the
+/// new key, see `identity::rotation_proof`.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+pub struct KeyRotated {
+    pub new_node_id: String,
+    pub proof_hex: String,
+}
+
+/// Asks `buddy_node_id` to fetch and keep a backup of the author, encrypted with a passphrase only
+/// the author's operators know. The backup itself is a blob.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+pub struct BackupStored {
+    pub buddy_node_id: String,
+    pub blob_hash: String,
+    pub created_at: i64,
+}
+
+/// Published by a replacement node, asking the buddies of a lost node for its latest backup.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+pub struct BackupRequested {
+    pub owner_node_id: String,
+}
+
+/// A buddy's answer to `BackupRequested`, the backup is available as a blob again.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+pub struct BackupOffered {
+    pub owner_node_id: String,
+    pub requested_by: String,
+    pub blob_hash: String,
+    pub created_at: i64,
+}
+
+/// A summary of how a node's site is doing, published every `HEALTH_REPORT_INTERVAL` so the region
+/// can tell which sites need attention. The host values are missing when the node can't read its
+/// host.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+pub struct NodeHealthReported {
+    pub reported_at: i64,
+    pub version: String,
+    pub uptime_secs: Option<i64>,
+    pub disk_free_bytes: Option<i64>,
+    pub disk_used_percent: Option<f64>,
+    pub load_1m: Option<f64>,
+    /// Kinds of the system alerts open on the node, e.g. `disk_full`.
+    #[serde(default)]
+    pub open_alerts: Vec<String>,
+}
+
+/// When an alerting rule fires, checked by every node against what it knows of the region.
+#[derive(serde::Deserialize, serde::Serialize, utoipa::ToSchema, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AlertCondition {
+    /// Neither a health report nor a heartbeat from a node for this long.
+    NodeNotSeen { hours: u32 },
+    /// A node reports its data volume fuller than this.
+    DiskAbove { percent: f64 },
+    /// A node reports open system alerts, e.g. a high temperature.
+    NodeUnhealthy,
+    /// Syncing with a node failed at least this many times in the last hour.
+    SyncFailing { failures: u32 },
+}
+
+/// Longest name of an alerting rule, in characters.
+pub const ALERT_RULE_NAME_MAX_CHARS: usize = 100;
+
+/// Checks an alerting rule, whether an operator gave it or another node sent it. Conditions of a
+/// kind this node doesn't know already fail to parse.
+pub fn validate_alert_rule(name: &str, condition: &AlertCondition) -> Result<(), &'static str> {
+    if name.trim().is_empty() {
+        return Err("Name can't be blank");
+    }
+    if name.chars().count() > ALERT_RULE_NAME_MAX_CHARS {
+        return Err("Name must be at most 100 characters");
+    }
+
+    match condition {
+        AlertCondition::NodeNotSeen { hours: 0 } => Err("Hours must be at least 1"),
+        AlertCondition::DiskAbove { percent } if !(0.0..=100.0).contains(percent) => Err("Percent must be between 0 and 100"),
+        AlertCondition::SyncFailing { failures: 0 } => Err("Failures must be at least 1"),
+        _ => Ok(()),
+    }
+}
+
+/// Adds or changes an alerting rule of the region. The newest `updated_at` wins.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+pub struct AlertRuleSaved {
+    pub id: String,
+    pub name: String,
+    pub condition: AlertCondition,
+    pub updated_at: i64,
+}
+
+/// Removes an alerting rule from the region for good.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+pub struct AlertRuleDeleted {
+    pub id: String,
+    pub deleted_at: i64,
+}
+
 #[derive(serde::Deserialize, serde::Serialize, Debug)]
 pub enum LoResEventPayload {
     NodeAnnounced(NodeAnnounced),
+    NodeLeft(NodeLeft),
+    NoticePosted(NoticePosted),
+    DirectMessageSent(DirectMessageSent),
+    KeyRotated(KeyRotated),
+    BackupStored(BackupStored),
+    BackupRequested(BackupRequested),
+    BackupOffered(BackupOffered),
+    NodeHealthReported(NodeHealthReported),
+    AlertRuleSaved(AlertRuleSaved),
+    AlertRuleDeleted(AlertRuleDeleted),
 }
 
-#[derive(Debug)]
+impl LoResEventPayload {
+    /// Name of the event type, as used in metrics.
+    pub fn name(&self) -> &'static str {
+        match self {
+            LoResEventPayload::NodeAnnounced(_) => "node_announced",
+            LoResEventPayload::NodeLeft(_) => "node_left",
+            LoResEventPayload::NoticePosted(_) => "notice_posted",
+            LoResEventPayload::DirectMessageSent(_) => "direct_message_sent",
+            LoResEventPayload::KeyRotated(_) => "key_rotated",
+            LoResEventPayload::BackupStored(_) => "backup_stored",
+            LoResEventPayload::BackupRequested(_) => "backup_requested",
+            LoResEventPayload::BackupOffered(_) => "backup_offered",
+            LoResEventPayload::NodeHealthReported(_) => "node_health_reported",
+            LoResEventPayload::AlertRuleSaved(_) => "alert_rule_saved",
+            LoResEventPayload::AlertRuleDeleted(_) => "alert_rule_deleted",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct LoResEventHeader {
     pub author_node_id: String,
 }
@@ -1,4 +1,8 @@
 pub mod container;
 pub mod fairing;
+pub mod identity;
 pub mod lores_events;
 pub mod messages;
+pub mod presence;
+pub mod regions;
+pub mod sealed_box;
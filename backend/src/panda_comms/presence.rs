@@ -0,0 +1,167 @@
+use anyhow::Result;
+use p2panda_core::identity::SIGNATURE_LEN;
+use p2panda_core::{PrivateKey, Signature};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::tokio::sync::Mutex;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use utoipa::ToSchema;
+
+use super::container::build_public_key_from_hex;
+use crate::infra::time::unix_now;
+
+/// How often each node publishes a heartbeat.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A node counts as online while its last heartbeat is younger than this.
+pub const PRESENCE_TIMEOUT: Duration = Duration::from_secs(3 * HEARTBEAT_INTERVAL.as_secs());
+
+/// A node not heard from for this long is forgotten.
+pub const PRESENCE_FORGOTTEN_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Messages sent over the gossip overlay only. They never end up in the persisted log, so unlike
+/// operations they aren't signed by p2panda: the sender signs them itself, together with when it
+/// sent them so an old message can't be passed off as a new one.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct EphemeralMessage {
+    pub sender_node_id: String,
+    pub sent_at: i64,
+    pub payload: EphemeralMessagePayload,
+    pub signature_hex: String,
+}
+
+impl EphemeralMessage {
+    pub fn signed(private_key: &PrivateKey, payload: EphemeralMessagePayload, sent_at: i64) -> Result<Self> {
+        let sender_node_id = private_key.public_key().to_hex();
+        let signature = private_key.sign(&signed_bytes(&sender_node_id, sent_at, &payload)?);
+
+        Ok(EphemeralMessage {
+            sender_node_id,
+            sent_at,
+            payload,
+            signature_hex: signature.to_hex(),
+        })
+    }
+
+    /// Whether the sender signed the message, within `PRESENCE_TIMEOUT` of `now`.
+    pub fn verify(&self, now: i64) -> bool {
+        if (now - self.sent_at).abs() > PRESENCE_TIMEOUT.as_secs() as i64 {
+            return false;
+        }
+
+        let Some(public_key) = build_public_key_from_hex(self.sender_node_id.clone()) else {
+            return false;
+        };
+        let Some(signature) = hex::decode(&self.signature_hex)
+            .ok()
+            .and_then(|bytes| <[u8; SIGNATURE_LEN]>::try_from(bytes).ok())
+            .map(|bytes| Signature::from_bytes(&bytes))
+        else {
+            return false;
+        };
+        let Ok(message) = signed_bytes(&self.sender_node_id, self.sent_at, &self.payload) else {
+            return false;
+        };
+
+        public_key.verify(&message, &signature)
+    }
+}
+
+fn signed_bytes(sender_node_id: &str, sent_at: i64, payload: &EphemeralMessagePayload) -> serde_json::Result<Vec<u8>> {
+    serde_json::to_vec(&("lores-ephemeral", sender_node_id, sent_at, payload))
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub enum EphemeralMessagePayload {
+    Heartbeat(Heartbeat),
+    /// Asks every node that hears it to send a heartbeat straight away.
+    WhoIsOnline,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct Heartbeat {
+    pub uptime_secs: u64,
+    pub version: String,
+    /// One minute load average, if the host exposes one.
+    pub load: Option<f32>,
+}
+
+impl Heartbeat {
+    pub fn current(uptime: Duration) -> Self {
+        Heartbeat {
+            uptime_secs: uptime.as_secs(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            load: read_load_average(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct NodePresence {
+    pub node_id: String,
+    pub online: bool,
+    pub last_seen_at: i64,
+    pub uptime_secs: u64,
+    pub version: String,
+    pub load: Option<f32>,
+}
+
+/// The latest heartbeat of every node we heard from, kept in memory only.
+#[derive(Clone, Default)]
+pub struct PresenceTable {
+    heartbeats: Arc<Mutex<HashMap<String, (i64, Heartbeat)>>>,
+}
+
+impl PresenceTable {
+    pub async fn record(&self, node_id: String, heartbeat: Heartbeat) {
+        self.record_at(node_id, heartbeat, unix_now())
+            .await
+    }
+
+    /// Records a heartbeat received at `seen_at`, and forgets the nodes not heard from for
+    /// `PRESENCE_FORGOTTEN_AFTER` by then.
+    pub async fn record_at(&self, node_id: String, heartbeat: Heartbeat, seen_at: i64) {
+        let mut heartbeats = self.heartbeats.lock().await;
+        heartbeats.retain(|_, (last_seen_at, _)| seen_at - *last_seen_at < PRESENCE_FORGOTTEN_AFTER.as_secs() as i64);
+        heartbeats.insert(node_id, (seen_at, heartbeat));
+    }
+
+    /// Forgets every node, e.g. after leaving the region.
+    pub async fn clear(&self) {
+        self.heartbeats.lock().await.clear();
+    }
+
+    pub async fn all(&self) -> Vec<NodePresence> {
+        self.all_at(unix_now()).await
+    }
+
+    /// Every node heard from, and whether it is online at `now`.
+    pub async fn all_at(&self, now: i64) -> Vec<NodePresence> {
+        let heartbeats = self.heartbeats.lock().await;
+
+        let mut presences: Vec<NodePresence> = heartbeats
+            .iter()
+            .map(|(node_id, (last_seen_at, heartbeat))| NodePresence {
+                node_id: node_id.clone(),
+                online: now - *last_seen_at < PRESENCE_TIMEOUT.as_secs() as i64,
+                last_seen_at: *last_seen_at,
+                uptime_secs: heartbeat.uptime_secs,
+                version: heartbeat.version.clone(),
+                load: heartbeat.load,
+            })
+            .collect();
+        presences.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+
+        presences
+    }
+}
+
+fn read_load_average() -> Option<f32> {
+    let loadavg = std::fs::read_to_string("/proc/loadavg").ok()?;
+    loadavg.split_whitespace().next()?.parse().ok()
+}
@@ -0,0 +1,509 @@
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use rocket::tokio::sync::{mpsc, Mutex};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::Sqlite;
+use thiserror::Error;
+use tracing::{error, info, warn};
+
+use super::container::{build_public_key_from_hex, ContainerError, ContainerState, MeshTopic, P2PandaContainer};
+use crate::events::fairing::{spawn_event_handler, EVENT_HANDLER_TASK};
+use crate::infra::backup::{create_backup, BackupContents};
+use crate::infra::db::{database_path, migrate, region_database_path};
+use crate::infra::metrics::metrics;
+use crate::infra::settings::Settings;
+use crate::infra::supervisor::Supervisor;
+use crate::repos::entities::NetworkConfig;
+use crate::repos::helpers::NETWORK_CONFIG_ID;
+use crate::repos::nodes::NodesRepo;
+use crate::repos::region_data::RegionDataRepo;
+use crate::repos::this_p2panda_node::{SimplifiedNodeAddress, ThisP2PandaNodeRepo};
+
+#[derive(Debug, Error)]
+pub enum RegionsError {
+    #[error("Internal server error: {0}")]
+    InternalServerError(String),
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+}
+
+/// A region this node is a member of: its network, the node running in it, and the database its
+/// events are projected into.
+#[derive(Clone)]
+pub struct RegionMembership {
+    pub config: NetworkConfig,
+    pub container: P2PandaContainer,
+    pub pool: sqlx::Pool<Sqlite>,
+}
+
+impl RegionMembership {
+    pub fn is_first(&self) -> bool {
+        self.config.id == NETWORK_CONFIG_ID as i64
+    }
+}
+
+/// The regions this node is a member of, by id. The first region runs on the container and
+/// database the rest of the node uses, alerting, health reports and backups included. Every other
+/// region runs a node of its own with the same identity, and projects its events into a database
+/// next to the main one.
+#[derive(Clone)]
+pub struct Regions {
+    memberships: Arc<Mutex<BTreeMap<i64, RegionMembership>>>,
+    first: P2PandaContainer,
+    database_url: String,
+    settings: Settings,
+    supervisor: Supervisor,
+}
+
+impl Regions {
+    pub fn new(first: P2PandaContainer, database_url: String, settings: Settings, supervisor: Supervisor) -> Self {
+        Regions {
+            memberships: Arc::new(Mutex::new(BTreeMap::new())),
+            first,
+            database_url,
+            settings,
+            supervisor,
+        }
+    }
+
+    /// Registers the regions stored in the main database, starting the nodes of all but the
+    /// first, which is started on its own. Regions already open are left as they are.
+    pub async fn load(&self, main_pool: &sqlx::Pool<Sqlite>) {
+        let configs = match ThisP2PandaNodeRepo::init()
+            .network_configs(main_pool)
+            .await
+        {
+            Ok(configs) => configs,
+            Err(e) => {
+                error!(error = %e, "Failed to load regions");
+                return;
+            }
+        };
+
+        for config in configs {
+            if config.id == NETWORK_CONFIG_ID as i64 {
+                self.register_first(main_pool, config).await;
+            } else if !self
+                .memberships
+                .lock()
+                .await
+                .contains_key(&config.id)
+            {
+                let region_id = config.id;
+                if let Err(e) = self.open(config).await {
+                    error!(region_id, error = %e, "Failed to open region");
+                }
+            }
+        }
+    }
+
+    pub async fn all(&self) -> Vec<RegionMembership> {
+        self.memberships
+            .lock()
+            .await
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    pub async fn get(&self, id: i64) -> Result<RegionMembership, RegionsError> {
+        self.memberships
+            .lock()
+            .await
+            .get(&id)
+            .cloned()
+            .ok_or(RegionsError::NotFound("This node is not a member of that region".to_string()))
+    }
+
+    /// Every region but the first.
+    pub async fn others(&self) -> Vec<RegionMembership> {
+        self.all()
+            .await
+            .into_iter()
+            .filter(|membership| !membership.is_first())
+            .collect()
+    }
+
+    /// Joins the first region, or moves the first region to another network, and (re)starts the
+    /// node with it.
+    pub async fn join_first(
+        &self,
+        main_pool: &sqlx::Pool<Sqlite>,
+        network_name: String,
+        peer_address: Option<SimplifiedNodeAddress>,
+    ) -> Result<RegionMembership, RegionsError> {
+        let repo = ThisP2PandaNodeRepo::init();
+        let mut connection = main_pool.acquire().await.map_err(internal)?;
+
+        repo.set_network_config(&mut connection, network_name.clone(), peer_address.clone())
+            .await
+            .map_err(internal)?;
+
+        self.first
+            .set_network_name(network_name.clone())
+            .await;
+        self.first
+            .set_bootstrap_node_id(
+                peer_address
+                    .as_ref()
+                    .and_then(|peer| build_public_key_from_hex(peer.node_id.clone())),
+            )
+            .await;
+
+        // (re)start the container with the new config
+        if let Err(e) = self.first.restart().await {
+            error!(error = %e, "Failed to start P2PandaContainer");
+        }
+
+        let config = NetworkConfig {
+            id: NETWORK_CONFIG_ID as i64,
+            network_name,
+            bootstrap_node_id: peer_address.map(|peer| peer.node_id),
+            topic_name: None,
+            log_id: None,
+        };
+
+        Ok(self.register_first(main_pool, config).await)
+    }
+
+    /// Joins a region besides the first, with the same identity, and announces this node there
+    /// under the name it has in the first region.
+    pub async fn join(
+        &self,
+        main_pool: &sqlx::Pool<Sqlite>,
+        network_name: String,
+        peer_address: Option<SimplifiedNodeAddress>,
+        topic_name: Option<String>,
+        log_id: Option<String>,
+    ) -> Result<RegionMembership, RegionsError> {
+        let is_member = self
+            .all()
+            .await
+            .iter()
+            .any(|membership| membership.config.network_name == network_name);
+        if is_member {
+            return Err(RegionsError::Conflict(format!("This node is already a member of {}", network_name)));
+        }
+
+        let config = ThisP2PandaNodeRepo::init()
+            .add_network_config(main_pool, &network_name, peer_address, topic_name, log_id)
+            .await
+            .map_err(internal)?;
+        let membership = self.open(config).await?;
+
+        if let Some(name) = self.own_name(main_pool).await {
+            if let Err(e) = membership.container.announce_node(name).await {
+                warn!(region_id = membership.config.id, error = %e, "Failed to announce node in region");
+            }
+        }
+
+        Ok(membership)
+    }
+
+    /// Hands the key of the first region's node to the nodes of the other regions and (re)starts
+    /// them, after the key was unlocked or replaced.
+    pub async fn restart_others(&self) {
+        for membership in self.others().await {
+            self.use_first_identity(&membership.container)
+                .await;
+
+            let result = match membership.container.state().await {
+                ContainerState::Running => membership.container.restart().await,
+                _ => membership.container.start().await,
+            };
+            if let Err(e) = result {
+                warn!(region_id = membership.config.id, error = %e, "Failed to restart node of region");
+            }
+        }
+    }
+
+    /// Brings the regions in line with the main database after a restore replaced it: closes the
+    /// regions it doesn't have anymore, hands the restored key to the others and opens the ones
+    /// only it has. The first region's node is (re)started by the restore.
+    pub async fn reload(&self, main_pool: &sqlx::Pool<Sqlite>) {
+        let configs = match ThisP2PandaNodeRepo::init()
+            .network_configs(main_pool)
+            .await
+        {
+            Ok(configs) => configs,
+            Err(e) => {
+                error!(error = %e, "Failed to reload regions");
+                return;
+            }
+        };
+
+        for membership in self.all().await {
+            if configs.contains(&membership.config) {
+                continue;
+            }
+
+            if membership.is_first() {
+                self.memberships
+                    .lock()
+                    .await
+                    .remove(&membership.config.id);
+            } else {
+                if let Err(e) = membership.container.shutdown().await {
+                    warn!(region_id = membership.config.id, error = %e, "Failed to stop node of region");
+                }
+                self.close_other(membership).await;
+            }
+        }
+
+        self.restart_others().await;
+        self.load(main_pool).await;
+    }
+
+    /// Leaves a region, after saving a backup of the node next to its database if `keep_backup`
+    /// is set. Leaving the first region leaves every region and forgets all they synced, so the
+    /// node is back to how it was before it joined one. Its key stays. The regions that can't be
+    /// left cleanly are closed anyway, and reported in the error once the first one is reset.
+    pub async fn leave(&self, main_pool: &sqlx::Pool<Sqlite>, id: i64, keep_backup: bool) -> Result<Option<PathBuf>, RegionsError> {
+        let membership = self.get(id).await?;
+
+        let backup_path = match keep_backup {
+            true => Some(self.save_backup(main_pool).await?),
+            false => None,
+        };
+
+        if !membership.is_first() {
+            self.leave_other(main_pool, membership).await?;
+            return Ok(backup_path);
+        }
+
+        let mut failures = Vec::new();
+        for other in self.others().await {
+            let region_id = other.config.id;
+
+            if let Err(e) = self.leave_other(main_pool, other.clone()).await {
+                warn!(region_id, error = %e, "Failed to leave region, closing it");
+                failures.push(format!("region {}: {}", region_id, e));
+
+                if let Err(e) = other.container.shutdown().await {
+                    warn!(region_id, error = %e, "Failed to stop node of region");
+                }
+                self.close_other(other).await;
+            }
+        }
+        self.first
+            .leave()
+            .await
+            .map_err(container_error)?;
+
+        let mut transaction = main_pool.begin().await.map_err(internal)?;
+        ThisP2PandaNodeRepo::init()
+            .clear_network_configs(&mut transaction)
+            .await
+            .map_err(internal)?;
+        RegionDataRepo::init()
+            .clear(&mut transaction)
+            .await
+            .map_err(internal)?;
+        transaction.commit().await.map_err(internal)?;
+
+        self.memberships.lock().await.clear();
+        info!("Left every region");
+
+        if !failures.is_empty() {
+            return Err(RegionsError::InternalServerError(format!(
+                "Left every region, but not cleanly: {}",
+                failures.join("; ")
+            )));
+        }
+
+        Ok(backup_path)
+    }
+
+    async fn leave_other(&self, main_pool: &sqlx::Pool<Sqlite>, membership: RegionMembership) -> Result<(), RegionsError> {
+        let id = membership.config.id;
+
+        membership
+            .container
+            .leave()
+            .await
+            .map_err(container_error)?;
+        ThisP2PandaNodeRepo::init()
+            .remove_network_config(main_pool, id)
+            .await
+            .map_err(internal)?;
+        let network_name = membership.config.network_name.clone();
+        self.close_other(membership).await;
+
+        info!(region_id = id, network_name, "Left region");
+
+        Ok(())
+    }
+
+    /// Forgets a region other than the first whose node is stopped, and removes its database.
+    async fn close_other(&self, membership: RegionMembership) {
+        let id = membership.config.id;
+
+        self.supervisor
+            .scoped(&region_scope(id))
+            .stop(EVENT_HANDLER_TASK);
+        membership.pool.close().await;
+        self.memberships.lock().await.remove(&id);
+        metrics().forget_region(id);
+
+        // the database goes with the region, along with the files SQLite keeps next to it
+        let path = region_database_path(&self.database_url, id);
+        for suffix in ["", "-wal", "-shm"] {
+            let file = PathBuf::from(format!("{}{}", path.display(), suffix));
+            if let Err(e) = rocket::tokio::fs::remove_file(&file).await {
+                if e.kind() != ErrorKind::NotFound {
+                    warn!(region_id = id, file = %file.display(), error = %e, "Failed to remove region database");
+                }
+            }
+        }
+    }
+
+    /// Writes a backup of the node, as `/admin/backup` makes it, next to the main database.
+    async fn save_backup(&self, main_pool: &sqlx::Pool<Sqlite>) -> Result<PathBuf, RegionsError> {
+        let (manifest, data) = create_backup(main_pool, &self.first, BackupContents::Full)
+            .await
+            .map_err(internal)?;
+
+        let database_path = database_path(&self.database_url);
+        let path = database_path
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(manifest.file_name());
+        rocket::tokio::fs::write(&path, data)
+            .await
+            .map_err(internal)?;
+
+        info!(path = %path.display(), "Saved backup before leaving");
+
+        Ok(path)
+    }
+
+    async fn register_first(&self, main_pool: &sqlx::Pool<Sqlite>, config: NetworkConfig) -> RegionMembership {
+        let membership = RegionMembership {
+            config,
+            container: self.first.clone(),
+            pool: main_pool.clone(),
+        };
+
+        self.memberships
+            .lock()
+            .await
+            .insert(membership.config.id, membership.clone());
+
+        membership
+    }
+
+    /// Sets up and starts the node and database of a region other than the first.
+    async fn open(&self, config: NetworkConfig) -> Result<RegionMembership, RegionsError> {
+        let path = region_database_path(&self.database_url, config.id);
+        let options = SqliteConnectOptions::new()
+            .filename(&path)
+            .create_if_missing(true);
+        let pool = SqlitePoolOptions::new()
+            .connect_with(options)
+            .await
+            .map_err(internal)?;
+        migrate(&pool).await.map_err(internal)?;
+
+        let topic = MeshTopic {
+            name: config
+                .topic_name
+                .clone()
+                .unwrap_or(self.settings.topic_name.clone()),
+            log_id: config
+                .log_id
+                .clone()
+                .unwrap_or(self.settings.log_id.clone()),
+        };
+        let supervisor = self.supervisor.scoped(&region_scope(config.id));
+        let (events_tx, events_rx) = mpsc::channel(self.settings.event_channel_size);
+
+        // the ports in the settings are taken by the first region
+        let container = P2PandaContainer::new(
+            events_tx,
+            self.settings.relay_url(),
+            self.settings.network_options().on_free_ports(),
+            topic,
+            supervisor.clone(),
+            config.id,
+        );
+        container
+            .set_network_name(config.network_name.clone())
+            .await;
+        container
+            .set_bootstrap_node_id(
+                config
+                    .bootstrap_node_id
+                    .clone()
+                    .and_then(build_public_key_from_hex),
+            )
+            .await;
+        self.use_first_identity(&container).await;
+
+        spawn_event_handler(&supervisor, Arc::new(Mutex::new(events_rx)), pool.clone(), container.clone());
+
+        let membership = RegionMembership { config, container, pool };
+        self.memberships
+            .lock()
+            .await
+            .insert(membership.config.id, membership.clone());
+
+        info!(
+            region_id = membership.config.id,
+            network_name = membership.config.network_name,
+            database = %path.display(),
+            "Opened region"
+        );
+        if let Err(e) = membership.container.start().await {
+            warn!(region_id = membership.config.id, error = %e, "Failed to start node of region");
+        }
+
+        Ok(membership)
+    }
+
+    async fn use_first_identity(&self, container: &P2PandaContainer) {
+        let params = self.first.get_params().await;
+
+        container
+            .set_loaded_private_key(params.private_key, params.key_status, params.key_passphrase)
+            .await;
+    }
+
+    /// The name this node announced in its first region, if any.
+    async fn own_name(&self, main_pool: &sqlx::Pool<Sqlite>) -> Option<String> {
+        let node_id = self.first.get_public_key().await.ok()?;
+        let mut connection = main_pool.acquire().await.ok()?;
+        let nodes = NodesRepo::init()
+            .all(&mut connection)
+            .await
+            .ok()?;
+
+        nodes
+            .into_iter()
+            .find(|node| node.id == node_id)
+            .map(|node| node.name)
+    }
+}
+
+/// What the tasks of a region other than the first are supervised under.
+fn region_scope(id: i64) -> String {
+    format!("region_{}", id)
+}
+
+/// A node busy starting or stopping can be left once it is done, anything else is internal.
+fn container_error(error: anyhow::Error) -> RegionsError {
+    match error.downcast_ref::<ContainerError>() {
+        Some(ContainerError::Busy(_)) => RegionsError::Conflict(error.to_string()),
+        _ => internal(error),
+    }
+}
+
+fn internal(error: impl std::fmt::Display) -> RegionsError {
+    RegionsError::InternalServerError(error.to_string())
+}
@@ -0,0 +1,27 @@
+use anyhow::{anyhow, Result};
+use crypto_box::aead::OsRng;
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use p2panda_core::{PrivateKey, PublicKey};
+
+/// Encrypts `plaintext` so that only the holder of the private key behind `recipient` can read
+/// it. The node's ed25519 identity is converted to its x25519 form, so no extra keys need to be
+/// exchanged. Sealed boxes are anonymous: who sent the message is known from the signed
+/// operation that carries it.
+pub fn seal(recipient: &PublicKey, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let verifying_key = VerifyingKey::from_bytes(recipient.as_bytes()).map_err(|e| anyhow!("Invalid recipient key: {}", e))?;
+    let box_public_key = crypto_box::PublicKey::from(verifying_key.to_montgomery().to_bytes());
+
+    box_public_key
+        .seal(&mut OsRng, plaintext)
+        .map_err(|_| anyhow!("Failed to seal message"))
+}
+
+/// Decrypts a message sealed with [`seal`] for the public key of `private_key`.
+pub fn open(private_key: &PrivateKey, sealed: &[u8]) -> Result<Vec<u8>> {
+    let signing_key = SigningKey::from_bytes(private_key.as_bytes());
+    let box_secret_key = crypto_box::SecretKey::from(signing_key.to_scalar_bytes());
+
+    box_secret_key
+        .unseal(sealed)
+        .map_err(|_| anyhow!("Failed to open sealed message"))
+}
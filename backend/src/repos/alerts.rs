@@ -0,0 +1,246 @@
+use sqlx::Sqlite;
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::entities::{Alert, AlertRule, AlertState};
+use crate::infra::metrics::metrics;
+use crate::infra::time::unix_now;
+
+pub struct AlertsRepo {}
+
+#[derive(Debug, Error)]
+pub enum AlertsError {
+    #[error("Internal server error: {0}")]
+    InternalServerError(String),
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+}
+
+struct AlertRuleRow {
+    id: String,
+    name: String,
+    condition: String,
+    author_node_id: String,
+    updated_at: i64,
+}
+
+impl AlertRuleRow {
+    /// Rules with a condition this version doesn't know, e.g. from a newer node, are left out.
+    fn into_rule(self) -> Option<AlertRule> {
+        Some(AlertRule {
+            condition: serde_json::from_str(&self.condition).ok()?,
+            id: self.id,
+            name: self.name,
+            author_node_id: self.author_node_id,
+            updated_at: self.updated_at,
+        })
+    }
+}
+
+impl AlertsRepo {
+    pub fn init() -> Self {
+        AlertsRepo {}
+    }
+
+    /// Keeps a rule, unless a newer version of it is stored already or it was deleted.
+    pub async fn save_rule(&self, pool: &sqlx::Pool<Sqlite>, rule: AlertRule) -> Result<(), AlertsError> {
+        let _timer = metrics().time_query("alerts.save_rule");
+        let condition = serde_json::to_string(&rule.condition).map_err(|e| AlertsError::InternalServerError(e.to_string()))?;
+
+        sqlx::query!(
+            "
+            INSERT INTO alert_rules (id, name, condition, author_node_id, updated_at)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET
+                name = excluded.name,
+                condition = excluded.condition,
+                author_node_id = excluded.author_node_id,
+                updated_at = excluded.updated_at
+            WHERE alert_rules.deleted_at IS NULL AND excluded.updated_at > alert_rules.updated_at
+            ",
+            rule.id,
+            rule.name,
+            condition,
+            rule.author_node_id,
+            rule.updated_at
+        )
+        .execute(pool)
+        .await
+        .map_err(|_| AlertsError::InternalServerError("Database error".to_string()))?;
+
+        Ok(())
+    }
+
+    /// Marks a rule deleted, also when it hasn't arrived yet.
+    pub async fn delete_rule(&self, pool: &sqlx::Pool<Sqlite>, id: &str, author_node_id: &str, deleted_at: i64) -> Result<(), AlertsError> {
+        let _timer = metrics().time_query("alerts.delete_rule");
+        sqlx::query!(
+            "
+            INSERT INTO alert_rules (id, name, condition, author_node_id, updated_at, deleted_at)
+            VALUES (?, '', 'null', ?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET deleted_at = excluded.deleted_at
+            WHERE alert_rules.deleted_at IS NULL
+            ",
+            id,
+            author_node_id,
+            deleted_at,
+            deleted_at
+        )
+        .execute(pool)
+        .await
+        .map_err(|_| AlertsError::InternalServerError("Database error".to_string()))?;
+
+        Ok(())
+    }
+
+    pub async fn rules(&self, pool: &sqlx::Pool<Sqlite>) -> Result<Vec<AlertRule>, AlertsError> {
+        let _timer = metrics().time_query("alerts.rules");
+        let rows = sqlx::query_as!(
+            AlertRuleRow,
+            "
+            SELECT id, name, condition, author_node_id, updated_at
+            FROM alert_rules
+            WHERE deleted_at IS NULL
+            ORDER BY name
+            "
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|_| AlertsError::InternalServerError("Database error".to_string()))?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(AlertRuleRow::into_rule)
+            .collect())
+    }
+
+    pub async fn rule(&self, pool: &sqlx::Pool<Sqlite>, id: &str) -> Result<AlertRule, AlertsError> {
+        let _timer = metrics().time_query("alerts.rule");
+        sqlx::query_as!(
+            AlertRuleRow,
+            "
+            SELECT id, name, condition, author_node_id, updated_at
+            FROM alert_rules
+            WHERE id = ? AND deleted_at IS NULL
+            ",
+            id
+        )
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| AlertsError::InternalServerError("Database error".to_string()))?
+        .and_then(AlertRuleRow::into_rule)
+        .ok_or(AlertsError::NotFound("Alert rule not found".to_string()))
+    }
+
+    /// Alerts in `state`, or all of them, newest first.
+    pub async fn alerts(&self, pool: &sqlx::Pool<Sqlite>, state: Option<AlertState>) -> Result<Vec<Alert>, AlertsError> {
+        let _timer = metrics().time_query("alerts.alerts");
+        let state = state.map(|state| state.as_str());
+
+        sqlx::query_as!(
+            Alert,
+            "
+            SELECT id, rule_id, node_id, state, message, opened_at, acknowledged_at, resolved_at
+            FROM alerts
+            WHERE ? IS NULL OR state = ?
+            ORDER BY opened_at DESC
+            ",
+            state,
+            state
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|_| AlertsError::InternalServerError("Database error".to_string()))
+    }
+
+    /// Alerts not resolved yet, acknowledged or not.
+    pub async fn active_alerts(&self, pool: &sqlx::Pool<Sqlite>) -> Result<Vec<Alert>, AlertsError> {
+        let _timer = metrics().time_query("alerts.active_alerts");
+        sqlx::query_as!(
+            Alert,
+            "
+            SELECT id, rule_id, node_id, state, message, opened_at, acknowledged_at, resolved_at
+            FROM alerts
+            WHERE state != 'resolved'
+            "
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|_| AlertsError::InternalServerError("Database error".to_string()))
+    }
+
+    pub async fn open_alert(&self, pool: &sqlx::Pool<Sqlite>, rule_id: &str, node_id: &str, message: &str) -> Result<Alert, AlertsError> {
+        let _timer = metrics().time_query("alerts.open_alert");
+        let alert = Alert {
+            id: Uuid::new_v4().to_string(),
+            rule_id: rule_id.to_string(),
+            node_id: node_id.to_string(),
+            state: AlertState::Open.as_str().to_string(),
+            message: message.to_string(),
+            opened_at: unix_now(),
+            acknowledged_at: None,
+            resolved_at: None,
+        };
+
+        sqlx::query!(
+            "
+            INSERT INTO alerts (id, rule_id, node_id, state, message, opened_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ",
+            alert.id,
+            alert.rule_id,
+            alert.node_id,
+            alert.state,
+            alert.message,
+            alert.opened_at
+        )
+        .execute(pool)
+        .await
+        .map_err(|_| AlertsError::InternalServerError("Database error".to_string()))?;
+
+        Ok(alert)
+    }
+
+    pub async fn acknowledge(&self, pool: &sqlx::Pool<Sqlite>, id: &str) -> Result<Alert, AlertsError> {
+        let _timer = metrics().time_query("alerts.acknowledge");
+        let now = unix_now();
+
+        sqlx::query_as!(
+            Alert,
+            "
+            UPDATE alerts
+            SET state = 'acknowledged', acknowledged_at = ?
+            WHERE id = ? AND state = 'open'
+            RETURNING id, rule_id, node_id, state, message, opened_at, acknowledged_at, resolved_at
+            ",
+            now,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| AlertsError::InternalServerError("Database error".to_string()))?
+        .ok_or(AlertsError::NotFound("No open alert with this id".to_string()))
+    }
+
+    pub async fn resolve(&self, pool: &sqlx::Pool<Sqlite>, id: &str) -> Result<Alert, AlertsError> {
+        let _timer = metrics().time_query("alerts.resolve");
+        let now = unix_now();
+
+        sqlx::query_as!(
+            Alert,
+            "
+            UPDATE alerts
+            SET state = 'resolved', resolved_at = ?
+            WHERE id = ? AND state != 'resolved'
+            RETURNING id, rule_id, node_id, state, message, opened_at, acknowledged_at, resolved_at
+            ",
+            now,
+            id
+        )
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| AlertsError::InternalServerError("Database error".to_string()))?
+        .ok_or(AlertsError::NotFound("No unresolved alert with this id".to_string()))
+    }
+}
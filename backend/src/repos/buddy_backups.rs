@@ -0,0 +1,278 @@
+use sqlx::Sqlite;
+use thiserror::Error;
+
+use super::entities::{BackupBuddy, BackupOffer, HeldBackup};
+use crate::infra::metrics::metrics;
+use crate::infra::time::unix_now;
+
+/// How many backups of each node we keep for it, older ones are dropped.
+pub const HELD_BACKUPS_PER_NODE: i64 = 3;
+
+/// Largest backup a buddy can have us keep, as it is pushed: encrypted and hex encoded.
+pub const MAX_HELD_BACKUP_BYTES: u64 = 256 * 1024 * 1024;
+
+/// How much room the backups we keep for all buddies may take together.
+pub const HELD_BACKUPS_QUOTA_BYTES: i64 = 1024 * 1024 * 1024;
+
+pub struct BuddyBackupsRepo {}
+
+#[derive(Debug, Error)]
+pub enum BuddyBackupsError {
+    #[error("Internal server error: {0}")]
+    InternalServerError(String),
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+}
+
+impl BuddyBackupsRepo {
+    pub fn init() -> Self {
+        BuddyBackupsRepo {}
+    }
+
+    pub async fn buddies(&self, pool: &sqlx::Pool<Sqlite>) -> Result<Vec<BackupBuddy>, BuddyBackupsError> {
+        let _timer = metrics().time_query("buddy_backups.buddies");
+        sqlx::query_as!(
+            BackupBuddy,
+            "
+            SELECT node_id, added_at
+            FROM backup_buddies
+            ORDER BY added_at
+            "
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|_| BuddyBackupsError::InternalServerError("Database error".to_string()))
+    }
+
+    pub async fn add_buddy(&self, pool: &sqlx::Pool<Sqlite>, node_id: &str) -> Result<(), BuddyBackupsError> {
+        let _timer = metrics().time_query("buddy_backups.add_buddy");
+        let now = unix_now();
+
+        sqlx::query!(
+            "
+            INSERT INTO backup_buddies (node_id, added_at)
+            VALUES (?, ?)
+            ON CONFLICT(node_id) DO NOTHING
+            ",
+            node_id,
+            now
+        )
+        .execute(pool)
+        .await
+        .map_err(|_| BuddyBackupsError::InternalServerError("Database error".to_string()))?;
+
+        Ok(())
+    }
+
+    pub async fn remove_buddy(&self, pool: &sqlx::Pool<Sqlite>, node_id: &str) -> Result<(), BuddyBackupsError> {
+        let _timer = metrics().time_query("buddy_backups.remove_buddy");
+        let result = sqlx::query!(
+            "
+            DELETE FROM backup_buddies
+            WHERE node_id = ?
+            ",
+            node_id
+        )
+        .execute(pool)
+        .await
+        .map_err(|_| BuddyBackupsError::InternalServerError("Database error".to_string()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(BuddyBackupsError::NotFound("Backup buddy not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    pub async fn is_buddy(&self, pool: &sqlx::Pool<Sqlite>, node_id: &str) -> Result<bool, BuddyBackupsError> {
+        let _timer = metrics().time_query("buddy_backups.is_buddy");
+        sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM backup_buddies WHERE node_id = ?) AS "is_buddy!: bool""#,
+            node_id
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|_| BuddyBackupsError::InternalServerError("Database error".to_string()))
+    }
+
+    /// Remembers that this node pushed the backup `blob_hash` to its buddies.
+    pub async fn record_push(&self, pool: &sqlx::Pool<Sqlite>, blob_hash: &str) -> Result<(), BuddyBackupsError> {
+        let _timer = metrics().time_query("buddy_backups.record_push");
+        let now = unix_now();
+
+        sqlx::query!(
+            "
+            INSERT INTO backup_pushes (blob_hash, pushed_at)
+            VALUES (?, ?)
+            ON CONFLICT(blob_hash) DO UPDATE SET pushed_at = excluded.pushed_at
+            ",
+            blob_hash,
+            now
+        )
+        .execute(pool)
+        .await
+        .map_err(|_| BuddyBackupsError::InternalServerError("Database error".to_string()))?;
+
+        Ok(())
+    }
+
+    /// When this node last pushed a backup to its buddies, if ever.
+    pub async fn last_pushed_at(&self, pool: &sqlx::Pool<Sqlite>) -> Result<Option<i64>, BuddyBackupsError> {
+        let _timer = metrics().time_query("buddy_backups.last_pushed_at");
+        sqlx::query_scalar!(r#"SELECT MAX(pushed_at) AS "pushed_at: i64" FROM backup_pushes"#)
+            .fetch_one(pool)
+            .await
+            .map_err(|_| BuddyBackupsError::InternalServerError("Database error".to_string()))
+    }
+
+    /// Keeps a backup pushed by `held_backup.owner_node_id`, dropping its oldest ones beyond
+    /// `HELD_BACKUPS_PER_NODE`. Returns whether it was kept: it isn't when all held backups would
+    /// then take more than `quota_bytes`.
+    pub async fn hold_backup(
+        &self,
+        pool: &sqlx::Pool<Sqlite>,
+        held_backup: HeldBackup,
+        archive: Vec<u8>,
+        quota_bytes: i64,
+    ) -> Result<bool, BuddyBackupsError> {
+        let _timer = metrics().time_query("buddy_backups.hold_backup");
+        let mut transaction = pool
+            .begin()
+            .await
+            .map_err(|_| BuddyBackupsError::InternalServerError("Database error".to_string()))?;
+
+        sqlx::query!(
+            "
+            INSERT INTO held_backups (blob_hash, owner_node_id, created_at, archive)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(blob_hash) DO NOTHING
+            ",
+            held_backup.blob_hash,
+            held_backup.owner_node_id,
+            held_backup.created_at,
+            archive
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| BuddyBackupsError::InternalServerError("Database error".to_string()))?;
+
+        sqlx::query!(
+            "
+            DELETE FROM held_backups
+            WHERE owner_node_id = ? AND blob_hash NOT IN (
+                SELECT blob_hash
+                FROM held_backups
+                WHERE owner_node_id = ?
+                ORDER BY created_at DESC
+                LIMIT ?
+            )
+            ",
+            held_backup.owner_node_id,
+            held_backup.owner_node_id,
+            HELD_BACKUPS_PER_NODE
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| BuddyBackupsError::InternalServerError("Database error".to_string()))?;
+
+        let held_bytes = sqlx::query_scalar!(r#"SELECT COALESCE(SUM(LENGTH(archive)), 0) AS "held_bytes!: i64" FROM held_backups"#)
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err(|_| BuddyBackupsError::InternalServerError("Database error".to_string()))?;
+        if held_bytes > quota_bytes {
+            // rolled back when dropped, the backups it would have replaced included
+            return Ok(false);
+        }
+
+        transaction
+            .commit()
+            .await
+            .map_err(|_| BuddyBackupsError::InternalServerError("Database error".to_string()))?;
+
+        Ok(true)
+    }
+
+    pub async fn held_backups(&self, pool: &sqlx::Pool<Sqlite>) -> Result<Vec<HeldBackup>, BuddyBackupsError> {
+        let _timer = metrics().time_query("buddy_backups.held_backups");
+        sqlx::query_as!(
+            HeldBackup,
+            "
+            SELECT blob_hash, owner_node_id, created_at
+            FROM held_backups
+            ORDER BY owner_node_id, created_at DESC
+            "
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|_| BuddyBackupsError::InternalServerError("Database error".to_string()))
+    }
+
+    /// The newest backup we hold for `owner_node_id`, with its archive.
+    pub async fn latest_held_backup(
+        &self,
+        pool: &sqlx::Pool<Sqlite>,
+        owner_node_id: &str,
+    ) -> Result<Option<(HeldBackup, Vec<u8>)>, BuddyBackupsError> {
+        let _timer = metrics().time_query("buddy_backups.latest_held_backup");
+        let row = sqlx::query!(
+            "
+            SELECT blob_hash, owner_node_id, created_at, archive
+            FROM held_backups
+            WHERE owner_node_id = ?
+            ORDER BY created_at DESC
+            LIMIT 1
+            ",
+            owner_node_id
+        )
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| BuddyBackupsError::InternalServerError("Database error".to_string()))?;
+
+        Ok(row.map(|row| {
+            let held_backup = HeldBackup {
+                blob_hash: row.blob_hash,
+                owner_node_id: row.owner_node_id,
+                created_at: row.created_at,
+            };
+            (held_backup, row.archive)
+        }))
+    }
+
+    pub async fn insert_offer(&self, pool: &sqlx::Pool<Sqlite>, offer: BackupOffer) -> Result<(), BuddyBackupsError> {
+        let _timer = metrics().time_query("buddy_backups.insert_offer");
+        sqlx::query!(
+            "
+            INSERT INTO backup_offers (blob_hash, owner_node_id, buddy_node_id, created_at, offered_at)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(blob_hash) DO NOTHING
+            ",
+            offer.blob_hash,
+            offer.owner_node_id,
+            offer.buddy_node_id,
+            offer.created_at,
+            offer.offered_at
+        )
+        .execute(pool)
+        .await
+        .map_err(|_| BuddyBackupsError::InternalServerError("Database error".to_string()))?;
+
+        Ok(())
+    }
+
+    /// Offers received for a restore, newest backup first.
+    pub async fn offers(&self, pool: &sqlx::Pool<Sqlite>) -> Result<Vec<BackupOffer>, BuddyBackupsError> {
+        let _timer = metrics().time_query("buddy_backups.offers");
+        sqlx::query_as!(
+            BackupOffer,
+            "
+            SELECT blob_hash, owner_node_id, buddy_node_id, created_at, offered_at
+            FROM backup_offers
+            ORDER BY created_at DESC
+            "
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|_| BuddyBackupsError::InternalServerError("Database error".to_string()))
+    }
+}
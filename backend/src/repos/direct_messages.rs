@@ -0,0 +1,92 @@
+use rocket_db_pools::Connection;
+use sqlx::Sqlite;
+use thiserror::Error;
+
+use super::entities::DirectMessage;
+use crate::infra::{db::MainDb, metrics::metrics, time::unix_now};
+
+pub struct DirectMessagesRepo {}
+
+#[derive(Debug, Error)]
+pub enum DirectMessagesError {
+    #[error("Internal server error: {0}")]
+    InternalServerError(String),
+
+    #[error("Message not found")]
+    NotFound(String),
+}
+
+impl DirectMessagesRepo {
+    pub fn init() -> Self {
+        DirectMessagesRepo {}
+    }
+
+    pub async fn insert(&self, pool: &sqlx::Pool<Sqlite>, message: DirectMessage) -> Result<(), DirectMessagesError> {
+        let _timer = metrics().time_query("direct_messages.insert");
+        let mut connection = pool
+            .acquire()
+            .await
+            .map_err(|_| DirectMessagesError::InternalServerError("Database error".to_string()))?;
+
+        sqlx::query!(
+            "
+            INSERT INTO direct_messages (id, sender_node_id, body, sent_at, received_at, read_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO NOTHING
+            ",
+            message.id,
+            message.sender_node_id,
+            message.body,
+            message.sent_at,
+            message.received_at,
+            message.read_at
+        )
+        .execute(&mut *connection)
+        .await
+        .map_err(|_| DirectMessagesError::InternalServerError("Database error".to_string()))?;
+
+        Ok(())
+    }
+
+    /// Messages received by this node, newest first.
+    pub async fn inbox(&self, db: &mut Connection<MainDb>) -> Result<Vec<DirectMessage>, DirectMessagesError> {
+        let _timer = metrics().time_query("direct_messages.inbox");
+        let messages = sqlx::query_as!(
+            DirectMessage,
+            "
+            SELECT id, sender_node_id, body, sent_at, received_at, read_at
+            FROM direct_messages
+            ORDER BY sent_at DESC
+            "
+        )
+        .fetch_all(&mut ***db)
+        .await
+        .map_err(|_| DirectMessagesError::InternalServerError("Database error".to_string()))?;
+
+        Ok(messages)
+    }
+
+    pub async fn mark_read(&self, db: &mut Connection<MainDb>, id: &str) -> Result<(), DirectMessagesError> {
+        let _timer = metrics().time_query("direct_messages.mark_read");
+        let now = unix_now();
+
+        let result = sqlx::query!(
+            "
+            UPDATE direct_messages
+            SET read_at = COALESCE(read_at, ?)
+            WHERE id = ?
+            ",
+            now,
+            id
+        )
+        .execute(&mut ***db)
+        .await
+        .map_err(|_| DirectMessagesError::InternalServerError("Database error".to_string()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(DirectMessagesError::NotFound("Message not found".to_string()));
+        }
+
+        Ok(())
+    }
+}
@@ -1,7 +1,11 @@
 use rocket::serde::{Deserialize, Serialize};
+use rocket::FromFormField;
 use sqlx;
+use utoipa::ToSchema;
 
-#[derive(sqlx::FromRow, Serialize, Deserialize)]
+use crate::panda_comms::lores_events::AlertCondition;
+
+#[derive(sqlx::FromRow, Serialize, Deserialize, ToSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct Node {
     pub id: String,
@@ -16,14 +20,185 @@ pub struct NodeConfig {
     pub name: String,
 }
 
-#[derive(sqlx::FromRow, Serialize, Deserialize)]
+#[derive(sqlx::FromRow, Serialize, Deserialize, ToSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct Region {
     pub network_id: String,
 }
 
+/// The network of a region this node is a member of.
+#[derive(sqlx::FromRow, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct NetworkConfig {
+    pub id: i64,
+    pub network_name: String,
+    pub bootstrap_node_id: Option<String>,
+    pub topic_name: Option<String>,
+    pub log_id: Option<String>,
+}
+
 #[derive(sqlx::FromRow, Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub struct PrivateKeyRow {
     pub private_key_hex: Option<String>,
+    pub private_key_encrypted: Option<String>,
+}
+
+#[derive(sqlx::FromRow, Serialize, Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct Notice {
+    pub id: String,
+    pub author_node_id: String,
+    pub title: String,
+    pub body: String,
+    pub priority: String,
+    pub posted_at: i64,
+    pub expires_at: Option<i64>,
+}
+
+#[derive(sqlx::FromRow, Serialize, Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct DirectMessage {
+    pub id: String,
+    pub sender_node_id: String,
+    pub body: String,
+    pub sent_at: i64,
+    pub received_at: i64,
+    pub read_at: Option<i64>,
+}
+
+#[derive(sqlx::FromRow, Serialize, Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct BackupBuddy {
+    pub node_id: String,
+    pub added_at: i64,
+}
+
+#[derive(sqlx::FromRow, Serialize, Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct HeldBackup {
+    pub blob_hash: String,
+    pub owner_node_id: String,
+    pub created_at: i64,
+}
+
+#[derive(sqlx::FromRow, Serialize, Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct BackupOffer {
+    pub blob_hash: String,
+    pub owner_node_id: String,
+    pub buddy_node_id: String,
+    pub created_at: i64,
+    pub offered_at: i64,
+}
+
+/// The host the node runs on at one point in time, see `infra::system_monitor`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct SystemSample {
+    pub sampled_at: i64,
+    /// Since the previous sample, so missing from the first one after a start.
+    pub cpu_percent: Option<f64>,
+    pub memory_used_percent: f64,
+    /// Of the volume holding the database.
+    pub disk_used_percent: f64,
+    pub disk_free_bytes: i64,
+    /// Of the hottest thermal zone, missing if the host reports none.
+    pub temperature_c: Option<f64>,
+    pub uptime_secs: i64,
+    pub load_1m: f64,
+    pub interfaces: Vec<InterfaceStats>,
+}
+
+/// Counters of a network interface since boot.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct InterfaceStats {
+    pub name: String,
+    pub rx_bytes: i64,
+    pub rx_errors: i64,
+    pub tx_bytes: i64,
+    pub tx_errors: i64,
+}
+
+#[derive(sqlx::FromRow, Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct SystemAlert {
+    pub id: String,
+    /// `high_cpu`, `high_memory`, `disk_full` or `high_temperature`.
+    pub kind: String,
+    /// The value that crossed the threshold.
+    pub value: f64,
+    pub threshold: f64,
+    pub raised_at: i64,
+    pub resolved_at: Option<i64>,
+}
+
+/// The latest health report of a node, see `NodeHealthReported`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct NodeHealth {
+    pub reported_at: i64,
+    pub version: String,
+    pub uptime_secs: Option<i64>,
+    pub disk_free_bytes: Option<i64>,
+    pub disk_used_percent: Option<f64>,
+    pub load_1m: Option<f64>,
+    pub open_alerts: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct RegionNode {
+    pub id: String,
+    pub name: String,
+    /// Missing until the node sends its first report.
+    pub health: Option<NodeHealth>,
+    /// Why the site needs attention, e.g. `no_recent_report` or `disk_full`. Empty when it is fine.
+    pub attention: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct AlertRule {
+    pub id: String,
+    pub name: String,
+    pub condition: AlertCondition,
+    pub author_node_id: String,
+    pub updated_at: i64,
+}
+
+#[derive(Serialize, Deserialize, FromFormField, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum AlertState {
+    Open,
+    /// Someone is looking into it. Stays so until resolved.
+    Acknowledged,
+    Resolved,
+}
+
+impl AlertState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertState::Open => "open",
+            AlertState::Acknowledged => "acknowledged",
+            AlertState::Resolved => "resolved",
+        }
+    }
+}
+
+/// A rule firing for a node, raised and resolved by this node's checks.
+#[derive(sqlx::FromRow, Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct Alert {
+    pub id: String,
+    pub rule_id: String,
+    /// The node the alert is about.
+    pub node_id: String,
+    /// `open`, `acknowledged` or `resolved`.
+    pub state: String,
+    pub message: String,
+    pub opened_at: i64,
+    pub acknowledged_at: Option<i64>,
+    pub resolved_at: Option<i64>,
 }
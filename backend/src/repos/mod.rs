@@ -1,5 +1,11 @@
+pub mod alerts;
+pub mod buddy_backups;
+pub mod direct_messages;
 pub mod entities;
 pub mod helpers;
 pub mod nodes;
+pub mod notices;
+pub mod region_data;
+pub mod system;
 pub mod this_node;
 pub mod this_p2panda_node;
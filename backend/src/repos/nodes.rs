@@ -1,21 +1,60 @@
-use sqlx::Sqlite;
+use sqlx::{Sqlite, SqliteConnection};
 use thiserror::Error;
 
-use super::entities::Node;
+use super::entities::{Node, NodeHealth};
+use crate::infra::metrics::metrics;
+use crate::infra::time::unix_now;
 
 pub struct NodesRepo {}
 
-#[derive(Debug, Error, Responder)]
+struct NodeWithHealthRow {
+    id: String,
+    name: String,
+    reported_at: Option<i64>,
+    version: Option<String>,
+    uptime_secs: Option<i64>,
+    disk_free_bytes: Option<i64>,
+    disk_used_percent: Option<f64>,
+    load_1m: Option<f64>,
+    open_alerts: Option<String>,
+}
+
+impl NodeWithHealthRow {
+    fn split(self) -> (Node, Option<NodeHealth>) {
+        let node = Node {
+            id: self.id,
+            name: self.name,
+        };
+
+        let (Some(reported_at), Some(version)) = (self.reported_at, self.version) else {
+            return (node, None);
+        };
+        let open_alerts = self
+            .open_alerts
+            .and_then(|open_alerts| serde_json::from_str(&open_alerts).ok())
+            .unwrap_or_default();
+
+        let health = NodeHealth {
+            reported_at,
+            version,
+            uptime_secs: self.uptime_secs,
+            disk_free_bytes: self.disk_free_bytes,
+            disk_used_percent: self.disk_used_percent,
+            load_1m: self.load_1m,
+            open_alerts,
+        };
+        (node, Some(health))
+    }
+}
+
+#[derive(Debug, Error)]
 pub enum NodesError {
     #[error("Internal server error: {0}")]
-    #[response(status = 500)]
     InternalServerError(String),
     // #[error("Cannot create node")]
-    // #[response(status = 409)]
     // CannotCreate(String),
 
     // #[error("Node not found")]
-    // #[response(status = 404)]
     // NotFound(String),
 }
 
@@ -25,7 +64,11 @@ impl NodesRepo {
     }
 
     pub async fn upsert(&self, pool: &sqlx::Pool<Sqlite>, node: Node) -> Result<(), NodesError> {
-        let mut connection = pool.acquire().await.unwrap();
+        let _timer = metrics().time_query("nodes.upsert");
+        let mut connection = pool
+            .acquire()
+            .await
+            .map_err(|_| NodesError::InternalServerError("Database error".to_string()))?;
 
         let _node = sqlx::query!(
             "INSERT INTO nodes (id, name) VALUES (?, ?) ON CONFLICT(id) DO UPDATE SET name = ?",
@@ -39,4 +82,169 @@ impl NodesRepo {
 
         Ok(())
     }
+
+    /// Forgets a node that left the region, along with its health report.
+    pub async fn remove(&self, pool: &sqlx::Pool<Sqlite>, node_id: &str) -> Result<(), NodesError> {
+        let _timer = metrics().time_query("nodes.remove");
+        let mut transaction = pool
+            .begin()
+            .await
+            .map_err(|_| NodesError::InternalServerError("Database error".to_string()))?;
+
+        sqlx::query!("DELETE FROM node_health WHERE node_id = ?", node_id)
+            .execute(&mut *transaction)
+            .await
+            .map_err(|_| NodesError::InternalServerError("Database error".to_string()))?;
+
+        sqlx::query!("DELETE FROM nodes WHERE id = ?", node_id)
+            .execute(&mut *transaction)
+            .await
+            .map_err(|_| NodesError::InternalServerError("Database error".to_string()))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|_| NodesError::InternalServerError("Database error".to_string()))?;
+
+        Ok(())
+    }
+
+    /// Records that `old_node_id` is now known as `new_node_id`, and moves the node, its notices,
+    /// messages and alert rules over to its new id.
+    pub async fn rotate_key(&self, pool: &sqlx::Pool<Sqlite>, old_node_id: &str, new_node_id: &str) -> Result<(), NodesError> {
+        let _timer = metrics().time_query("nodes.rotate_key");
+        let mut transaction = pool
+            .begin()
+            .await
+            .map_err(|_| NodesError::InternalServerError("Database error".to_string()))?;
+        let rotated_at = unix_now();
+
+        sqlx::query!(
+            "
+            INSERT INTO key_rotations (old_node_id, new_node_id, rotated_at)
+            VALUES (?, ?, ?)
+            ON CONFLICT(old_node_id) DO NOTHING
+            ",
+            old_node_id,
+            new_node_id,
+            rotated_at
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| NodesError::InternalServerError("Database error".to_string()))?;
+
+        sqlx::query!(
+            "
+            UPDATE nodes SET id = ?
+            WHERE id = ? AND NOT EXISTS (SELECT 1 FROM nodes WHERE id = ?)
+            ",
+            new_node_id,
+            old_node_id,
+            new_node_id
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| NodesError::InternalServerError("Database error".to_string()))?;
+
+        // what the node wrote under the old id is now shown under the new one
+        sqlx::query!("UPDATE notices SET author_node_id = ? WHERE author_node_id = ?", new_node_id, old_node_id)
+            .execute(&mut *transaction)
+            .await
+            .map_err(|_| NodesError::InternalServerError("Database error".to_string()))?;
+
+        sqlx::query!(
+            "UPDATE direct_messages SET sender_node_id = ? WHERE sender_node_id = ?",
+            new_node_id,
+            old_node_id
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| NodesError::InternalServerError("Database error".to_string()))?;
+
+        sqlx::query!(
+            "UPDATE alert_rules SET author_node_id = ? WHERE author_node_id = ?",
+            new_node_id,
+            old_node_id
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| NodesError::InternalServerError("Database error".to_string()))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|_| NodesError::InternalServerError("Database error".to_string()))?;
+
+        Ok(())
+    }
+
+    pub async fn all(&self, connection: &mut SqliteConnection) -> Result<Vec<Node>, NodesError> {
+        let _timer = metrics().time_query("nodes.all");
+        let nodes = sqlx::query_as!(Node, "SELECT id, name FROM nodes ORDER BY name")
+            .fetch_all(&mut *connection)
+            .await
+            .map_err(|_| NodesError::InternalServerError("Database error".to_string()))?;
+
+        Ok(nodes)
+    }
+
+    /// Nodes with their latest health report, if they sent one.
+    pub async fn all_with_health(&self, connection: &mut SqliteConnection) -> Result<Vec<(Node, Option<NodeHealth>)>, NodesError> {
+        let _timer = metrics().time_query("nodes.all_with_health");
+        let rows = sqlx::query_as!(
+            NodeWithHealthRow,
+            r#"
+            SELECT nodes.id, nodes.name,
+                node_health.reported_at AS "reported_at?", node_health.version AS "version?",
+                node_health.uptime_secs, node_health.disk_free_bytes, node_health.disk_used_percent,
+                node_health.load_1m, node_health.open_alerts AS "open_alerts?"
+            FROM nodes
+            LEFT JOIN node_health ON node_health.node_id = nodes.id
+            ORDER BY nodes.name
+            "#
+        )
+        .fetch_all(&mut *connection)
+        .await
+        .map_err(|_| NodesError::InternalServerError("Database error".to_string()))?;
+
+        Ok(rows
+            .into_iter()
+            .map(NodeWithHealthRow::split)
+            .collect())
+    }
+
+    /// Keeps the health report of `node_id`, unless a newer one arrived first, e.g. during a sync.
+    pub async fn record_health(&self, pool: &sqlx::Pool<Sqlite>, node_id: &str, health: NodeHealth) -> Result<(), NodesError> {
+        let _timer = metrics().time_query("nodes.record_health");
+        let open_alerts = serde_json::to_string(&health.open_alerts).map_err(|e| NodesError::InternalServerError(e.to_string()))?;
+
+        sqlx::query!(
+            "
+            INSERT INTO node_health (node_id, reported_at, version, uptime_secs, disk_free_bytes, disk_used_percent, load_1m, open_alerts)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(node_id) DO UPDATE SET
+                reported_at = excluded.reported_at,
+                version = excluded.version,
+                uptime_secs = excluded.uptime_secs,
+                disk_free_bytes = excluded.disk_free_bytes,
+                disk_used_percent = excluded.disk_used_percent,
+                load_1m = excluded.load_1m,
+                open_alerts = excluded.open_alerts
+            WHERE excluded.reported_at > node_health.reported_at
+            ",
+            node_id,
+            health.reported_at,
+            health.version,
+            health.uptime_secs,
+            health.disk_free_bytes,
+            health.disk_used_percent,
+            health.load_1m,
+            open_alerts
+        )
+        .execute(pool)
+        .await
+        .map_err(|_| NodesError::InternalServerError("Database error".to_string()))?;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,71 @@
+use sqlx::{Sqlite, SqliteConnection};
+use thiserror::Error;
+
+use super::entities::Notice;
+use crate::infra::{metrics::metrics, time::unix_now};
+
+pub struct NoticesRepo {}
+
+#[derive(Debug, Error)]
+pub enum NoticesError {
+    #[error("Internal server error: {0}")]
+    InternalServerError(String),
+}
+
+impl NoticesRepo {
+    pub fn init() -> Self {
+        NoticesRepo {}
+    }
+
+    /// Notices are immutable once posted, so seeing the same one again (e.g. after a re-sync) is
+    /// ignored.
+    pub async fn insert(&self, pool: &sqlx::Pool<Sqlite>, notice: Notice) -> Result<(), NoticesError> {
+        let _timer = metrics().time_query("notices.insert");
+        let mut connection = pool
+            .acquire()
+            .await
+            .map_err(|_| NoticesError::InternalServerError("Database error".to_string()))?;
+
+        sqlx::query!(
+            "
+            INSERT INTO notices (id, author_node_id, title, body, priority, posted_at, expires_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO NOTHING
+            ",
+            notice.id,
+            notice.author_node_id,
+            notice.title,
+            notice.body,
+            notice.priority,
+            notice.posted_at,
+            notice.expires_at
+        )
+        .execute(&mut *connection)
+        .await
+        .map_err(|_| NoticesError::InternalServerError("Database error".to_string()))?;
+
+        Ok(())
+    }
+
+    /// Notices that have not expired yet, newest first.
+    pub async fn current(&self, connection: &mut SqliteConnection) -> Result<Vec<Notice>, NoticesError> {
+        let _timer = metrics().time_query("notices.current");
+        let now = unix_now();
+
+        let notices = sqlx::query_as!(
+            Notice,
+            "
+            SELECT id, author_node_id, title, body, priority, posted_at, expires_at
+            FROM notices
+            WHERE expires_at IS NULL OR expires_at > ?
+            ORDER BY posted_at DESC
+            ",
+            now
+        )
+        .fetch_all(&mut *connection)
+        .await
+        .map_err(|_| NoticesError::InternalServerError("Database error".to_string()))?;
+
+        Ok(notices)
+    }
+}
@@ -0,0 +1,53 @@
+use sqlx::SqliteConnection;
+use thiserror::Error;
+
+use crate::infra::metrics::metrics;
+
+/// Tables filled from the events of a region, in an order that deletes rows before the ones they
+/// point to. The node's own key, host samples and the backups it holds for other nodes aren't
+/// region data and stay, so a node that left can still give a lost buddy its backup.
+const REGION_TABLES: [&str; 9] = [
+    "alerts",
+    "alert_rules",
+    "backup_offers",
+    "backup_buddies",
+    "key_rotations",
+    "direct_messages",
+    "notices",
+    "node_health",
+    "nodes",
+];
+
+pub struct RegionDataRepo {}
+
+#[derive(Debug, Error)]
+pub enum RegionDataError {
+    #[error("Internal server error: {0}")]
+    InternalServerError(String),
+}
+
+impl RegionDataRepo {
+    pub fn init() -> Self {
+        RegionDataRepo {}
+    }
+
+    /// Empties everything this node learned from its region, as if it never joined one.
+    pub async fn clear(&self, connection: &mut SqliteConnection) -> Result<(), RegionDataError> {
+        let _timer = metrics().time_query("region_data.clear");
+
+        // this node's own row in `nodes` goes too
+        sqlx::query!("UPDATE node_configs SET this_node_id = NULL")
+            .execute(&mut *connection)
+            .await
+            .map_err(|_| RegionDataError::InternalServerError("Database error".to_string()))?;
+
+        for table in REGION_TABLES {
+            sqlx::query(&format!("DELETE FROM {}", table))
+                .execute(&mut *connection)
+                .await
+                .map_err(|_| RegionDataError::InternalServerError("Database error".to_string()))?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+use sqlx::Sqlite;
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::entities::{InterfaceStats, SystemAlert, SystemSample};
+use crate::infra::metrics::metrics;
+use crate::infra::system_monitor::SystemAlertKind;
+
+pub struct SystemRepo {}
+
+#[derive(Debug, Error)]
+pub enum SystemError {
+    #[error("Internal server error: {0}")]
+    InternalServerError(String),
+}
+
+struct SystemSampleRow {
+    sampled_at: i64,
+    cpu_percent: Option<f64>,
+    memory_used_percent: f64,
+    disk_used_percent: f64,
+    disk_free_bytes: i64,
+    temperature_c: Option<f64>,
+    uptime_secs: i64,
+    load_1m: f64,
+    interfaces: String,
+}
+
+impl From<SystemSampleRow> for SystemSample {
+    fn from(row: SystemSampleRow) -> Self {
+        // written by `add_sample`, an unreadable list only loses the interfaces
+        let interfaces: Vec<InterfaceStats> = serde_json::from_str(&row.interfaces).unwrap_or_default();
+
+        SystemSample {
+            sampled_at: row.sampled_at,
+            cpu_percent: row.cpu_percent,
+            memory_used_percent: row.memory_used_percent,
+            disk_used_percent: row.disk_used_percent,
+            disk_free_bytes: row.disk_free_bytes,
+            temperature_c: row.temperature_c,
+            uptime_secs: row.uptime_secs,
+            load_1m: row.load_1m,
+            interfaces,
+        }
+    }
+}
+
+impl SystemRepo {
+    pub fn init() -> Self {
+        SystemRepo {}
+    }
+
+    pub async fn add_sample(&self, pool: &sqlx::Pool<Sqlite>, sample: &SystemSample) -> Result<(), SystemError> {
+        let _timer = metrics().time_query("system.add_sample");
+        let interfaces = serde_json::to_string(&sample.interfaces).map_err(|e| SystemError::InternalServerError(e.to_string()))?;
+
+        sqlx::query!(
+            "
+            INSERT INTO system_samples (sampled_at, cpu_percent, memory_used_percent, disk_used_percent, disk_free_bytes, temperature_c, uptime_secs, load_1m, interfaces)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(sampled_at) DO NOTHING
+            ",
+            sample.sampled_at,
+            sample.cpu_percent,
+            sample.memory_used_percent,
+            sample.disk_used_percent,
+            sample.disk_free_bytes,
+            sample.temperature_c,
+            sample.uptime_secs,
+            sample.load_1m,
+            interfaces
+        )
+        .execute(pool)
+        .await
+        .map_err(|_| SystemError::InternalServerError("Database error".to_string()))?;
+
+        Ok(())
+    }
+
+    /// Samples taken since `since`, oldest first.
+    pub async fn samples(&self, pool: &sqlx::Pool<Sqlite>, since: i64) -> Result<Vec<SystemSample>, SystemError> {
+        let _timer = metrics().time_query("system.samples");
+        let rows = sqlx::query_as!(
+            SystemSampleRow,
+            "
+            SELECT sampled_at, cpu_percent, memory_used_percent, disk_used_percent, disk_free_bytes, temperature_c, uptime_secs, load_1m, interfaces
+            FROM system_samples
+            WHERE sampled_at >= ?
+            ORDER BY sampled_at
+            ",
+            since
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|_| SystemError::InternalServerError("Database error".to_string()))?;
+
+        Ok(rows.into_iter().map(SystemSample::from).collect())
+    }
+
+    /// The newest sample, if it was taken since `since`.
+    pub async fn latest_sample(&self, pool: &sqlx::Pool<Sqlite>, since: i64) -> Result<Option<SystemSample>, SystemError> {
+        let _timer = metrics().time_query("system.latest_sample");
+        let row = sqlx::query_as!(
+            SystemSampleRow,
+            "
+            SELECT sampled_at, cpu_percent, memory_used_percent, disk_used_percent, disk_free_bytes, temperature_c, uptime_secs, load_1m, interfaces
+            FROM system_samples
+            WHERE sampled_at >= ?
+            ORDER BY sampled_at DESC
+            LIMIT 1
+            ",
+            since
+        )
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| SystemError::InternalServerError("Database error".to_string()))?;
+
+        Ok(row.map(SystemSample::from))
+    }
+
+    /// Alerts still open, and the ones resolved since `since`, newest first.
+    pub async fn alerts(&self, pool: &sqlx::Pool<Sqlite>, since: i64) -> Result<Vec<SystemAlert>, SystemError> {
+        let _timer = metrics().time_query("system.alerts");
+        sqlx::query_as!(
+            SystemAlert,
+            "
+            SELECT id, kind, value, threshold, raised_at, resolved_at
+            FROM system_alerts
+            WHERE resolved_at IS NULL OR resolved_at >= ?
+            ORDER BY raised_at DESC
+            ",
+            since
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|_| SystemError::InternalServerError("Database error".to_string()))
+    }
+
+    pub async fn open_alerts(&self, pool: &sqlx::Pool<Sqlite>) -> Result<Vec<SystemAlert>, SystemError> {
+        let _timer = metrics().time_query("system.open_alerts");
+        sqlx::query_as!(
+            SystemAlert,
+            "
+            SELECT id, kind, value, threshold, raised_at, resolved_at
+            FROM system_alerts
+            WHERE resolved_at IS NULL
+            "
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|_| SystemError::InternalServerError("Database error".to_string()))
+    }
+
+    pub async fn raise_alert(
+        &self,
+        pool: &sqlx::Pool<Sqlite>,
+        kind: SystemAlertKind,
+        value: f64,
+        threshold: f64,
+        raised_at: i64,
+    ) -> Result<(), SystemError> {
+        let _timer = metrics().time_query("system.raise_alert");
+        let id = Uuid::new_v4().to_string();
+        let kind = kind.as_str();
+
+        sqlx::query!(
+            "
+            INSERT INTO system_alerts (id, kind, value, threshold, raised_at)
+            VALUES (?, ?, ?, ?, ?)
+            ",
+            id,
+            kind,
+            value,
+            threshold,
+            raised_at
+        )
+        .execute(pool)
+        .await
+        .map_err(|_| SystemError::InternalServerError("Database error".to_string()))?;
+
+        Ok(())
+    }
+
+    pub async fn resolve_alert(&self, pool: &sqlx::Pool<Sqlite>, id: &str, resolved_at: i64) -> Result<(), SystemError> {
+        let _timer = metrics().time_query("system.resolve_alert");
+        sqlx::query!(
+            "
+            UPDATE system_alerts
+            SET resolved_at = ?
+            WHERE id = ?
+            ",
+            resolved_at,
+            id
+        )
+        .execute(pool)
+        .await
+        .map_err(|_| SystemError::InternalServerError("Database error".to_string()))?;
+
+        Ok(())
+    }
+
+    /// Drops samples, and resolved alerts, from before `before`.
+    pub async fn prune(&self, pool: &sqlx::Pool<Sqlite>, before: i64) -> Result<(), SystemError> {
+        let _timer = metrics().time_query("system.prune");
+        sqlx::query!(
+            "
+            DELETE FROM system_samples
+            WHERE sampled_at < ?
+            ",
+            before
+        )
+        .execute(pool)
+        .await
+        .map_err(|_| SystemError::InternalServerError("Database error".to_string()))?;
+
+        sqlx::query!(
+            "
+            DELETE FROM system_alerts
+            WHERE resolved_at < ?
+            ",
+            before
+        )
+        .execute(pool)
+        .await
+        .map_err(|_| SystemError::InternalServerError("Database error".to_string()))?;
+
+        Ok(())
+    }
+}
@@ -1,18 +1,19 @@
 use super::entities::Node;
-use crate::{infra::db::MainDb, repos::helpers::NODE_CONFIG_ID};
+use crate::{
+    infra::{db::MainDb, metrics::metrics},
+    repos::helpers::NODE_CONFIG_ID,
+};
 use rocket_db_pools::Connection;
 use thiserror::Error;
 
 pub struct ThisNodeRepo {}
 
-#[derive(Debug, Error, Responder)]
+#[derive(Debug, Error)]
 pub enum ThisNodeRepoError {
     #[error("Internal server error: {0}")]
-    #[response(status = 500)]
     InternalServerError(String),
 
     #[error("Node not found")]
-    #[response(status = 404)]
     NotFound(String),
 }
 
@@ -22,6 +23,7 @@ impl ThisNodeRepo {
     }
 
     pub async fn find(&self, db: &mut Connection<MainDb>) -> Result<Node, ThisNodeRepoError> {
+        let _timer = metrics().time_query("this_node.find");
         let node = sqlx::query_as!(
             Node,
             "
@@ -1,23 +1,30 @@
 use crate::{
-    infra::db::MainDb,
+    infra::{
+        crypto::{decrypt_with_passphrase, encrypt_with_passphrase, EncryptedBlob},
+        db::MainDb,
+        metrics::metrics,
+    },
+    panda_comms::{container::build_private_key_from_hex, identity::PrivateKeyStatus},
     repos::{
-        entities::PrivateKeyRow,
+        entities::{NetworkConfig, PrivateKeyRow},
         helpers::{NETWORK_CONFIG_ID, NODE_CONFIG_ID},
     },
 };
-use hex;
-use p2panda_core::{identity::PRIVATE_KEY_LEN, PrivateKey};
+use p2panda_core::PrivateKey;
 use rocket_db_pools::Connection;
-use sqlx;
+use sqlx::{self, Sqlite, SqliteConnection};
 use thiserror::Error;
+use tracing::info;
 
 pub struct ThisP2PandaNodeRepo {}
 
-#[derive(Debug, Error, Responder)]
+#[derive(Debug, Error)]
 pub enum ThisP2PandaNodeRepoError {
     #[error("Internal server error: {0}")]
-    #[response(status = 500)]
     InternalServerError(String),
+
+    #[error("Invalid: {0}")]
+    Invalid(String),
 }
 
 #[derive(Clone)]
@@ -31,7 +38,12 @@ impl ThisP2PandaNodeRepo {
     }
 
     pub async fn get_network_name(&self, db: &MainDb) -> Result<Option<String>, ThisP2PandaNodeRepoError> {
-        let mut connection = db.sqlite_pool().acquire().await.unwrap();
+        let _timer = metrics().time_query("this_p2panda_node.get_network_name");
+        let mut connection = db
+            .sqlite_pool()
+            .acquire()
+            .await
+            .map_err(|_| ThisP2PandaNodeRepoError::InternalServerError("Database error".to_string()))?;
 
         let result = sqlx::query!(
             "
@@ -55,6 +67,7 @@ impl ThisP2PandaNodeRepo {
     // TODO: I don't know how to handle the DB connection in these two different ways, this is a
     // temporary solution
     pub async fn get_network_name_conn(&self, connection: &mut Connection<MainDb>) -> Result<Option<String>, ThisP2PandaNodeRepoError> {
+        let _timer = metrics().time_query("this_p2panda_node.get_network_name_conn");
         let result = sqlx::query!(
             "
             SELECT network_name
@@ -75,7 +88,12 @@ impl ThisP2PandaNodeRepo {
     }
 
     pub async fn get_bootstrap_details(&self, db: &MainDb) -> Result<Option<SimplifiedNodeAddress>, ThisP2PandaNodeRepoError> {
-        let mut connection = db.sqlite_pool().acquire().await.unwrap();
+        let _timer = metrics().time_query("this_p2panda_node.get_bootstrap_details");
+        let mut connection = db
+            .sqlite_pool()
+            .acquire()
+            .await
+            .map_err(|_| ThisP2PandaNodeRepoError::InternalServerError("Database error".to_string()))?;
 
         let result = sqlx::query!(
             "
@@ -101,15 +119,16 @@ impl ThisP2PandaNodeRepo {
 
     pub async fn set_network_config(
         &self,
-        db: &mut Connection<MainDb>,
+        connection: &mut SqliteConnection,
         network_name: String,
         peer_address: Option<SimplifiedNodeAddress>,
     ) -> Result<(), ThisP2PandaNodeRepoError> {
+        let _timer = metrics().time_query("this_p2panda_node.set_network_config");
         let bootstrap_node_id = peer_address
             .as_ref()
             .map(|peer| peer.node_id.clone());
 
-        let _region = sqlx::query!(
+        sqlx::query!(
             "
             UPDATE network_configs
             SET network_name = ?, bootstrap_node_id = ?
@@ -119,76 +138,200 @@ impl ThisP2PandaNodeRepo {
             bootstrap_node_id,
             NETWORK_CONFIG_ID
         )
-        .execute(&mut ***db)
-        .await;
+        .execute(&mut *connection)
+        .await
+        .map_err(|_| ThisP2PandaNodeRepoError::InternalServerError("Database error".to_string()))?;
 
-        return Ok(());
+        Ok(())
     }
 
-    pub async fn get_or_create_private_key(&self, db: &MainDb) -> Result<PrivateKey, ThisP2PandaNodeRepoError> {
-        let private_key = self.get_private_key(db).await?;
+    /// The regions this node is a member of, its first one (`NETWORK_CONFIG_ID`) included once
+    /// it joined it.
+    pub async fn network_configs(&self, pool: &sqlx::Pool<Sqlite>) -> Result<Vec<NetworkConfig>, ThisP2PandaNodeRepoError> {
+        let _timer = metrics().time_query("this_p2panda_node.network_configs");
+        sqlx::query_as!(
+            NetworkConfig,
+            r#"
+            SELECT id AS "id!: i64", network_name AS "network_name!", bootstrap_node_id, topic_name, log_id
+            FROM network_configs
+            WHERE network_name IS NOT NULL
+            ORDER BY id
+            "#
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|_| ThisP2PandaNodeRepoError::InternalServerError("Database error".to_string()))
+    }
 
-        match private_key {
-            None => {
-                let new_private_key: PrivateKey = self.create_private_key(db).await?;
-                return Ok(new_private_key);
-            }
-            Some(private_key) => {
-                return Ok(private_key);
-            }
-        }
+    /// Adds a region besides the first one, with the next free id.
+    pub async fn add_network_config(
+        &self,
+        pool: &sqlx::Pool<Sqlite>,
+        network_name: &str,
+        peer_address: Option<SimplifiedNodeAddress>,
+        topic_name: Option<String>,
+        log_id: Option<String>,
+    ) -> Result<NetworkConfig, ThisP2PandaNodeRepoError> {
+        let _timer = metrics().time_query("this_p2panda_node.add_network_config");
+        let bootstrap_node_id = peer_address.map(|peer| peer.node_id);
+
+        sqlx::query_as!(
+            NetworkConfig,
+            r#"
+            INSERT INTO network_configs (id, network_name, bootstrap_node_id, topic_name, log_id)
+            SELECT MAX(id) + 1, ?, ?, ?, ?
+            FROM network_configs
+            RETURNING id AS "id!: i64", network_name AS "network_name!", bootstrap_node_id, topic_name, log_id
+            "#,
+            network_name,
+            bootstrap_node_id,
+            topic_name,
+            log_id
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|_| ThisP2PandaNodeRepoError::InternalServerError("Database error".to_string()))
     }
 
-    async fn get_private_key(&self, db: &MainDb) -> Result<Option<PrivateKey>, ThisP2PandaNodeRepoError> {
-        let private_key_hex: Option<String> = self.get_private_key_hex(db).await?;
+    /// Forgets every region, which leaves this node unconfigured.
+    pub async fn clear_network_configs(&self, connection: &mut SqliteConnection) -> Result<(), ThisP2PandaNodeRepoError> {
+        let _timer = metrics().time_query("this_p2panda_node.clear_network_configs");
 
-        match private_key_hex {
-            None => return Ok(None),
-            Some(private_key_hex) => {
-                let private_key = Self::build_private_key_from_hex(private_key_hex)
-                    .ok_or(ThisP2PandaNodeRepoError::InternalServerError("Failed to build private key".to_string()))?;
+        sqlx::query!(
+            "
+            UPDATE network_configs
+            SET network_name = NULL, bootstrap_node_id = NULL, topic_name = NULL, log_id = NULL
+            WHERE network_configs.id = ?
+            ",
+            NETWORK_CONFIG_ID
+        )
+        .execute(&mut *connection)
+        .await
+        .map_err(|_| ThisP2PandaNodeRepoError::InternalServerError("Database error".to_string()))?;
 
-                return Ok(Some(private_key));
-            }
-        }
+        sqlx::query!("DELETE FROM network_configs WHERE network_configs.id != ?", NETWORK_CONFIG_ID)
+            .execute(&mut *connection)
+            .await
+            .map_err(|_| ThisP2PandaNodeRepoError::InternalServerError("Database error".to_string()))?;
+
+        Ok(())
     }
 
-    async fn create_private_key(&self, db: &MainDb) -> Result<PrivateKey, ThisP2PandaNodeRepoError> {
-        let new_private_key = PrivateKey::new();
+    /// Forgets a region besides the first one.
+    pub async fn remove_network_config(&self, pool: &sqlx::Pool<Sqlite>, id: i64) -> Result<(), ThisP2PandaNodeRepoError> {
+        let _timer = metrics().time_query("this_p2panda_node.remove_network_config");
+
+        sqlx::query!("DELETE FROM network_configs WHERE network_configs.id = ?", id)
+            .execute(pool)
+            .await
+            .map_err(|_| ThisP2PandaNodeRepoError::InternalServerError("Database error".to_string()))?;
+
+        Ok(())
+    }
 
-        self.set_private_key_hex(db, new_private_key.to_hex())
+    /// Loads the node private key, creating one on first start. With a passphrase the key is kept
+    /// encrypted at rest: a plaintext key is encrypted on the way and a new key is stored
+    /// encrypted. An encrypted key without a passphrase comes back locked.
+    pub async fn load_private_key(
+        &self,
+        db: &MainDb,
+        passphrase: Option<&str>,
+    ) -> Result<(Option<PrivateKey>, PrivateKeyStatus), ThisP2PandaNodeRepoError> {
+        let _timer = metrics().time_query("this_p2panda_node.load_private_key");
+        let stored = self
+            .get_stored_private_key(db.sqlite_pool())
             .await?;
 
-        println!("Created new private key");
+        match (stored.private_key_encrypted, stored.private_key_hex) {
+            (Some(encrypted), _) => {
+                let Some(passphrase) = passphrase else {
+                    return Ok((None, PrivateKeyStatus::Locked));
+                };
+
+                let encrypted: EncryptedBlob = serde_json::from_str(&encrypted)
+                    .map_err(|_| ThisP2PandaNodeRepoError::InternalServerError("Corrupted private key".to_string()))?;
+                let private_key_hex =
+                    decrypt_with_passphrase(passphrase, &encrypted).map_err(|e| ThisP2PandaNodeRepoError::Invalid(e.to_string()))?;
+                let private_key = String::from_utf8(private_key_hex)
+                    .ok()
+                    .and_then(build_private_key_from_hex)
+                    .ok_or(ThisP2PandaNodeRepoError::InternalServerError("Failed to build private key".to_string()))?;
+
+                Ok((Some(private_key), PrivateKeyStatus::Unlocked))
+            }
+            (None, Some(private_key_hex)) => {
+                let private_key = build_private_key_from_hex(private_key_hex)
+                    .ok_or(ThisP2PandaNodeRepoError::InternalServerError("Failed to build private key".to_string()))?;
+
+                if passphrase.is_none() {
+                    return Ok((Some(private_key), PrivateKeyStatus::Plaintext));
+                }
 
-        return Ok(new_private_key);
+                self.set_private_key(db, &private_key, passphrase)
+                    .await?;
+                info!("Encrypted private key at rest");
+
+                Ok((Some(private_key), PrivateKeyStatus::Unlocked))
+            }
+            (None, None) => {
+                let private_key = PrivateKey::new();
+
+                self.set_private_key(db, &private_key, passphrase)
+                    .await?;
+                info!("Created new private key");
+
+                Ok((Some(private_key), PrivateKeyStatus::for_passphrase(passphrase)))
+            }
+        }
     }
 
-    async fn set_private_key_hex(&self, db: &MainDb, private_key_hex: String) -> Result<(), ThisP2PandaNodeRepoError> {
-        let mut connection = db.sqlite_pool().acquire().await.unwrap();
+    /// Stores the private key, encrypted if a passphrase is given. Only one of the two columns is
+    /// ever set, so switching to encryption also wipes the plaintext key.
+    pub async fn set_private_key(&self, db: &MainDb, private_key: &PrivateKey, passphrase: Option<&str>) -> Result<(), ThisP2PandaNodeRepoError> {
+        let _timer = metrics().time_query("this_p2panda_node.set_private_key");
+        let (private_key_hex, private_key_encrypted) = match passphrase {
+            None => (Some(private_key.to_hex()), None),
+            Some(passphrase) => {
+                let encrypted = encrypt_with_passphrase(passphrase, private_key.to_hex().as_bytes())
+                    .map_err(|e| ThisP2PandaNodeRepoError::InternalServerError(e.to_string()))?;
+                let encrypted = serde_json::to_string(&encrypted).map_err(|e| ThisP2PandaNodeRepoError::InternalServerError(e.to_string()))?;
+                (None, Some(encrypted))
+            }
+        };
+
+        let mut connection = db
+            .sqlite_pool()
+            .acquire()
+            .await
+            .map_err(|_| ThisP2PandaNodeRepoError::InternalServerError("Database error".to_string()))?;
 
-        let _region = sqlx::query!(
+        sqlx::query!(
             "
             UPDATE node_configs
-            SET private_key_hex = ?
+            SET private_key_hex = ?, private_key_encrypted = ?
             WHERE node_configs.id = ?
             ",
             private_key_hex,
+            private_key_encrypted,
             NODE_CONFIG_ID
         )
         .execute(&mut *connection)
-        .await;
+        .await
+        .map_err(|_| ThisP2PandaNodeRepoError::InternalServerError("Database error".to_string()))?;
 
-        return Ok(());
+        Ok(())
     }
 
-    async fn get_private_key_hex(&self, db: &MainDb) -> Result<Option<String>, ThisP2PandaNodeRepoError> {
-        let mut connection = db.sqlite_pool().acquire().await.unwrap();
+    async fn get_stored_private_key(&self, pool: &sqlx::Pool<Sqlite>) -> Result<PrivateKeyRow, ThisP2PandaNodeRepoError> {
+        let mut connection = pool
+            .acquire()
+            .await
+            .map_err(|_| ThisP2PandaNodeRepoError::InternalServerError("Database error".to_string()))?;
 
-        let result = sqlx::query_as!(
+        sqlx::query_as!(
             PrivateKeyRow,
             "
-            SELECT private_key_hex
+            SELECT private_key_hex, private_key_encrypted
             FROM node_configs
             WHERE node_configs.id = ?
             LIMIT 1
@@ -197,15 +340,6 @@ impl ThisP2PandaNodeRepo {
         )
         .fetch_one(&mut *connection)
         .await
-        .map_err(|_| ThisP2PandaNodeRepoError::InternalServerError("Database error".to_string()))?;
-
-        return Ok(result.private_key_hex);
-    }
-
-    // TODO: This should be in p2panda-core, submit a PR
-    fn build_private_key_from_hex(private_key_hex: String) -> Option<PrivateKey> {
-        let private_key_bytes = hex::decode(private_key_hex).ok()?;
-        let private_key_byte_array: [u8; PRIVATE_KEY_LEN] = private_key_bytes.try_into().ok()?;
-        Some(PrivateKey::from_bytes(&private_key_byte_array))
+        .map_err(|_| ThisP2PandaNodeRepoError::InternalServerError("Database error".to_string()))
     }
 }
@@ -0,0 +1,405 @@
+use rocket::data::{Data, ToByteUnit};
+use rocket::http::Header;
+use rocket::response::stream::{Event, EventStream};
+use rocket::serde::json::Json;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use rocket::{Route, Shutdown, State};
+use utoipa::openapi::schema::{KnownFormat, ObjectBuilder, Schema, SchemaFormat, Type};
+use utoipa::openapi::RefOr;
+use utoipa::{OpenApi, PartialSchema, ToSchema};
+
+use crate::infra::api_error::ApiError;
+use crate::infra::backup::{create_backup, restore_backup, start_restored, BackupContents, BackupManifest};
+use crate::infra::buddy_backups::{backup_passphrase_from_env, push_backup, restore_from_buddy};
+use crate::infra::db::MainDb;
+use crate::infra::diagnostics::{collect, Diagnostics};
+use crate::infra::logging::{log_buffer, LogQuery, LogRecord};
+use crate::infra::settings::Settings;
+use crate::panda_comms::container::P2PandaContainer;
+use crate::panda_comms::identity::PrivateKeyStatus;
+use crate::panda_comms::regions::Regions;
+use crate::repos::buddy_backups::BuddyBackupsRepo;
+use crate::repos::entities::{BackupBuddy, BackupOffer, HeldBackup};
+
+const MAX_ARCHIVE_MIB: u64 = 512;
+
+#[derive(Responder)]
+#[response(content_type = "application/gzip")]
+struct BackupArchive {
+    data: Vec<u8>,
+    disposition: Header<'static>,
+}
+
+/// A backup archive as it appears in the OpenAPI document.
+struct ArchiveBytes;
+
+impl PartialSchema for ArchiveBytes {
+    fn schema() -> RefOr<Schema> {
+        ObjectBuilder::new()
+            .schema_type(Type::String)
+            .format(Some(SchemaFormat::KnownFormat(KnownFormat::Binary)))
+            .into()
+    }
+}
+
+impl ToSchema for ArchiveBytes {}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Backup archive", content_type = "application/gzip", body = ArchiveBytes),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[post("/backup")]
+async fn backup(db: &State<MainDb>, panda_container: &State<P2PandaContainer>) -> Result<BackupArchive, ApiError> {
+    let (manifest, data) = create_backup(db.sqlite_pool(), panda_container, BackupContents::Full).await?;
+
+    let file_name = manifest.file_name();
+
+    Ok(BackupArchive {
+        data,
+        disposition: Header::new("Content-Disposition", format!("attachment; filename=\"{}\"", file_name)),
+    })
+}
+
+#[derive(Responder)]
+#[response(content_type = "json")]
+struct DiagnosticsFile {
+    data: Json<Diagnostics>,
+    disposition: Header<'static>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct RestoreDetails {
+    pub manifest: BackupManifest,
+    pub key_status: PrivateKeyStatus,
+}
+
+/// Takes an archive made by `/backup` as the request body. If its key is encrypted with another
+/// passphrase the node comes back locked, see `/api/this_p2panda_node/key/unlock`.
+#[utoipa::path(
+    request_body(content = ArchiveBytes, content_type = "application/octet-stream", description = "Archive made by `/backup`"),
+    responses(
+        (status = 200, description = "Restored", body = RestoreDetails),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[post("/restore", data = "<archive>")]
+async fn restore(
+    db: &State<MainDb>,
+    archive: Data<'_>,
+    panda_container: &State<P2PandaContainer>,
+    regions: &State<Regions>,
+) -> Result<Json<RestoreDetails>, ApiError> {
+    let archive = archive
+        .open(MAX_ARCHIVE_MIB.mebibytes())
+        .into_bytes()
+        .await
+        .map_err(ApiError::internal)?;
+
+    if !archive.is_complete() {
+        return Err(ApiError::validation_failed(format!("Backup is larger than {} MiB", MAX_ARCHIVE_MIB)));
+    }
+
+    let manifest = restore_backup(db, panda_container, archive.into_inner()).await?;
+    start_restored(panda_container).await?;
+    regions.reload(db.sqlite_pool()).await;
+
+    Ok(Json(RestoreDetails {
+        manifest,
+        key_status: panda_container.get_params().await.key_status,
+    }))
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Nodes this node pushes its backups to", body = Vec<BackupBuddy>),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[get("/backup_buddies", format = "json")]
+async fn backup_buddies(db: &State<MainDb>) -> Result<Json<Vec<BackupBuddy>>, ApiError> {
+    let repo = BuddyBackupsRepo::init();
+
+    Ok(Json(repo.buddies(db.sqlite_pool()).await?))
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+struct BackupBuddyData {
+    node_id: String,
+}
+
+#[utoipa::path(
+    request_body = BackupBuddyData,
+    responses(
+        (status = 200, description = "Backup buddies after adding", body = Vec<BackupBuddy>),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[post("/backup_buddies", format = "json", data = "<data>")]
+async fn add_backup_buddy(db: &State<MainDb>, data: Json<BackupBuddyData>) -> Result<Json<Vec<BackupBuddy>>, ApiError> {
+    let repo = BuddyBackupsRepo::init();
+
+    repo.add_buddy(db.sqlite_pool(), &data.node_id)
+        .await?;
+    Ok(Json(repo.buddies(db.sqlite_pool()).await?))
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Backup buddies after removing", body = Vec<BackupBuddy>),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[delete("/backup_buddies/<node_id>")]
+async fn remove_backup_buddy(db: &State<MainDb>, node_id: &str) -> Result<Json<Vec<BackupBuddy>>, ApiError> {
+    let repo = BuddyBackupsRepo::init();
+
+    repo.remove_buddy(db.sqlite_pool(), node_id)
+        .await?;
+    Ok(Json(repo.buddies(db.sqlite_pool()).await?))
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+struct PushBackupData {
+    /// Falls back to `LORES_BACKUP_PASSPHRASE`.
+    passphrase: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct PushedBackup {
+    pub blob_hash: String,
+}
+
+/// Pushes a backup to the buddies now, instead of waiting for the schedule.
+#[utoipa::path(
+    request_body = PushBackupData,
+    responses(
+        (status = 200, description = "The pushed backup", body = PushedBackup),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[post("/backup_buddies/push", format = "json", data = "<data>")]
+async fn push_backup_now(
+    db: &State<MainDb>,
+    data: Json<PushBackupData>,
+    panda_container: &State<P2PandaContainer>,
+) -> Result<Json<PushedBackup>, ApiError> {
+    let passphrase = data
+        .passphrase
+        .clone()
+        .or_else(backup_passphrase_from_env)
+        .ok_or(ApiError::validation_failed("No backup passphrase given"))?;
+
+    let blob_hash = push_backup(db.sqlite_pool(), panda_container, &passphrase).await?;
+
+    Ok(Json(PushedBackup { blob_hash }))
+}
+
+/// Backups this node keeps for its buddies.
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Backups held for buddies", body = Vec<HeldBackup>),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[get("/held_backups", format = "json")]
+async fn held_backups(db: &State<MainDb>) -> Result<Json<Vec<HeldBackup>>, ApiError> {
+    let repo = BuddyBackupsRepo::init();
+
+    Ok(Json(repo.held_backups(db.sqlite_pool()).await?))
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+struct RequestBackupData {
+    owner_node_id: String,
+}
+
+/// Asks the region for the latest backup of a lost node. Buddies holding one answer with an offer,
+/// see `/buddy_restore/offers`.
+#[utoipa::path(
+    request_body = RequestBackupData,
+    responses(
+        (status = 200, description = "Requested", body = String),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[post("/buddy_restore/request", format = "json", data = "<data>")]
+async fn request_buddy_backup(data: Json<RequestBackupData>, panda_container: &State<P2PandaContainer>) -> Result<Json<String>, ApiError> {
+    panda_container
+        .request_backup(data.owner_node_id.clone())
+        .await?;
+
+    Ok(Json("Requested".to_string()))
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Offers received, newest backup first", body = Vec<BackupOffer>),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[get("/buddy_restore/offers", format = "json")]
+async fn buddy_backup_offers(db: &State<MainDb>) -> Result<Json<Vec<BackupOffer>>, ApiError> {
+    let repo = BuddyBackupsRepo::init();
+
+    Ok(Json(repo.offers(db.sqlite_pool()).await?))
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+struct BuddyRestoreData {
+    blob_hash: String,
+    passphrase: String,
+}
+
+#[utoipa::path(
+    request_body = BuddyRestoreData,
+    responses(
+        (status = 200, description = "Restored", body = RestoreDetails),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[post("/buddy_restore", format = "json", data = "<data>")]
+async fn buddy_restore(
+    db: &State<MainDb>,
+    data: Json<BuddyRestoreData>,
+    panda_container: &State<P2PandaContainer>,
+    regions: &State<Regions>,
+) -> Result<Json<RestoreDetails>, ApiError> {
+    let manifest = restore_from_buddy(db, panda_container, data.blob_hash.clone(), &data.passphrase).await?;
+    regions.reload(db.sqlite_pool()).await;
+
+    Ok(Json(RestoreDetails {
+        manifest,
+        key_status: panda_container.get_params().await.key_status,
+    }))
+}
+
+/// The settings the node was started with, with the webhook URL redacted. They are read-only,
+/// change them in the config file or environment and restart.
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Settings in effect", body = Settings),
+    )
+)]
+#[get("/settings", format = "json")]
+fn settings(settings: &State<Settings>) -> Json<Settings> {
+    Json(settings.redacted())
+}
+
+/// Recent log records, oldest first. The node keeps the last 2000.
+#[utoipa::path(
+    params(LogQuery),
+    responses(
+        (status = 200, description = "Matching log records", body = Vec<LogRecord>),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[get("/logs?<query..>", format = "json")]
+fn logs(query: LogQuery) -> Result<Json<Vec<LogRecord>>, ApiError> {
+    let min_level = query
+        .min_level()
+        .map_err(ApiError::validation_failed)?;
+
+    Ok(Json(log_buffer().records(&query, min_level)))
+}
+
+/// Log records as they are logged, as server-sent events with a `LogRecord` each. `limit` is
+/// ignored.
+#[utoipa::path(
+    params(LogQuery),
+    responses(
+        (status = 200, description = "Stream of matching log records", content_type = "text/event-stream", body = LogRecord),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[get("/logs/stream?<query..>")]
+fn logs_stream(query: LogQuery, mut end: Shutdown) -> Result<EventStream![], ApiError> {
+    let min_level = query
+        .min_level()
+        .map_err(ApiError::validation_failed)?;
+    let mut records = log_buffer().subscribe();
+
+    Ok(EventStream! {
+        loop {
+            let record = select! {
+                record = records.recv() => match record {
+                    Ok(record) => record,
+                    Err(RecvError::Closed) => break,
+                    // the ids show the client what it missed
+                    Err(RecvError::Lagged(_)) => continue,
+                },
+                _ = &mut end => break,
+            };
+
+            if query.matches(min_level, &record) {
+                yield Event::json(&record);
+            }
+        }
+    })
+}
+
+/// A JSON file with what is needed to look into a problem with the node: settings, status, peers,
+/// recent sync failures and warnings, migrations and versions. Holds no keys or passphrases.
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Diagnostics bundle", body = Diagnostics),
+    )
+)]
+#[get("/diagnostics")]
+async fn diagnostics(db: &State<MainDb>, panda_container: &State<P2PandaContainer>, settings: &State<Settings>) -> DiagnosticsFile {
+    let diagnostics = collect(db.sqlite_pool(), panda_container, settings).await;
+
+    let file_name = format!("lores-node-diagnostics-{}.json", diagnostics.generated_at);
+
+    DiagnosticsFile {
+        data: Json(diagnostics),
+        disposition: Header::new("Content-Disposition", format!("attachment; filename=\"{}\"", file_name)),
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    backup,
+    restore,
+    backup_buddies,
+    add_backup_buddy,
+    remove_backup_buddy,
+    push_backup_now,
+    held_backups,
+    request_buddy_backup,
+    buddy_backup_offers,
+    buddy_restore,
+    settings,
+    logs,
+    logs_stream,
+    diagnostics
+))]
+pub struct ApiDoc;
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        backup,
+        restore,
+        backup_buddies,
+        add_backup_buddy,
+        remove_backup_buddy,
+        push_backup_now,
+        held_backups,
+        request_buddy_backup,
+        buddy_backup_offers,
+        buddy_restore,
+        settings,
+        logs,
+        logs_stream,
+        diagnostics
+    ]
+}
@@ -0,0 +1,151 @@
+use rocket::serde::json::Json;
+use rocket::serde::Deserialize;
+use rocket::{Route, State};
+use utoipa::{OpenApi, ToSchema};
+use uuid::Uuid;
+
+use crate::infra::api_error::ApiError;
+use crate::infra::db::MainDb;
+use crate::panda_comms::container::P2PandaContainer;
+use crate::panda_comms::lores_events::{validate_alert_rule, AlertCondition, AlertRuleSaved};
+use crate::repos::alerts::AlertsRepo;
+use crate::repos::entities::{Alert, AlertRule, AlertState};
+
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+struct AlertRuleData {
+    name: String,
+    condition: AlertCondition,
+}
+
+impl AlertRuleData {
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_alert_rule(&self.name, &self.condition).map_err(ApiError::validation_failed)
+    }
+}
+
+async fn saved_rule(saved: AlertRuleSaved, panda_container: &P2PandaContainer) -> Result<AlertRule, ApiError> {
+    Ok(AlertRule {
+        id: saved.id,
+        name: saved.name,
+        condition: saved.condition,
+        author_node_id: panda_container.get_public_key().await?,
+        updated_at: saved.updated_at,
+    })
+}
+
+/// The alerting rules of the region. Every node checks them against what it knows.
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Alerting rules", body = Vec<AlertRule>),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[get("/rules", format = "json")]
+async fn rules(db: &State<MainDb>) -> Result<Json<Vec<AlertRule>>, ApiError> {
+    let repo = AlertsRepo::init();
+
+    Ok(Json(repo.rules(db.sqlite_pool()).await?))
+}
+
+/// Adds a rule to the region.
+#[utoipa::path(
+    request_body = AlertRuleData,
+    responses(
+        (status = 200, description = "The added rule", body = AlertRule),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[post("/rules", format = "json", data = "<data>")]
+async fn create_rule(data: Json<AlertRuleData>, panda_container: &State<P2PandaContainer>) -> Result<Json<AlertRule>, ApiError> {
+    data.validate()?;
+    let data = data.into_inner();
+
+    let saved = panda_container
+        .save_alert_rule(Uuid::new_v4().to_string(), data.name, data.condition)
+        .await?;
+
+    Ok(Json(saved_rule(saved, panda_container).await?))
+}
+
+#[utoipa::path(
+    request_body = AlertRuleData,
+    responses(
+        (status = 200, description = "The changed rule", body = AlertRule),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[put("/rules/<id>", format = "json", data = "<data>")]
+async fn update_rule(
+    db: &State<MainDb>,
+    id: &str,
+    data: Json<AlertRuleData>,
+    panda_container: &State<P2PandaContainer>,
+) -> Result<Json<AlertRule>, ApiError> {
+    data.validate()?;
+    let rule = AlertsRepo::init()
+        .rule(db.sqlite_pool(), id)
+        .await?;
+    let data = data.into_inner();
+
+    let saved = panda_container
+        .save_alert_rule(rule.id, data.name, data.condition)
+        .await?;
+
+    Ok(Json(saved_rule(saved, panda_container).await?))
+}
+
+/// Removes a rule from the region. Its alerts are resolved on the next check.
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Deleted"),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[delete("/rules/<id>")]
+async fn delete_rule(db: &State<MainDb>, id: &str, panda_container: &State<P2PandaContainer>) -> Result<Json<()>, ApiError> {
+    let rule = AlertsRepo::init()
+        .rule(db.sqlite_pool(), id)
+        .await?;
+
+    panda_container.delete_alert_rule(rule.id).await?;
+
+    Ok(Json(()))
+}
+
+/// Alerts raised by this node, newest first.
+#[utoipa::path(
+    params(("state" = Option<AlertState>, Query, description = "Only alerts in this state")),
+    responses(
+        (status = 200, description = "Alerts", body = Vec<Alert>),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[get("/?<state>", format = "json")]
+async fn alerts(db: &State<MainDb>, state: Option<AlertState>) -> Result<Json<Vec<Alert>>, ApiError> {
+    let repo = AlertsRepo::init();
+
+    Ok(Json(repo.alerts(db.sqlite_pool(), state).await?))
+}
+
+/// Marks an open alert as being looked into. It is still resolved once its rule stops firing.
+#[utoipa::path(
+    responses(
+        (status = 200, description = "The acknowledged alert", body = Alert),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[post("/<id>/acknowledge")]
+async fn acknowledge(db: &State<MainDb>, id: &str) -> Result<Json<Alert>, ApiError> {
+    let repo = AlertsRepo::init();
+
+    Ok(Json(repo.acknowledge(db.sqlite_pool(), id).await?))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(rules, create_rule, update_rule, delete_rule, alerts, acknowledge))]
+pub struct ApiDoc;
+
+pub fn routes() -> Vec<Route> {
+    routes![rules, create_rule, update_rule, delete_rule, alerts, acknowledge]
+}
@@ -1,8 +1,9 @@
 use rocket::serde::json::Json;
 use rocket::serde::{Deserialize, Serialize};
 use rocket::Route;
+use utoipa::{OpenApi, ToSchema};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 #[serde(crate = "rocket::serde")]
 struct AppList {
     apps: Vec<String>,
@@ -11,6 +12,11 @@ struct AppList {
 // A type which either has an AppList or an error
 type AppListResult = Result<AppList, String>;
 
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Installed apps, wrapped in `Ok`", body = AppList),
+    )
+)]
 #[get("/installed", format = "json")]
 fn installed_apps() -> Json<AppListResult> {
     Json(Ok(AppList {
@@ -18,6 +24,10 @@ fn installed_apps() -> Json<AppListResult> {
     }))
 }
 
+#[derive(OpenApi)]
+#[openapi(paths(installed_apps))]
+pub struct ApiDoc;
+
 pub fn routes() -> Vec<Route> {
     routes![installed_apps]
 }
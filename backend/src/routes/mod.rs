@@ -1,4 +1,87 @@
+use rocket::Route;
+use utoipa::OpenApi;
+
+pub mod admin;
+pub mod alerts;
 pub mod apps;
+pub mod regions;
 pub mod this_node;
 pub mod this_p2panda_node;
 pub mod this_region;
+
+/// A group of API routes with their OpenAPI description, mounted under its version at `path`.
+pub struct ApiModule {
+    pub path: &'static str,
+    pub routes: Vec<Route>,
+    pub openapi: utoipa::openapi::OpenApi,
+}
+
+/// A version of the API, mounted at `/api/<name>`. A breaking change goes into a new version
+/// added next to the current one, which is then marked deprecated so clients get warned before it
+/// is removed.
+pub struct ApiVersion {
+    pub name: &'static str,
+    pub modules: Vec<ApiModule>,
+    pub deprecated: bool,
+}
+
+impl ApiVersion {
+    pub fn base(&self) -> String {
+        format!("/api/{}", self.name)
+    }
+}
+
+/// Every API version served, oldest first. The last one is the current version.
+pub fn api_versions() -> Vec<ApiVersion> {
+    vec![ApiVersion {
+        name: "v1",
+        modules: v1_modules(),
+        deprecated: false,
+    }]
+}
+
+/// The routes as they were mounted before versioning, straight under `/api`. They behave like v1
+/// and are deprecated in favour of the current version.
+pub fn unversioned_modules() -> Vec<ApiModule> {
+    v1_modules()
+}
+
+fn v1_modules() -> Vec<ApiModule> {
+    vec![
+        ApiModule {
+            path: "/this_node",
+            routes: this_node::routes(),
+            openapi: this_node::ApiDoc::openapi(),
+        },
+        ApiModule {
+            path: "/this_region",
+            routes: this_region::routes(),
+            openapi: this_region::ApiDoc::openapi(),
+        },
+        ApiModule {
+            path: "/regions",
+            routes: regions::routes(),
+            openapi: regions::ApiDoc::openapi(),
+        },
+        ApiModule {
+            path: "/this_p2panda_node",
+            routes: this_p2panda_node::routes(),
+            openapi: this_p2panda_node::ApiDoc::openapi(),
+        },
+        ApiModule {
+            path: "/apps",
+            routes: apps::routes(),
+            openapi: apps::ApiDoc::openapi(),
+        },
+        ApiModule {
+            path: "/alerts",
+            routes: alerts::routes(),
+            openapi: alerts::ApiDoc::openapi(),
+        },
+        ApiModule {
+            path: "/admin",
+            routes: admin::routes(),
+            openapi: admin::ApiDoc::openapi(),
+        },
+    ]
+}
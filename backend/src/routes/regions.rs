@@ -0,0 +1,207 @@
+use rocket::serde::json::Json;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::{Route, State};
+use utoipa::{OpenApi, ToSchema};
+
+use crate::infra::api_error::ApiError;
+use crate::infra::db::MainDb;
+use crate::infra::health_reports::region_nodes;
+use crate::panda_comms::container::ContainerState;
+use crate::panda_comms::presence::NodePresence;
+use crate::panda_comms::regions::{RegionMembership, Regions};
+use crate::repos::entities::{Notice, RegionNode};
+use crate::repos::notices::NoticesRepo;
+use crate::repos::this_p2panda_node::SimplifiedNodeAddress;
+
+use super::this_p2panda_node::BootstrapNodePeer;
+use super::this_region::{leave_region, post_notice_with, LeaveRegionData, LeftRegion, PostNoticeData};
+
+/// A region this node is a member of. Region `0` is the first one, the one `/this_region` is
+/// about.
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct RegionDetails {
+    pub id: i64,
+    pub network_name: String,
+    pub bootstrap_node_id: Option<String>,
+    pub topic_name: Option<String>,
+    pub log_id: Option<String>,
+    pub state: ContainerState,
+}
+
+impl RegionDetails {
+    async fn of(membership: RegionMembership) -> Self {
+        RegionDetails {
+            id: membership.config.id,
+            network_name: membership.config.network_name,
+            bootstrap_node_id: membership.config.bootstrap_node_id,
+            topic_name: membership.config.topic_name,
+            log_id: membership.config.log_id,
+            state: membership.container.state().await,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+struct JoinRegionData {
+    network_name: String,
+    bootstrap_peer: Option<BootstrapNodePeer>,
+    /// Gossip topic of the region, if it doesn't use the one in the settings.
+    topic_name: Option<String>,
+    /// Log of the region, if it doesn't use the one in the settings.
+    log_id: Option<String>,
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "The regions this node is a member of", body = Vec<RegionDetails>),
+    )
+)]
+#[get("/", format = "json")]
+async fn index(regions: &State<Regions>) -> Json<Vec<RegionDetails>> {
+    let mut details = vec![];
+    for membership in regions.all().await {
+        details.push(RegionDetails::of(membership).await);
+    }
+
+    Json(details)
+}
+
+/// Joins a region. The first region joined becomes region `0`, with the topic and log from the
+/// settings. Any later one runs next to it with the same identity.
+#[utoipa::path(
+    request_body = JoinRegionData,
+    responses(
+        (status = 200, description = "The joined region", body = RegionDetails),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[post("/", format = "json", data = "<data>")]
+async fn join(db: &State<MainDb>, data: Json<JoinRegionData>, regions: &State<Regions>) -> Result<Json<RegionDetails>, ApiError> {
+    let data = data.into_inner();
+    if data.network_name.trim().is_empty() {
+        return Err(ApiError::validation_failed("Network name can't be blank"));
+    }
+
+    let peer_address = data
+        .bootstrap_peer
+        .map(|peer| SimplifiedNodeAddress { node_id: peer.node_id });
+
+    let membership = if regions.all().await.is_empty() {
+        if data.topic_name.is_some() || data.log_id.is_some() {
+            return Err(ApiError::validation_failed("The first region uses the topic and log from the settings"));
+        }
+
+        regions
+            .join_first(db.sqlite_pool(), data.network_name, peer_address)
+            .await?
+    } else {
+        regions
+            .join(db.sqlite_pool(), data.network_name, peer_address, data.topic_name, data.log_id)
+            .await?
+    };
+
+    Ok(Json(RegionDetails::of(membership).await))
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "The region", body = RegionDetails),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[get("/<id>", format = "json")]
+async fn show(id: i64, regions: &State<Regions>) -> Result<Json<RegionDetails>, ApiError> {
+    let membership = regions.get(id).await?;
+
+    Ok(Json(RegionDetails::of(membership).await))
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Nodes in the region, with their latest health report", body = Vec<RegionNode>),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[get("/<id>/nodes", format = "json")]
+async fn nodes(id: i64, regions: &State<Regions>) -> Result<Json<Vec<RegionNode>>, ApiError> {
+    let membership = regions.get(id).await?;
+    let mut connection = membership
+        .pool
+        .acquire()
+        .await
+        .map_err(ApiError::internal)?;
+
+    Ok(Json(region_nodes(&mut connection).await?))
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Which nodes in the region are online", body = Vec<NodePresence>),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[get("/<id>/presence", format = "json")]
+async fn presence(id: i64, regions: &State<Regions>) -> Result<Json<Vec<NodePresence>>, ApiError> {
+    let membership = regions.get(id).await?;
+
+    Ok(Json(membership.container.presence().all().await))
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Notices in the region that have not expired", body = Vec<Notice>),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[get("/<id>/notices", format = "json")]
+async fn notices(id: i64, regions: &State<Regions>) -> Result<Json<Vec<Notice>>, ApiError> {
+    let membership = regions.get(id).await?;
+    let mut connection = membership
+        .pool
+        .acquire()
+        .await
+        .map_err(ApiError::internal)?;
+
+    Ok(Json(
+        NoticesRepo::init()
+            .current(&mut connection)
+            .await?,
+    ))
+}
+
+#[utoipa::path(
+    request_body = PostNoticeData,
+    responses(
+        (status = 200, description = "The posted notice", body = Notice),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[post("/<id>/notices", format = "json", data = "<data>")]
+async fn post_notice(id: i64, data: Json<PostNoticeData>, regions: &State<Regions>) -> Result<Json<Notice>, ApiError> {
+    let membership = regions.get(id).await?;
+
+    Ok(Json(post_notice_with(&membership.container, data.into_inner()).await?))
+}
+
+/// Leaves the region. Leaving region `0` leaves every region, like `/this_region/leave`.
+#[utoipa::path(
+    request_body = LeaveRegionData,
+    responses(
+        (status = 200, description = "Left the region", body = LeftRegion),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[post("/<id>/leave", format = "json", data = "<data>")]
+async fn leave(id: i64, db: &State<MainDb>, data: Json<LeaveRegionData>, regions: &State<Regions>) -> Result<Json<LeftRegion>, ApiError> {
+    Ok(Json(leave_region(db, regions, id, data.keep_backup).await?))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(index, join, show, nodes, presence, notices, post_notice, leave))]
+pub struct ApiDoc;
+
+pub fn routes() -> Vec<Route> {
+    routes![index, join, show, nodes, presence, notices, post_notice, leave]
+}
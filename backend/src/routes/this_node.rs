@@ -1,29 +1,50 @@
 use rocket::serde::json::Json;
-use rocket::serde::Deserialize;
+use rocket::serde::{Deserialize, Serialize};
 use rocket::Route;
 use rocket::{post, State};
 use rocket_db_pools::Connection;
+use tracing::warn;
+use utoipa::{OpenApi, ToSchema};
 
+use crate::infra::api_error::ApiError;
 use crate::infra::db::MainDb;
-use crate::panda_comms::container::P2PandaContainer;
-use crate::repos::entities::Node;
-use crate::repos::this_node::{ThisNodeRepo, ThisNodeRepoError};
+use crate::infra::settings::Settings;
+use crate::infra::system_monitor::{SystemThresholds, SYSTEM_HISTORY};
+use crate::infra::time::unix_now;
+use crate::panda_comms::container::{build_public_key_from_hex, P2PandaContainer};
+use crate::panda_comms::regions::Regions;
+use crate::repos::direct_messages::DirectMessagesRepo;
+use crate::repos::entities::{DirectMessage, Node, SystemAlert, SystemSample};
+use crate::repos::system::SystemRepo;
+use crate::repos::this_node::ThisNodeRepo;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 #[serde(crate = "rocket::serde")]
 struct CreateNodeDetails {
     name: String,
 }
 
+#[utoipa::path(
+    request_body = CreateNodeDetails,
+    responses(
+        (status = 200, description = "The announced node", body = Node),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
 #[post("/create", data = "<data>")]
-async fn create(data: Json<CreateNodeDetails>, panda_container: &State<P2PandaContainer>) -> Result<Json<Node>, ThisNodeRepoError> {
+async fn create(data: Json<CreateNodeDetails>, panda_container: &State<P2PandaContainer>, regions: &State<Regions>) -> Result<Json<Node>, ApiError> {
     panda_container
         .announce_node(data.name.clone())
-        .await
-        .map_err(|e| {
-            println!("got error: {}", e);
-            ThisNodeRepoError::InternalServerError(e.to_string())
-        })?;
+        .await?;
+    for membership in regions.others().await {
+        if let Err(e) = membership
+            .container
+            .announce_node(data.name.clone())
+            .await
+        {
+            warn!(region_id = membership.config.id, error = %e, "Failed to announce node in region");
+        }
+    }
 
     return Ok(Json(Node {
         id: "1".to_string(),
@@ -31,13 +52,126 @@ async fn create(data: Json<CreateNodeDetails>, panda_container: &State<P2PandaCo
     }));
 }
 
+#[utoipa::path(
+    responses(
+        (status = 200, description = "This node", body = Node),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
 #[get("/", format = "json")]
-async fn show(mut db: Connection<MainDb>) -> Result<Json<Node>, ThisNodeRepoError> {
+async fn show(mut db: Connection<MainDb>) -> Result<Json<Node>, ApiError> {
     let repo = ThisNodeRepo::init();
 
-    repo.find(&mut db).await.map(|node| Json(node))
+    Ok(Json(repo.find(&mut db).await?))
 }
 
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Direct messages received by this node", body = Vec<DirectMessage>),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[get("/inbox", format = "json")]
+async fn inbox(mut db: Connection<MainDb>) -> Result<Json<Vec<DirectMessage>>, ApiError> {
+    let repo = DirectMessagesRepo::init();
+
+    Ok(Json(repo.inbox(&mut db).await?))
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Marked as read"),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[post("/inbox/<id>/read")]
+async fn mark_read(mut db: Connection<MainDb>, id: &str) -> Result<Json<()>, ApiError> {
+    let repo = DirectMessagesRepo::init();
+
+    Ok(Json(repo.mark_read(&mut db, id).await?))
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+struct SendDirectMessageData {
+    recipient_node_id: String,
+    body: String,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+struct SentDirectMessage {
+    id: String,
+    recipient_node_id: String,
+    sent_at: i64,
+}
+
+#[utoipa::path(
+    request_body = SendDirectMessageData,
+    responses(
+        (status = 200, description = "The sent message", body = SentDirectMessage),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[post("/messages", format = "json", data = "<data>")]
+async fn send_message(data: Json<SendDirectMessageData>, panda_container: &State<P2PandaContainer>) -> Result<Json<SentDirectMessage>, ApiError> {
+    if data.body.trim().is_empty() {
+        return Err(ApiError::validation_failed("Message can't be blank"));
+    }
+    if build_public_key_from_hex(data.recipient_node_id.clone()).is_none() {
+        return Err(ApiError::validation_failed("Recipient is not a valid node id"));
+    }
+
+    let data = data.into_inner();
+    let sent = panda_container
+        .send_direct_message(data.recipient_node_id, data.body)
+        .await?;
+
+    Ok(Json(SentDirectMessage {
+        id: sent.id,
+        recipient_node_id: sent.recipient_node_id,
+        sent_at: sent.sent_at,
+    }))
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+struct SystemStatus {
+    /// Missing until the first sample is taken, shortly after start.
+    latest: Option<SystemSample>,
+    /// Samples of the last day, oldest first.
+    history: Vec<SystemSample>,
+    /// Open alerts, and the ones resolved in the last day, newest first.
+    alerts: Vec<SystemAlert>,
+    thresholds: SystemThresholds,
+}
+
+/// CPU, memory, disk, temperature, uptime and network of the host the node runs on.
+#[utoipa::path(
+    responses(
+        (status = 200, description = "The host of this node", body = SystemStatus),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[get("/system", format = "json")]
+async fn system(db: &State<MainDb>, settings: &State<Settings>) -> Result<Json<SystemStatus>, ApiError> {
+    let repo = SystemRepo::init();
+    let since = unix_now() - SYSTEM_HISTORY.as_secs() as i64;
+
+    let history = repo.samples(db.sqlite_pool(), since).await?;
+
+    Ok(Json(SystemStatus {
+        latest: history.last().cloned(),
+        alerts: repo.alerts(db.sqlite_pool(), since).await?,
+        thresholds: SystemThresholds::from_settings(settings),
+        history,
+    }))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(create, show, inbox, mark_read, send_message, system))]
+pub struct ApiDoc;
+
 pub fn routes() -> Vec<Route> {
-    routes![create, show]
+    routes![create, show, inbox, mark_read, send_message, system]
 }
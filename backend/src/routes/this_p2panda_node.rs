@@ -1,73 +1,359 @@
 use iroh::NodeAddr;
+use p2panda_core::PrivateKey;
 use p2panda_net::NodeAddress;
 use rocket::serde::json::Json;
 use rocket::serde::{Deserialize, Serialize};
 use rocket::{Route, State};
+use tracing::warn;
+use utoipa::{OpenApi, ToSchema};
 
-use crate::panda_comms::container::P2PandaContainer;
-use crate::repos::this_p2panda_node::ThisP2PandaNodeRepoError;
+use crate::infra::api_error::ApiError;
+use crate::infra::db::MainDb;
+use crate::panda_comms::container::{ContainerState, P2PandaContainer};
+use crate::panda_comms::identity::{IdentityBackup, PrivateKeyStatus};
+use crate::panda_comms::regions::Regions;
+use crate::repos::this_p2panda_node::ThisP2PandaNodeRepo;
 
-#[derive(sqlx::FromRow, Serialize, Deserialize)]
+const MIN_PASSPHRASE_LEN: usize = 8;
+
+#[derive(sqlx::FromRow, Serialize, Deserialize, ToSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct NodeDetails {
     pub panda_node_id: String,
+    #[schema(value_type = Object)]
     pub iroh_node_addr: NodeAddr,
+    #[schema(value_type = Vec<Object>)]
     pub peers: Vec<NodeAddress>,
 }
 
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Network details of this node", body = NodeDetails),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
 #[get("/", format = "json")]
-async fn show(panda_container: &State<P2PandaContainer>) -> Result<Json<NodeDetails>, ThisP2PandaNodeRepoError> {
-    let public_key: String = panda_container
-        .get_public_key()
-        .await
-        .unwrap()
-        .to_string();
-    println!("public key: {}", public_key);
+async fn show(panda_container: &State<P2PandaContainer>) -> Result<Json<NodeDetails>, ApiError> {
+    check_unlocked(panda_container).await?;
 
-    let node_addr = panda_container.get_node_addr().await;
-    println!("node addr: {:?}", node_addr);
+    let public_key: String = panda_container.get_public_key().await?;
+    let node_addr = panda_container.get_node_addr().await?;
 
-    let mut peers = panda_container.known_peers().await;
-
-    if peers.is_err() {
-        println!("Failed to get known peers {:?}", peers);
-        peers = Ok(vec![]);
-    } else {
-        println!("peers: {:?}", peers);
-    }
+    let peers = match panda_container.known_peers().await {
+        Ok(peers) => peers,
+        Err(e) => {
+            warn!(error = %e, "Failed to get known peers");
+            vec![]
+        }
+    };
 
     let node_details = NodeDetails {
         panda_node_id: public_key,
         iroh_node_addr: node_addr,
-        peers: peers.unwrap(),
+        peers,
     };
 
     Ok(Json(node_details))
 }
 
+/// Whether the network is running, and why not if it failed.
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Where the network is in its lifecycle", body = ContainerState),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[get("/status", format = "json")]
+async fn status(panda_container: &State<P2PandaContainer>) -> Json<ContainerState> {
+    Json(panda_container.state().await)
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Restarted", body = String),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
 #[post("/restart", format = "json")]
-async fn restart(panda_container: &State<P2PandaContainer>) -> Result<Json<String>, ThisP2PandaNodeRepoError> {
-    panda_container.restart().await.map_err(|e| {
-        println!("got error: {}", e);
-        ThisP2PandaNodeRepoError::InternalServerError(e.to_string())
-    })?;
+async fn restart(panda_container: &State<P2PandaContainer>) -> Result<Json<String>, ApiError> {
+    panda_container.restart().await?;
 
     Ok(Json("Restarted".to_string()))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct BootstrapNodePeer {
     pub node_id: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct BootstrapNodeData {
     pub network_name: String,
     pub bootstrap_peer: Option<BootstrapNodePeer>,
 }
 
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct IdentityDetails {
+    pub node_id: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+struct PassphraseData {
+    passphrase: String,
+}
+
+#[utoipa::path(
+    request_body = PassphraseData,
+    responses(
+        (status = 200, description = "The private key, encrypted with the passphrase", body = IdentityBackup),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[post("/identity/export", format = "json", data = "<data>")]
+async fn export_identity(data: Json<PassphraseData>, panda_container: &State<P2PandaContainer>) -> Result<Json<IdentityBackup>, ApiError> {
+    check_passphrase(&data.passphrase)?;
+    check_unlocked(panda_container).await?;
+
+    let private_key = panda_container
+        .get_params()
+        .await
+        .private_key
+        .ok_or(ApiError::conflict("No private key yet"))?;
+
+    IdentityBackup::create(&private_key, &data.passphrase)
+        .map(Json)
+        .map_err(ApiError::internal)
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+struct ImportIdentityData {
+    backup: IdentityBackup,
+    passphrase: String,
+}
+
+/// Takes over the identity of a node from an exported backup. Only allowed before joining a
+/// region, so a running node can't have its identity swapped by accident.
+#[utoipa::path(
+    request_body = ImportIdentityData,
+    responses(
+        (status = 200, description = "The imported identity", body = IdentityDetails),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[post("/identity/import", format = "json", data = "<data>")]
+async fn import_identity(
+    db: &State<MainDb>,
+    data: Json<ImportIdentityData>,
+    panda_container: &State<P2PandaContainer>,
+) -> Result<Json<IdentityDetails>, ApiError> {
+    let repo = ThisP2PandaNodeRepo::init();
+
+    if repo.get_network_name(db).await?.is_some() {
+        return Err(ApiError::conflict("This node has already joined a region"));
+    }
+
+    let private_key = data
+        .backup
+        .restore(&data.passphrase)
+        .map_err(|e| ApiError::validation_failed(e.to_string()))?;
+
+    check_unlocked(panda_container).await?;
+    let key_passphrase = panda_container.get_params().await.key_passphrase;
+
+    repo.set_private_key(db, &private_key, key_passphrase.as_deref())
+        .await?;
+    panda_container
+        .set_private_key(private_key.clone())
+        .await;
+
+    Ok(Json(IdentityDetails {
+        node_id: private_key.public_key().to_hex(),
+    }))
+}
+
+/// Moves this node to a freshly generated key, e.g. after the old one leaked. Every region it is a
+/// member of is told with the old key before it is thrown away.
+#[utoipa::path(
+    responses(
+        (status = 200, description = "The new identity", body = IdentityDetails),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[post("/identity/rotate", format = "json")]
+async fn rotate_identity(
+    db: &State<MainDb>,
+    panda_container: &State<P2PandaContainer>,
+    regions: &State<Regions>,
+) -> Result<Json<IdentityDetails>, ApiError> {
+    check_unlocked(panda_container).await?;
+
+    let repo = ThisP2PandaNodeRepo::init();
+    let new_private_key = PrivateKey::new();
+    let key_passphrase = panda_container.get_params().await.key_passphrase;
+
+    panda_container
+        .announce_key_rotation(&new_private_key)
+        .await?;
+    for membership in regions.others().await {
+        if let Err(e) = membership
+            .container
+            .announce_key_rotation(&new_private_key)
+            .await
+        {
+            warn!(region_id = membership.config.id, error = %e, "Failed to announce key rotation in region");
+        }
+    }
+
+    repo.set_private_key(db, &new_private_key, key_passphrase.as_deref())
+        .await?;
+    panda_container
+        .set_private_key(new_private_key.clone())
+        .await;
+    panda_container.restart().await?;
+    regions.restart_others().await;
+
+    Ok(Json(IdentityDetails {
+        node_id: new_private_key.public_key().to_hex(),
+    }))
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct KeyDetails {
+    pub status: PrivateKeyStatus,
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Whether the private key can be used", body = KeyDetails),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[get("/key", format = "json")]
+async fn show_key(panda_container: &State<P2PandaContainer>) -> Json<KeyDetails> {
+    Json(KeyDetails {
+        status: panda_container.get_params().await.key_status,
+    })
+}
+
+/// Unlocks a key that is encrypted at rest when no passphrase was given at startup, then starts
+/// the network of every region this node is a member of.
+#[utoipa::path(
+    request_body = PassphraseData,
+    responses(
+        (status = 200, description = "Unlocked", body = KeyDetails),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[post("/key/unlock", format = "json", data = "<data>")]
+async fn unlock_key(
+    db: &State<MainDb>,
+    data: Json<PassphraseData>,
+    panda_container: &State<P2PandaContainer>,
+    regions: &State<Regions>,
+) -> Result<Json<KeyDetails>, ApiError> {
+    if panda_container.get_params().await.key_status != PrivateKeyStatus::Locked {
+        return Err(ApiError::conflict("Private key is not locked"));
+    }
+
+    let repo = ThisP2PandaNodeRepo::init();
+    let (private_key, status) = repo
+        .load_private_key(db, Some(&data.passphrase))
+        .await?;
+
+    panda_container
+        .set_loaded_private_key(private_key, status, Some(data.passphrase.clone()))
+        .await;
+    panda_container.start().await?;
+    regions.restart_others().await;
+
+    Ok(Json(KeyDetails { status }))
+}
+
+/// Encrypts the key at rest with a new passphrase. From the next start on the node stays locked
+/// unless the passphrase is given through the environment or `/key/unlock`.
+#[utoipa::path(
+    request_body = PassphraseData,
+    responses(
+        (status = 200, description = "Encrypted", body = KeyDetails),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[post("/key/encrypt", format = "json", data = "<data>")]
+async fn encrypt_key(
+    db: &State<MainDb>,
+    data: Json<PassphraseData>,
+    panda_container: &State<P2PandaContainer>,
+) -> Result<Json<KeyDetails>, ApiError> {
+    check_passphrase(&data.passphrase)?;
+    check_unlocked(panda_container).await?;
+
+    let private_key = panda_container
+        .get_params()
+        .await
+        .private_key
+        .ok_or(ApiError::conflict("No private key yet"))?;
+
+    let repo = ThisP2PandaNodeRepo::init();
+    repo.set_private_key(db, &private_key, Some(&data.passphrase))
+        .await?;
+
+    let status = PrivateKeyStatus::Unlocked;
+    panda_container
+        .set_loaded_private_key(Some(private_key), status, Some(data.passphrase.clone()))
+        .await;
+
+    Ok(Json(KeyDetails { status }))
+}
+
+async fn check_unlocked(panda_container: &P2PandaContainer) -> Result<(), ApiError> {
+    if panda_container.get_params().await.key_status == PrivateKeyStatus::Locked {
+        return Err(ApiError::key_locked());
+    }
+
+    Ok(())
+}
+
+fn check_passphrase(passphrase: &str) -> Result<(), ApiError> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(ApiError::validation_failed(format!(
+            "Passphrase must be at least {} characters",
+            MIN_PASSPHRASE_LEN
+        )));
+    }
+
+    Ok(())
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    show,
+    status,
+    restart,
+    export_identity,
+    import_identity,
+    rotate_identity,
+    show_key,
+    unlock_key,
+    encrypt_key
+))]
+pub struct ApiDoc;
+
 pub fn routes() -> Vec<Route> {
-    routes![show, restart]
+    routes![
+        show,
+        status,
+        restart,
+        export_identity,
+        import_identity,
+        rotate_identity,
+        show_key,
+        unlock_key,
+        encrypt_key
+    ]
 }
@@ -1,89 +1,186 @@
-use p2panda_core::PublicKey;
 use rocket::serde::json::Json;
+use rocket::serde::{Deserialize, Serialize};
 use rocket::{Route, State};
 use rocket_db_pools::Connection;
+use utoipa::{OpenApi, ToSchema};
 
+use crate::infra::api_error::ApiError;
 use crate::infra::db::MainDb;
-use crate::panda_comms::container::{build_public_key_from_hex, P2PandaContainer};
-use crate::repos::entities::{Node, Region};
-use crate::repos::this_p2panda_node::{SimplifiedNodeAddress, ThisP2PandaNodeRepo, ThisP2PandaNodeRepoError};
+use crate::infra::health_reports::region_nodes;
+use crate::panda_comms::container::P2PandaContainer;
+use crate::panda_comms::lores_events::NoticePriority;
+use crate::panda_comms::presence::NodePresence;
+use crate::panda_comms::regions::Regions;
+use crate::repos::entities::{Notice, Region, RegionNode};
+use crate::repos::helpers::NETWORK_CONFIG_ID;
+use crate::repos::notices::NoticesRepo;
+use crate::repos::this_p2panda_node::{SimplifiedNodeAddress, ThisP2PandaNodeRepo};
 
 use super::this_p2panda_node::BootstrapNodeData;
 
+#[utoipa::path(
+    responses(
+        (status = 200, description = "The region this node joined, if any", body = Option<Region>),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
 #[get("/", format = "json")]
-async fn show(mut db: Connection<MainDb>) -> Result<Json<Option<Region>>, ThisP2PandaNodeRepoError> {
+async fn show(mut db: Connection<MainDb>) -> Result<Json<Option<Region>>, ApiError> {
     let repo = ThisP2PandaNodeRepo::init();
 
-    repo.get_network_name_conn(&mut db)
-        .await
-        .map(|network_id| match network_id {
-            Some(network_id) => {
-                println!("got network id {}", network_id);
-                Json(Some(Region { network_id }))
-            }
-            None => {
-                println!("no network id");
-                Json(None)
-            }
-        })
+    let network_id = repo.get_network_name_conn(&mut db).await?;
+
+    match network_id {
+        Some(network_id) => Ok(Json(Some(Region { network_id }))),
+        None => Ok(Json(None)),
+    }
 }
 
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Nodes in the region, with their latest health report", body = Vec<RegionNode>),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
 #[get("/nodes", format = "json")]
-async fn nodes() -> Result<Json<Vec<Node>>, ThisP2PandaNodeRepoError> {
-    // create dummy data
-    let nodes = vec![
-        Node {
-            id: "1".to_string(),
-            name: "Node 1".to_string(),
-        },
-        Node {
-            id: "2".to_string(),
-            name: "Node 2".to_string(),
-        },
-    ];
-
-    Ok(Json(nodes))
+async fn nodes(mut db: Connection<MainDb>) -> Result<Json<Vec<RegionNode>>, ApiError> {
+    Ok(Json(region_nodes(&mut db).await?))
 }
 
-#[post("/bootstrap", format = "json", data = "<data>")]
-async fn bootstrap(
-    mut db: Connection<MainDb>,
-    data: Json<BootstrapNodeData>,
-    panda_container: &State<P2PandaContainer>,
-) -> Result<Json<()>, ThisP2PandaNodeRepoError> {
-    let repo = ThisP2PandaNodeRepo::init();
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Which nodes in the region are online", body = Vec<NodePresence>),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[get("/presence", format = "json")]
+async fn presence(panda_container: &State<P2PandaContainer>) -> Json<Vec<NodePresence>> {
+    Json(panda_container.presence().all().await)
+}
 
-    let bootstrap_peer = &data.bootstrap_peer;
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Notices that have not expired", body = Vec<Notice>),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[get("/notices", format = "json")]
+async fn notices(mut db: Connection<MainDb>) -> Result<Json<Vec<Notice>>, ApiError> {
+    let repo = NoticesRepo::init();
 
-    let peer_address: Option<SimplifiedNodeAddress> = bootstrap_peer
-        .as_ref()
-        .map(|peer| SimplifiedNodeAddress {
-            node_id: peer.node_id.clone(),
-        });
+    Ok(Json(repo.current(&mut db).await?))
+}
 
-    repo.set_network_config(&mut db, data.network_name.clone(), peer_address.clone())
-        .await?;
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct PostNoticeData {
+    title: String,
+    body: String,
+    #[serde(default)]
+    priority: NoticePriority,
+    expires_at: Option<i64>,
+}
 
-    panda_container
-        .set_network_name(data.network_name.clone())
-        .await;
-
-    let bootstrap_node_id: Option<PublicKey> = match peer_address.clone() {
-        Some(bootstrap) => build_public_key_from_hex(bootstrap.node_id.clone()),
-        None => None,
-    };
-    panda_container
-        .set_bootstrap_node_id(bootstrap_node_id)
-        .await;
-
-    // start the container
-    if let Err(e) = panda_container.start().await {
-        println!("Failed to start P2PandaContainer: {:?}", e);
+#[utoipa::path(
+    request_body = PostNoticeData,
+    responses(
+        (status = 200, description = "The posted notice", body = Notice),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[post("/notices", format = "json", data = "<data>")]
+async fn post_notice(data: Json<PostNoticeData>, panda_container: &State<P2PandaContainer>) -> Result<Json<Notice>, ApiError> {
+    Ok(Json(post_notice_with(panda_container, data.into_inner()).await?))
+}
+
+/// Posts a notice to the region `panda_container` runs in.
+pub(crate) async fn post_notice_with(panda_container: &P2PandaContainer, data: PostNoticeData) -> Result<Notice, ApiError> {
+    if data.title.trim().is_empty() {
+        return Err(ApiError::validation_failed("Title can't be blank"));
     }
 
+    let author_node_id = panda_container.get_public_key().await?;
+
+    let posted = panda_container
+        .post_notice(data.title, data.body, data.priority, data.expires_at)
+        .await?;
+
+    Ok(Notice {
+        id: posted.id,
+        author_node_id,
+        title: posted.title,
+        body: posted.body,
+        priority: posted.priority.as_str().to_string(),
+        posted_at: posted.posted_at,
+        expires_at: posted.expires_at,
+    })
+}
+
+#[utoipa::path(
+    request_body = BootstrapNodeData,
+    responses(
+        (status = 200, description = "Region config saved and network (re)started"),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[post("/bootstrap", format = "json", data = "<data>")]
+async fn bootstrap(db: &State<MainDb>, data: Json<BootstrapNodeData>, regions: &State<Regions>) -> Result<Json<()>, ApiError> {
+    let data = data.into_inner();
+
+    let peer_address: Option<SimplifiedNodeAddress> = data
+        .bootstrap_peer
+        .map(|peer| SimplifiedNodeAddress { node_id: peer.node_id });
+
+    regions
+        .join_first(db.sqlite_pool(), data.network_name, peer_address)
+        .await?;
+
     Ok(Json(()))
 }
 
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct LeaveRegionData {
+    /// Save a backup of the node next to its database before leaving.
+    #[serde(default)]
+    pub keep_backup: bool,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct LeftRegion {
+    /// Where the backup was saved, if one was asked for.
+    pub backup_path: Option<String>,
+}
+
+/// Leaves the region, and every other region this node is a member of, then forgets all they
+/// synced. The node keeps its key and is back to waiting for a region to join.
+#[utoipa::path(
+    request_body = LeaveRegionData,
+    responses(
+        (status = 200, description = "Left the region", body = LeftRegion),
+        (status = "default", description = "Error", body = ApiError),
+    )
+)]
+#[post("/leave", format = "json", data = "<data>")]
+async fn leave(db: &State<MainDb>, data: Json<LeaveRegionData>, regions: &State<Regions>) -> Result<Json<LeftRegion>, ApiError> {
+    Ok(Json(leave_region(db, regions, NETWORK_CONFIG_ID as i64, data.keep_backup).await?))
+}
+
+pub(crate) async fn leave_region(db: &MainDb, regions: &Regions, id: i64, keep_backup: bool) -> Result<LeftRegion, ApiError> {
+    let backup_path = regions
+        .leave(db.sqlite_pool(), id, keep_backup)
+        .await?;
+
+    Ok(LeftRegion {
+        backup_path: backup_path.map(|path| path.display().to_string()),
+    })
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(show, nodes, presence, notices, post_notice, bootstrap, leave))]
+pub struct ApiDoc;
+
 pub fn routes() -> Vec<Route> {
-    routes![show, nodes, bootstrap]
+    routes![show, nodes, presence, notices, post_notice, bootstrap, leave]
 }
@@ -0,0 +1,399 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use rocket::http::Status;
+use rocket::serde::json::json;
+
+use super::harness::{idle_container, migrated_pool, TestRegion};
+use crate::events::handler_map::handle_event;
+use crate::infra::alerting::{check_rules, evaluate, Firing, RegionView};
+use crate::infra::notifications::{rfc2822_date, AlertNotification, Notifier, Notify};
+use crate::infra::settings::Settings;
+use crate::infra::time::unix_now;
+use crate::panda_comms::container::SyncFailure;
+use crate::panda_comms::lores_events::{AlertCondition, AlertRuleSaved, LoResEvent, LoResEventHeader, LoResEventPayload};
+use crate::repos::alerts::AlertsRepo;
+use crate::repos::entities::{Alert, AlertRule, Node, NodeHealth};
+use crate::repos::nodes::NodesRepo;
+
+const SYNC_TIMEOUT: Duration = Duration::from_secs(60);
+const HOUR: i64 = 60 * 60;
+
+fn rule(id: &str, condition: AlertCondition) -> AlertRule {
+    AlertRule {
+        id: id.to_string(),
+        name: id.to_string(),
+        condition,
+        author_node_id: "author".to_string(),
+        updated_at: 0,
+    }
+}
+
+fn node(id: &str, reported_at: i64, disk_used_percent: f64, open_alerts: &[&str]) -> (Node, Option<NodeHealth>) {
+    let health = NodeHealth {
+        reported_at,
+        version: "1.2.3".to_string(),
+        uptime_secs: None,
+        disk_free_bytes: None,
+        disk_used_percent: Some(disk_used_percent),
+        load_1m: None,
+        open_alerts: open_alerts
+            .iter()
+            .map(|alert| alert.to_string())
+            .collect(),
+    };
+
+    (
+        Node {
+            id: id.to_string(),
+            name: id.to_uppercase(),
+        },
+        Some(health),
+    )
+}
+
+fn fired(firings: &[Firing]) -> Vec<(&str, &str)> {
+    firings
+        .iter()
+        .map(|firing| (firing.rule_id.as_str(), firing.node_id.as_str()))
+        .collect()
+}
+
+#[test]
+fn rules_fire_for_the_nodes_matching_them() {
+    let now = 100 * HOUR;
+    let view = RegionView {
+        nodes: vec![
+            node("quiet", now - 5 * HOUR, 50.0, &[]),
+            node("full", now - 60, 95.0, &["disk_full"]),
+            node("fine", now - 5 * HOUR, 10.0, &[]),
+            (
+                Node {
+                    id: "never_reported".to_string(),
+                    name: "Never Reported".to_string(),
+                },
+                None,
+            ),
+        ],
+        // heard from by heartbeat since its last report
+        last_heartbeats: HashMap::from([("fine".to_string(), now - 60)]),
+        sync_failures: vec![
+            SyncFailure {
+                peer: "full".to_string(),
+                failed_at: now - 60,
+            },
+            SyncFailure {
+                peer: "full".to_string(),
+                failed_at: now - 120,
+            },
+            SyncFailure {
+                peer: "quiet".to_string(),
+                failed_at: now - 2 * HOUR,
+            },
+            SyncFailure {
+                peer: "quiet".to_string(),
+                failed_at: now - 60,
+            },
+        ],
+        now,
+    };
+    let rules = [
+        rule("not_seen", AlertCondition::NodeNotSeen { hours: 3 }),
+        rule("disk", AlertCondition::DiskAbove { percent: 90.0 }),
+        rule("unhealthy", AlertCondition::NodeUnhealthy),
+        rule("sync", AlertCondition::SyncFailing { failures: 2 }),
+    ];
+
+    let firings = evaluate(&rules, &view);
+
+    assert_eq!(
+        fired(&firings),
+        [("not_seen", "quiet"), ("disk", "full"), ("unhealthy", "full"), ("sync", "full")]
+    );
+    assert_eq!(firings[1].message, "FULL disk is 95% full");
+    assert_eq!(firings[3].message, "Syncing with FULL failed 2 times in the last hour");
+}
+
+/// Keeps the events of the notifications instead of sending them.
+#[derive(Default)]
+struct RecordingNotifier {
+    events: Arc<Mutex<Vec<(&'static str, String)>>>,
+}
+
+impl RecordingNotifier {
+    fn take(&self) -> Vec<(&'static str, String)> {
+        std::mem::take(&mut *self.events.lock().unwrap())
+    }
+}
+
+impl Notify for RecordingNotifier {
+    fn notify(&self, notification: AlertNotification) -> impl Future<Output = ()> + Send {
+        self.events
+            .lock()
+            .unwrap()
+            .push((notification.event, notification.alert.node_id));
+        async {}
+    }
+}
+
+#[rocket::async_test]
+async fn alerts_open_and_resolve_once_per_change() {
+    let pool = migrated_pool().await;
+    let container = idle_container();
+    let notifier = RecordingNotifier::default();
+    let alerts = AlertsRepo::init();
+    let nodes = NodesRepo::init();
+
+    alerts
+        .save_rule(&pool, rule("disk", AlertCondition::DiskAbove { percent: 90.0 }))
+        .await
+        .unwrap();
+    let (pi, health) = node("pi", unix_now(), 95.0, &[]);
+    let mut health = health.unwrap();
+    nodes.upsert(&pool, pi).await.unwrap();
+    nodes
+        .record_health(&pool, "pi", health.clone())
+        .await
+        .unwrap();
+
+    check_rules(&pool, &container, &notifier)
+        .await
+        .unwrap();
+    assert_eq!(notifier.take(), [("opened", "pi".to_string())]);
+    let opened = alerts.active_alerts(&pool).await.unwrap();
+    assert_eq!(opened.len(), 1);
+    assert_eq!(opened[0].state, "open");
+
+    // still firing: neither an open nor an acknowledged alert is opened again
+    check_rules(&pool, &container, &notifier)
+        .await
+        .unwrap();
+    alerts
+        .acknowledge(&pool, &opened[0].id)
+        .await
+        .unwrap();
+    check_rules(&pool, &container, &notifier)
+        .await
+        .unwrap();
+    assert!(notifier.take().is_empty());
+    assert_eq!(alerts.active_alerts(&pool).await.unwrap().len(), 1);
+
+    health.disk_used_percent = Some(50.0);
+    health.reported_at += 1;
+    nodes
+        .record_health(&pool, "pi", health)
+        .await
+        .unwrap();
+
+    check_rules(&pool, &container, &notifier)
+        .await
+        .unwrap();
+    check_rules(&pool, &container, &notifier)
+        .await
+        .unwrap();
+    assert_eq!(notifier.take(), [("resolved", "pi".to_string())]);
+    assert!(alerts
+        .active_alerts(&pool)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[rocket::async_test]
+async fn invalid_alert_rules_from_the_region_are_ignored() {
+    let pool = migrated_pool().await;
+    let container = idle_container();
+    let saved = |id: &str, name: &str, condition: AlertCondition| {
+        LoResEvent::new(
+            LoResEventHeader {
+                author_node_id: "author".to_string(),
+            },
+            LoResEventPayload::AlertRuleSaved(AlertRuleSaved {
+                id: id.to_string(),
+                name: name.to_string(),
+                condition,
+                updated_at: 1,
+            }),
+        )
+    };
+
+    for event in [
+        saved("blank", " ", AlertCondition::NodeUnhealthy),
+        saved("long", &"x".repeat(101), AlertCondition::NodeUnhealthy),
+        saved("never", "Never seen", AlertCondition::NodeNotSeen { hours: 0 }),
+        saved("disk", "Disk", AlertCondition::DiskAbove { percent: 250.0 }),
+        saved("full", "Full disk", AlertCondition::DiskAbove { percent: 100.0 }),
+        saved("valid", "Unhealthy", AlertCondition::NodeUnhealthy),
+    ] {
+        handle_event(event, &pool, &container)
+            .await
+            .unwrap();
+    }
+
+    let rules = AlertsRepo::init().rules(&pool).await.unwrap();
+    let ids: Vec<&str> = rules
+        .iter()
+        .map(|rule| rule.id.as_str())
+        .collect();
+    assert_eq!(ids, ["full", "valid"]);
+
+    // a kind of condition this node doesn't know never makes it to the handler
+    let unknown = json!({ "AlertRuleSaved": { "id": "x", "name": "X", "condition": { "kind": "weather" }, "updated_at": 1 } });
+    assert!(rocket::serde::json::from_value::<LoResEventPayload>(unknown).is_err());
+}
+
+#[rocket::async_test]
+async fn alerts_are_emailed_through_smtp() {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("listener");
+    let port = listener.local_addr().expect("address").port();
+
+    // a mail server that accepts everything and hands back what it got
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().expect("connection");
+        let mut reader = BufReader::new(stream.try_clone().expect("stream"));
+        let mut writer = stream;
+        let mut received = vec![];
+        let mut in_data = false;
+
+        writer
+            .write_all(b"220 test ESMTP\r\n")
+            .expect("greeting");
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).expect("line") == 0 {
+                break;
+            }
+            let line = line.trim_end().to_string();
+            received.push(line.clone());
+
+            let reply: &[u8] = match line.as_str() {
+                "." if in_data => {
+                    in_data = false;
+                    b"250 queued\r\n"
+                }
+                _ if in_data => continue,
+                "DATA" => {
+                    in_data = true;
+                    b"354 go ahead\r\n"
+                }
+                "QUIT" => {
+                    writer.write_all(b"221 bye\r\n").expect("reply");
+                    break;
+                }
+                _ if line.starts_with("RCPT TO:") => b"251 will forward\r\n",
+                _ => b"250 ok\r\n",
+            };
+            writer.write_all(reply).expect("reply");
+        }
+
+        received
+    });
+
+    let settings = Settings {
+        smtp_host: "127.0.0.1".to_string(),
+        smtp_port: port,
+        alert_email_from: "node@example.org".to_string(),
+        alert_email_to: "ops@example.org, mesh@example.org".to_string(),
+        ..Default::default()
+    };
+    let notification = AlertNotification {
+        event: "opened",
+        alert: Alert {
+            id: "alert".to_string(),
+            rule_id: "rule".to_string(),
+            node_id: "node".to_string(),
+            state: "open".to_string(),
+            // rule names and messages come from other nodes
+            message: "PI-1 disk is 95% full\n.\nQUIT".to_string(),
+            opened_at: 0,
+            acknowledged_at: None,
+            resolved_at: None,
+        },
+        rule_name: "Disk filling up\r\nBcc: <everyone@example.org>".to_string(),
+        node_name: "PI-1".to_string(),
+    };
+
+    Notifier::from_settings(&settings)
+        .notify(notification)
+        .await;
+
+    let received = server.join().expect("server");
+    assert!(received.contains(&"MAIL FROM:<node@example.org>".to_string()), "{:?}", received);
+    assert!(received.contains(&"RCPT TO:<ops@example.org>".to_string()), "{:?}", received);
+    assert!(received.contains(&"RCPT TO:<mesh@example.org>".to_string()), "{:?}", received);
+    assert!(
+        received.contains(&"Subject: [LoRes opened] Disk filling up  Bcc: <everyone@example.org>: PI-1 disk is 95% full . QUIT".to_string()),
+        "{:?}",
+        received
+    );
+    assert!(
+        !received
+            .iter()
+            .any(|line| line.starts_with("Bcc:")),
+        "{:?}",
+        received
+    );
+    assert!(
+        received
+            .iter()
+            .any(|line| line.starts_with("Date: ")),
+        "{:?}",
+        received
+    );
+    assert!(
+        received
+            .iter()
+            .any(|line| line.starts_with("Message-ID: <")),
+        "{:?}",
+        received
+    );
+    // the dot line in the message is doubled rather than ending the data
+    assert!(received.contains(&"..".to_string()), "{:?}", received);
+    assert_eq!(
+        received
+            .iter()
+            .filter(|line| line.as_str() == "QUIT")
+            .count(),
+        2,
+        "{:?}",
+        received
+    );
+    assert_eq!(received.last().map(String::as_str), Some("QUIT"));
+}
+
+#[test]
+fn email_dates_are_in_rfc_2822_format() {
+    assert_eq!(rfc2822_date(0), "Thu, 1 Jan 1970 00:00:00 +0000");
+    assert_eq!(rfc2822_date(951_782_400), "Tue, 29 Feb 2000 00:00:00 +0000");
+    assert_eq!(rfc2822_date(1_700_000_000), "Tue, 14 Nov 2023 22:13:20 +0000");
+}
+
+#[rocket::async_test]
+async fn alert_rules_are_shared_with_the_region() {
+    let region = TestRegion::start("test_alert_rules_region", 2).await;
+
+    let status = region.nodes[0]
+        .post_json(
+            "/api/v1/alerts/rules",
+            json!({ "name": "Site down", "condition": { "kind": "node_not_seen", "hours": 6 } }),
+        )
+        .await;
+    assert_eq!(status, Status::Ok);
+
+    let rules = region.nodes[1]
+        .wait_for_json("/api/v1/alerts/rules", SYNC_TIMEOUT, |rules| {
+            rules
+                .as_array()
+                .is_some_and(|rules| !rules.is_empty())
+        })
+        .await
+        .expect("rule to reach the region");
+
+    assert_eq!(rules[0]["name"], "Site down");
+    assert_eq!(rules[0]["condition"], json!({ "kind": "node_not_seen", "hours": 6 }));
+}
@@ -0,0 +1,38 @@
+use rocket::http::{ContentType, Status};
+use rocket::serde::json::Value;
+
+use super::harness::TestNode;
+
+#[rocket::async_test]
+async fn errors_have_a_json_body_with_a_code() {
+    let node = TestNode::start().await;
+
+    let response = node
+        .client
+        .get("/api/no_such_route")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::NotFound);
+    let body: Value = response
+        .into_json()
+        .await
+        .expect("JSON error body");
+    assert_eq!(body["code"], "not_found");
+
+    let response = node
+        .client
+        .post("/api/admin/restore")
+        .header(ContentType::Binary)
+        .body("not a backup")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    let body: Value = response
+        .into_json()
+        .await
+        .expect("JSON error body");
+    assert_eq!(body["code"], "validation_failed");
+    assert!(body["message"]
+        .as_str()
+        .is_some_and(|message| !message.is_empty()));
+}
@@ -0,0 +1,30 @@
+use rocket::http::{Accept, Status};
+
+use super::harness::TestNode;
+
+#[rocket::async_test]
+async fn unversioned_routes_point_to_the_current_version() {
+    let node = TestNode::start().await;
+
+    let response = node
+        .client
+        .get("/api/v1/this_p2panda_node/status")
+        .header(Accept::JSON)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.headers().get_one("Deprecation"), None);
+
+    let response = node
+        .client
+        .get("/api/this_p2panda_node/status")
+        .header(Accept::JSON)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.headers().get_one("Deprecation"), Some("true"));
+    assert_eq!(
+        response.headers().get_one("Link"),
+        Some("</api/v1/this_p2panda_node/status>; rel=\"successor-version\"")
+    );
+}
@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use rocket::http::{ContentType, Status};
+use rocket::serde::json::json;
+
+use super::harness::{migrated_pool, TestNode, TestRegion};
+use crate::infra::backup::replace_database;
+
+const SYNC_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[rocket::async_test]
+async fn backup_restores_identity_on_a_fresh_node() {
+    let region = TestRegion::start("test_backup_region", 1).await;
+    let node = &region.nodes[0];
+    let node_id = node.panda_node_id().await;
+
+    let status = node
+        .post_json("/api/this_node/create", json!({ "name": "Backed Up" }))
+        .await;
+    assert_eq!(status, Status::Ok);
+    assert!(node
+        .wait_for_region_node(&node_id, SYNC_TIMEOUT)
+        .await
+        .is_some());
+
+    let response = node
+        .client
+        .post("/api/admin/backup")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let archive = response
+        .into_bytes()
+        .await
+        .expect("backup archive");
+
+    let fresh_node = TestNode::start().await;
+    let response = fresh_node
+        .client
+        .post("/api/admin/restore")
+        .header(ContentType::Binary)
+        .body(archive)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    assert_eq!(fresh_node.panda_node_id().await, node_id);
+    let restored_node = fresh_node
+        .wait_for_region_node(&node_id, SYNC_TIMEOUT)
+        .await
+        .expect("restored region node");
+    assert_eq!(restored_node.name, "Backed Up");
+}
+
+#[rocket::async_test]
+async fn restore_rejects_a_broken_archive() {
+    let node = TestNode::start().await;
+    let key_before = node.get_json("/api/this_p2panda_node/key").await;
+
+    let response = node
+        .client
+        .post("/api/admin/restore")
+        .header(ContentType::Binary)
+        .body("not a backup")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+
+    assert_eq!(node.get_json("/api/this_p2panda_node/key").await, key_before);
+}
+
+#[rocket::async_test]
+async fn failed_restore_leaves_foreign_keys_checked() {
+    let pool = migrated_pool().await;
+    let missing = tempfile::tempdir()
+        .unwrap()
+        .path()
+        .join("missing")
+        .join("backup.sqlite");
+
+    assert!(replace_database(&pool, &missing).await.is_err());
+
+    let foreign_keys: i64 = sqlx::query_scalar("PRAGMA foreign_keys")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(foreign_keys, 1);
+}
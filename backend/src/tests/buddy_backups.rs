@@ -0,0 +1,186 @@
+use std::time::Duration;
+
+use rocket::http::Status;
+use rocket::serde::json::{json, Value};
+
+use super::harness::{idle_container, migrated_pool, TestRegion};
+use crate::events::handler_map::handle_event;
+use crate::infra::buddy_backups::{next_push_in, BACKUP_INTERVAL, OVERDUE_BACKUP_DELAY};
+use crate::infra::time::unix_now;
+use crate::panda_comms::lores_events::{LoResEvent, LoResEventHeader, LoResEventPayload, NodeLeft};
+use crate::repos::buddy_backups::BuddyBackupsRepo;
+use crate::repos::entities::{HeldBackup, Node};
+use crate::repos::nodes::NodesRepo;
+
+const SYNC_TIMEOUT: Duration = Duration::from_secs(60);
+
+fn not_empty(value: &Value) -> bool {
+    value
+        .as_array()
+        .is_some_and(|items| !items.is_empty())
+}
+
+#[test]
+fn overdue_backups_are_pushed_soon_after_start() {
+    let now = 1_700_000_000;
+    let interval = BACKUP_INTERVAL.as_secs() as i64;
+
+    assert_eq!(next_push_in(None, now), OVERDUE_BACKUP_DELAY);
+    assert_eq!(next_push_in(Some(now - 2 * interval), now), OVERDUE_BACKUP_DELAY);
+    assert_eq!(next_push_in(Some(now - interval + 1), now), OVERDUE_BACKUP_DELAY);
+    assert_eq!(next_push_in(Some(now - 60 * 60), now), BACKUP_INTERVAL - Duration::from_secs(60 * 60));
+}
+
+#[rocket::async_test]
+async fn latest_push_is_remembered() {
+    let pool = migrated_pool().await;
+    let repo = BuddyBackupsRepo::init();
+    assert_eq!(repo.last_pushed_at(&pool).await.unwrap(), None);
+
+    let before = unix_now();
+    repo.record_push(&pool, "blob").await.unwrap();
+
+    assert!(repo
+        .last_pushed_at(&pool)
+        .await
+        .unwrap()
+        .is_some_and(|pushed_at| pushed_at >= before));
+}
+
+#[rocket::async_test]
+async fn held_backups_stay_within_the_quota() {
+    let pool = migrated_pool().await;
+    let repo = BuddyBackupsRepo::init();
+    let held = |blob_hash: &str, owner_node_id: &str| HeldBackup {
+        blob_hash: blob_hash.to_string(),
+        owner_node_id: owner_node_id.to_string(),
+        created_at: unix_now(),
+    };
+
+    assert!(repo
+        .hold_backup(&pool, held("a1", "a"), vec![0; 10], 25)
+        .await
+        .unwrap());
+    assert!(repo
+        .hold_backup(&pool, held("b1", "b"), vec![0; 10], 25)
+        .await
+        .unwrap());
+    assert!(!repo
+        .hold_backup(&pool, held("c1", "c"), vec![0; 10], 25)
+        .await
+        .unwrap());
+
+    let owners: Vec<String> = repo
+        .held_backups(&pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|held_backup| held_backup.owner_node_id)
+        .collect();
+    assert_eq!(owners, ["a", "b"]);
+}
+
+#[rocket::async_test]
+async fn nodes_that_leave_are_no_longer_backup_buddies() {
+    let pool = migrated_pool().await;
+    let repo = BuddyBackupsRepo::init();
+    for id in ["leaving", "staying"] {
+        let node = Node {
+            id: id.to_string(),
+            name: id.to_string(),
+        };
+        NodesRepo::init()
+            .upsert(&pool, node)
+            .await
+            .unwrap();
+        repo.add_buddy(&pool, id).await.unwrap();
+    }
+
+    let left = |author: &str| {
+        LoResEvent::new(
+            LoResEventHeader {
+                author_node_id: author.to_string(),
+            },
+            LoResEventPayload::NodeLeft(NodeLeft { left_at: unix_now() }),
+        )
+    };
+    handle_event(left("leaving"), &pool, &idle_container())
+        .await
+        .unwrap();
+    // a node that was never a buddy leaves too
+    handle_event(left("stranger"), &pool, &idle_container())
+        .await
+        .unwrap();
+
+    let buddies: Vec<String> = repo
+        .buddies(&pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|buddy| buddy.node_id)
+        .collect();
+    assert_eq!(buddies, ["staying"]);
+
+    let mut connection = pool.acquire().await.unwrap();
+    let nodes = NodesRepo::init()
+        .all(&mut connection)
+        .await
+        .unwrap();
+    assert_eq!(nodes.len(), 1);
+    assert_eq!(nodes[0].id, "staying");
+}
+
+#[rocket::async_test]
+async fn lost_node_is_restored_from_its_buddy() {
+    let mut region = TestRegion::start("test_buddy_backup_region", 2).await;
+    let owner_id = region.nodes[0].panda_node_id().await;
+    let buddy_id = region.nodes[1].panda_node_id().await;
+
+    // buddies keep each other's backups, and only those
+    for (node, other_id) in [(0, &buddy_id), (1, &owner_id)] {
+        let status = region.nodes[node]
+            .post_json("/api/admin/backup_buddies", json!({ "node_id": other_id }))
+            .await;
+        assert_eq!(status, Status::Ok);
+    }
+    let status = region.nodes[0]
+        .post_json("/api/admin/backup_buddies/push", json!({ "passphrase": "correct horse" }))
+        .await;
+    assert_eq!(status, Status::Ok);
+
+    let held = region.nodes[1]
+        .wait_for_json("/api/admin/held_backups", SYNC_TIMEOUT, not_empty)
+        .await
+        .expect("held backup");
+    assert_eq!(held[0]["owner_node_id"], owner_id.as_str());
+
+    let replacement = region.join().await;
+    let node = &region.nodes[replacement];
+
+    let status = node
+        .post_json("/api/admin/buddy_restore/request", json!({ "owner_node_id": owner_id }))
+        .await;
+    assert_eq!(status, Status::Ok);
+
+    let offers = node
+        .wait_for_json("/api/admin/buddy_restore/offers", SYNC_TIMEOUT, not_empty)
+        .await
+        .expect("backup offer");
+
+    let status = node
+        .post_json(
+            "/api/admin/buddy_restore",
+            json!({ "blob_hash": offers[0]["blob_hash"], "passphrase": "wrong horse" }),
+        )
+        .await;
+    assert_eq!(status, Status::UnprocessableEntity);
+
+    let status = node
+        .post_json(
+            "/api/admin/buddy_restore",
+            json!({ "blob_hash": offers[0]["blob_hash"], "passphrase": "correct horse" }),
+        )
+        .await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(node.panda_node_id().await, owner_id);
+}
@@ -0,0 +1,76 @@
+use clap::Parser;
+use p2panda_core::PrivateKey;
+use sqlx::sqlite::SqlitePoolOptions;
+
+use crate::cli::{self, Cli, Invite};
+
+#[test]
+fn invite_round_trips_and_rejects_bad_node_ids() {
+    let node_id = PrivateKey::new().public_key().to_hex();
+
+    let invite: Invite = format!("valley@north@{}", node_id)
+        .parse()
+        .expect("valid invite");
+    assert_eq!(invite.network_name, "valley@north");
+    assert_eq!(invite.bootstrap_node_id, node_id);
+    assert_eq!(invite.to_string(), format!("valley@north@{}", node_id));
+
+    assert!("valley@not-a-node-id".parse::<Invite>().is_err());
+    assert!(node_id.parse::<Invite>().is_err());
+}
+
+#[test]
+fn join_takes_either_a_network_name_or_an_invite() {
+    let node_id = PrivateKey::new().public_key().to_hex();
+    let invite = format!("valley@{}", node_id);
+
+    assert!(Cli::try_parse_from(["lores-node", "join", "valley"]).is_ok());
+    assert!(Cli::try_parse_from(["lores-node", "join", "--invite", &invite]).is_ok());
+    assert!(Cli::try_parse_from(["lores-node", "join"]).is_err());
+    assert!(Cli::try_parse_from(["lores-node", "join", "valley", "--invite", &invite]).is_err());
+
+    assert!(Cli::try_parse_from(["lores-node"])
+        .expect("no command")
+        .serves());
+    assert!(!Cli::try_parse_from(["lores-node", "nodes"])
+        .expect("nodes")
+        .serves());
+}
+
+#[rocket::async_test]
+async fn local_commands_work_on_the_database_of_a_stopped_node() {
+    let data_dir = tempfile::tempdir().unwrap();
+    let database_url = format!("sqlite://{}?mode=rwc", data_dir.path().join("lores.db").display());
+    let bootstrap_node_id = PrivateKey::new().public_key().to_hex();
+    let invite = format!("valley@{}", bootstrap_node_id);
+
+    let run = |args: &[&str]| {
+        let mut argv = vec!["lores-node", "--database-url", &database_url];
+        argv.extend_from_slice(args);
+        cli::run(Cli::try_parse_from(argv).expect("valid arguments"))
+    };
+
+    run(&["init"]).await.expect("init");
+    run(&["join", "--invite", &invite])
+        .await
+        .expect("join");
+    run(&["nodes"]).await.expect("nodes");
+
+    let pool = SqlitePoolOptions::new()
+        .connect(&database_url)
+        .await
+        .unwrap();
+    let private_key_hex: Option<String> = sqlx::query_scalar("SELECT private_key_hex FROM node_configs")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert!(private_key_hex.is_some());
+
+    let (network_name, joined_node_id): (Option<String>, Option<String>) =
+        sqlx::query_as("SELECT network_name, bootstrap_node_id FROM network_configs")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(network_name.as_deref(), Some("valley"));
+    assert_eq!(joined_node_id, Some(bootstrap_node_id));
+}
@@ -0,0 +1,87 @@
+use std::time::Duration;
+
+use p2panda_core::PrivateKey;
+use rocket::http::{ContentType, Status};
+use rocket::serde::json::{json, Value};
+use rocket::tokio::time::{sleep, Instant};
+
+use super::harness::{TestNode, TestRegion};
+use crate::panda_comms::sealed_box;
+
+const SYNC_TIMEOUT: Duration = Duration::from_secs(60);
+const ABSENCE_WINDOW: Duration = Duration::from_secs(5);
+
+async fn wait_for_inbox(node: &TestNode, timeout: Duration) -> Vec<Value> {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        let inbox = node.get_json("/api/this_node/inbox").await;
+        let inbox = inbox
+            .as_array()
+            .expect("list of messages")
+            .clone();
+
+        if !inbox.is_empty() || Instant::now() >= deadline {
+            return inbox;
+        }
+
+        sleep(Duration::from_millis(250)).await;
+    }
+}
+
+#[test]
+fn sealed_box_only_opens_for_the_recipient() {
+    let recipient = PrivateKey::new();
+    let someone_else = PrivateKey::new();
+
+    let sealed = sealed_box::seal(&recipient.public_key(), b"please restart your router").unwrap();
+
+    assert_eq!(sealed_box::open(&recipient, &sealed).unwrap(), b"please restart your router");
+    assert!(sealed_box::open(&someone_else, &sealed).is_err());
+}
+
+#[rocket::async_test]
+async fn direct_message_only_reaches_the_recipient() {
+    let region = TestRegion::start("test_direct_messages_region", 3).await;
+    let sender_id = region.nodes[0].panda_node_id().await;
+    let recipient_id = region.nodes[1].panda_node_id().await;
+
+    let status = region.nodes[0]
+        .post_json(
+            "/api/this_node/messages",
+            json!({ "recipient_node_id": recipient_id, "body": "please restart your router" }),
+        )
+        .await;
+    assert_eq!(status, Status::Ok);
+
+    let inbox = wait_for_inbox(&region.nodes[1], SYNC_TIMEOUT).await;
+    assert_eq!(inbox.len(), 1);
+    assert_eq!(inbox[0]["body"], "please restart your router");
+    assert_eq!(inbox[0]["sender_node_id"], sender_id);
+
+    assert!(wait_for_inbox(&region.nodes[2], ABSENCE_WINDOW)
+        .await
+        .is_empty());
+    assert!(wait_for_inbox(&region.nodes[0], ABSENCE_WINDOW)
+        .await
+        .is_empty());
+}
+
+#[rocket::async_test]
+async fn direct_message_to_an_invalid_node_id_is_rejected() {
+    let node = TestNode::start().await;
+
+    let response = node
+        .client
+        .post("/api/this_node/messages")
+        .header(ContentType::JSON)
+        .body(json!({ "recipient_node_id": "not-a-node-id", "body": "hello" }).to_string())
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    let body: Value = response
+        .into_json()
+        .await
+        .expect("JSON error body");
+    assert_eq!(body["code"], "validation_failed");
+}
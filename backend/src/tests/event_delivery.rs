@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use rocket::http::Status;
+use rocket::serde::json::json;
+
+use super::faulty_delivery::DeliveryCondition;
+use super::harness::TestRegion;
+
+const SYNC_TIMEOUT: Duration = Duration::from_secs(60);
+const ABSENCE_WINDOW: Duration = Duration::from_secs(5);
+
+#[rocket::async_test]
+async fn held_back_events_are_handled_once_released() {
+    let region = TestRegion::start("test_held_back_region", 3).await;
+    region.hold_back(&[0, 1], &[2]).await;
+
+    let announcer = &region.nodes[0];
+    let status = announcer
+        .post_json("/api/this_node/create", json!({ "name": "Announcer" }))
+        .await;
+    assert_eq!(status, Status::Ok);
+    let announcer_id = announcer.panda_node_id().await;
+
+    assert!(region.nodes[1]
+        .wait_for_region_node(&announcer_id, SYNC_TIMEOUT)
+        .await
+        .is_some());
+    assert!(region.nodes[2]
+        .wait_for_region_node(&announcer_id, ABSENCE_WINDOW)
+        .await
+        .is_none());
+
+    region.release().await;
+
+    assert!(region.nodes[2]
+        .wait_for_region_node(&announcer_id, SYNC_TIMEOUT)
+        .await
+        .is_some());
+}
+
+#[rocket::async_test]
+async fn dropped_events_are_never_handled() {
+    let region = TestRegion::start("test_dropping_region", 2).await;
+    region
+        .set_delivery(0, 1, DeliveryCondition::Dropping)
+        .await;
+
+    let announcer = &region.nodes[0];
+    let status = announcer
+        .post_json("/api/this_node/create", json!({ "name": "Announcer" }))
+        .await;
+    assert_eq!(status, Status::Ok);
+    let announcer_id = announcer.panda_node_id().await;
+
+    assert!(region.nodes[1]
+        .wait_for_region_node(&announcer_id, ABSENCE_WINDOW)
+        .await
+        .is_none());
+}
+
+#[rocket::async_test]
+async fn delayed_events_are_still_handled() {
+    let region = TestRegion::start("test_delayed_region", 2).await;
+    region
+        .set_delivery(0, 1, DeliveryCondition::Delayed(Duration::from_secs(2)))
+        .await;
+
+    let announcer = &region.nodes[0];
+    let status = announcer
+        .post_json("/api/this_node/create", json!({ "name": "Announcer" }))
+        .await;
+    assert_eq!(status, Status::Ok);
+    let announcer_id = announcer.panda_node_id().await;
+
+    assert!(region.nodes[1]
+        .wait_for_region_node(&announcer_id, SYNC_TIMEOUT)
+        .await
+        .is_some());
+}
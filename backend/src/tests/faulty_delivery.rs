@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rocket::tokio::sync::{mpsc, Mutex};
+use rocket::tokio::time::{sleep_until, Instant};
+use rocket::tokio::{self};
+
+use crate::panda_comms::lores_events::LoResEvent;
+
+/// How events authored by one node get from the node's `P2PandaContainer` to its event handler.
+///
+/// These faults sit after p2panda has synced the events, so they test how the node copes with
+/// events arriving late or not at all. A broken network is a [`super::faulty_network::UdpProxy`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeliveryCondition {
+    Delivered,
+    /// Events are held back until released, in order.
+    HeldBack,
+    /// Events are delivered in order, each one the given delay after it arrived or after the
+    /// previous one was delivered, whichever is later. Events by other authors aren't held up.
+    Delayed(Duration),
+    /// Events never reach the handler. Sync doesn't bring them back either, as p2panda already
+    /// handed them over.
+    Dropping,
+}
+
+struct DeliveryState {
+    conditions: HashMap<String, DeliveryCondition>,
+    held: Vec<LoResEvent>,
+    /// One queue per author with delayed events, so a delay only holds up that author's events.
+    delay_lanes: HashMap<String, mpsc::UnboundedSender<(Instant, Duration, LoResEvent)>>,
+}
+
+/// Sits between a node's `P2PandaContainer` and its event handler, and decides per authoring node
+/// whether received events are delivered, delayed, held back or dropped.
+///
+/// It lives outside of the node, so it keeps its conditions (and held events) when the node is
+/// restarted.
+#[derive(Clone)]
+pub struct FaultyDelivery {
+    state: Arc<Mutex<DeliveryState>>,
+    handler_tx: Arc<Mutex<Option<mpsc::Sender<LoResEvent>>>>,
+}
+
+impl FaultyDelivery {
+    pub fn new() -> Self {
+        FaultyDelivery {
+            state: Arc::new(Mutex::new(DeliveryState {
+                conditions: HashMap::new(),
+                held: vec![],
+                delay_lanes: HashMap::new(),
+            })),
+            handler_tx: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Creates the channels for a (re)started node: the sender goes to the container and the
+    /// receiver to the event handler.
+    pub async fn connect(&self) -> (mpsc::Sender<LoResEvent>, mpsc::Receiver<LoResEvent>) {
+        let (container_tx, mut container_rx) = mpsc::channel::<LoResEvent>(32);
+        let (handler_tx, handler_rx) = mpsc::channel::<LoResEvent>(32);
+
+        *self.handler_tx.lock().await = Some(handler_tx.clone());
+        // the lanes of the previous run deliver to its handler
+        self.state.lock().await.delay_lanes.clear();
+
+        let state = self.state.clone();
+
+        tokio::spawn(async move {
+            while let Some(event) = container_rx.recv().await {
+                let mut state_lock = state.lock().await;
+                let condition = state_lock
+                    .conditions
+                    .get(&event.header.author_node_id)
+                    .copied()
+                    .unwrap_or(DeliveryCondition::Delivered);
+
+                match condition {
+                    DeliveryCondition::Delivered => {}
+                    DeliveryCondition::HeldBack => {
+                        state_lock.held.push(event);
+                        continue;
+                    }
+                    DeliveryCondition::Delayed(delay) => {
+                        let lane = state_lock
+                            .delay_lanes
+                            .entry(event.header.author_node_id.clone())
+                            .or_insert_with(|| delay_lane(handler_tx.clone()));
+                        let _ = lane.send((Instant::now(), delay, event));
+                        continue;
+                    }
+                    DeliveryCondition::Dropping => continue,
+                }
+                drop(state_lock);
+
+                if handler_tx.send(event).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        (container_tx, handler_rx)
+    }
+
+    pub async fn set(&self, author_node_id: &str, condition: DeliveryCondition) {
+        let mut state = self.state.lock().await;
+        state
+            .conditions
+            .insert(author_node_id.to_string(), condition);
+    }
+
+    /// Delivers the events of `author_node_id` normally again, starting with the ones held back.
+    pub async fn release(&self, author_node_id: &str) {
+        // events received meanwhile wait for the lock, so they are delivered after the held ones
+        let mut state = self.state.lock().await;
+        state.conditions.remove(author_node_id);
+
+        let (released, held): (Vec<LoResEvent>, Vec<LoResEvent>) = state
+            .held
+            .drain(..)
+            .partition(|event| event.header.author_node_id == author_node_id);
+        state.held = held;
+
+        let handler_tx = self.handler_tx.lock().await.clone();
+        if let Some(handler_tx) = handler_tx {
+            for event in released {
+                handler_tx
+                    .send(event)
+                    .await
+                    .expect("event handler running");
+            }
+        }
+    }
+}
+
+/// Delivers the events sent to it in order, each the delay after it was received or after the
+/// previous one was delivered.
+fn delay_lane(handler_tx: mpsc::Sender<LoResEvent>) -> mpsc::UnboundedSender<(Instant, Duration, LoResEvent)> {
+    let (lane_tx, mut lane_rx) = mpsc::unbounded_channel::<(Instant, Duration, LoResEvent)>();
+
+    tokio::spawn(async move {
+        let mut previous_delivery: Option<Instant> = None;
+
+        while let Some((received_at, delay, event)) = lane_rx.recv().await {
+            let start = previous_delivery.map_or(received_at, |delivered_at| delivered_at.max(received_at));
+            sleep_until(start + delay).await;
+
+            if handler_tx.send(event).await.is_err() {
+                break;
+            }
+            previous_delivery = Some(Instant::now());
+        }
+    });
+
+    lane_tx
+}
@@ -0,0 +1,122 @@
+use std::collections::{HashMap, HashSet};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+
+use rocket::tokio::net::UdpSocket;
+use rocket::tokio::sync::Mutex;
+use rocket::tokio::{self};
+
+/// Large enough for any QUIC datagram iroh sends.
+const MAX_DATAGRAM_SIZE: usize = 65_536;
+
+/// A UDP proxy in front of a test node's network, which other nodes are told to reach it on.
+///
+/// Unlike [`super::faulty_delivery::FaultyDelivery`], which sits after p2panda, the proxy breaks
+/// the network itself: packets between the node and a blocked address are dropped both ways, so
+/// connections time out and p2panda has to find its way back once the address is unblocked.
+///
+/// Packets from each sender are forwarded from a socket of their own, so the node sees every
+/// sender on a different address, like peers behind a NAT.
+#[derive(Clone)]
+pub struct UdpProxy {
+    address: SocketAddr,
+    target: SocketAddr,
+    blocked: Arc<Mutex<HashSet<SocketAddr>>>,
+}
+
+impl UdpProxy {
+    /// Starts forwarding packets sent to a free port on localhost to `target`.
+    pub async fn start(target: SocketAddr) -> Self {
+        let socket = Arc::new(
+            UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))
+                .await
+                .expect("proxy socket"),
+        );
+        let address = socket.local_addr().expect("proxy address");
+        let blocked = Arc::new(Mutex::new(HashSet::new()));
+
+        let proxy = UdpProxy { address, target, blocked };
+        let forwarding = proxy.clone();
+
+        tokio::spawn(async move {
+            let mut upstreams: HashMap<SocketAddr, Arc<UdpSocket>> = HashMap::new();
+            let mut buf = vec![0; MAX_DATAGRAM_SIZE];
+
+            while let Ok((len, sender)) = socket.recv_from(&mut buf).await {
+                if forwarding.is_blocked(sender).await {
+                    continue;
+                }
+
+                let upstream = match upstreams.get(&sender) {
+                    Some(upstream) => upstream.clone(),
+                    None => {
+                        let upstream = forwarding
+                            .connect_upstream(socket.clone(), sender, target)
+                            .await;
+                        upstreams.insert(sender, upstream.clone());
+                        upstream
+                    }
+                };
+                let _ = upstream.send(&buf[..len]).await;
+            }
+        });
+
+        proxy
+    }
+
+    /// Where other nodes reach the node through the proxy.
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// The node's own address, which it sends to other nodes' proxies from.
+    pub fn target(&self) -> SocketAddr {
+        self.target
+    }
+
+    /// Drops the packets between the node and `sender` in both directions, until unblocked.
+    pub async fn block(&self, sender: SocketAddr) {
+        self.blocked.lock().await.insert(sender);
+    }
+
+    pub async fn unblock_all(&self) {
+        self.blocked.lock().await.clear();
+    }
+
+    async fn is_blocked(&self, sender: SocketAddr) -> bool {
+        self.blocked.lock().await.contains(&sender)
+    }
+
+    /// Opens the socket packets from `sender` are forwarded to the node from, and sends the node's
+    /// replies on it back to `sender`.
+    async fn connect_upstream(&self, socket: Arc<UdpSocket>, sender: SocketAddr, target: SocketAddr) -> Arc<UdpSocket> {
+        let upstream = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))
+            .await
+            .expect("upstream socket");
+        upstream
+            .connect(target)
+            .await
+            .expect("node address");
+        let upstream = Arc::new(upstream);
+
+        let replies = upstream.clone();
+        let proxy = self.clone();
+        tokio::spawn(async move {
+            let mut buf = vec![0; MAX_DATAGRAM_SIZE];
+
+            loop {
+                // the node being down while it restarts shows up as an error, it comes back on the
+                // same port
+                let Ok(len) = replies.recv(&mut buf).await else {
+                    continue;
+                };
+                if proxy.is_blocked(sender).await {
+                    continue;
+                }
+                let _ = socket.send_to(&buf[..len], sender).await;
+            }
+        });
+
+        upstream
+    }
+}
@@ -0,0 +1,440 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use iroh::NodeAddr;
+use p2panda_net::NodeAddress;
+use rocket::figment::value::Value as FigmentValue;
+use rocket::http::{ContentType, Status};
+use rocket::local::asynchronous::Client;
+use rocket::serde::json::{json, Value};
+use rocket::tokio::sync::mpsc;
+use rocket::tokio::time::{sleep, Instant};
+use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+use tempfile::TempDir;
+
+use super::faulty_delivery::{DeliveryCondition, FaultyDelivery};
+use super::faulty_network::UdpProxy;
+use crate::infra::db::{migrate, region_database_path};
+use crate::infra::settings::Settings;
+use crate::infra::supervisor::Supervisor;
+use crate::panda_comms::container::{build_public_key_from_hex, P2PandaContainer};
+use crate::repos::entities::Node;
+use crate::repos::helpers::NETWORK_CONFIG_ID;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+const START_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A lores-node running in-process, with its own temporary SQLite database (and therefore its own
+/// private key). The node has no relay, so it can only reach other nodes on localhost. Events it
+/// receives pass through a [`FaultyDelivery`] before being handled.
+pub struct TestNode {
+    pub client: Client,
+    pub delivery: FaultyDelivery,
+    /// In front of the node's network when it is in a region started behind proxies.
+    pub proxy: Option<UdpProxy>,
+    data_dir: TempDir,
+    settings: Vec<(String, String)>,
+}
+
+impl TestNode {
+    pub async fn start() -> Self {
+        Self::start_with(&[]).await
+    }
+
+    /// Starts a node with `settings` on top of the ones every test node has, also after a restart.
+    pub async fn start_with(settings: &[(&str, &str)]) -> Self {
+        let data_dir = tempfile::tempdir().expect("temp dir");
+        let settings = settings
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+
+        Self::start_in(data_dir, FaultyDelivery::new(), None, settings).await
+    }
+
+    async fn start_in(data_dir: TempDir, delivery: FaultyDelivery, proxy: Option<UdpProxy>, settings: Vec<(String, String)>) -> Self {
+        let database_url = database_url(&data_dir);
+
+        let mut figment = rocket::Config::figment()
+            .merge(("databases.main_db.url", database_url))
+            .merge(("frontend_asset_path", ""))
+            .merge(("relay_url", ""))
+            // many nodes share the host
+            .merge(("iroh_port_v4", 0))
+            .merge(("iroh_port_v6", 0))
+            .merge(("log_level", "off"));
+        for (key, value) in &settings {
+            // read like an environment variable, so ports and flags can be given as text
+            let value: FigmentValue = value.parse().expect("setting value");
+            figment = figment.merge((key.as_str(), value));
+        }
+        let node_settings = Settings::extract(&figment).expect("valid settings");
+
+        let (channel_tx, channel_rx) = delivery.connect().await;
+
+        let client = Client::tracked(crate::build_rocket(figment, node_settings, channel_tx, channel_rx))
+            .await
+            .expect("valid rocket instance");
+
+        TestNode {
+            client,
+            delivery,
+            proxy,
+            data_dir,
+            settings,
+        }
+    }
+
+    /// Stops the node and starts it again on the same database, so it keeps its identity and
+    /// region configuration.
+    pub async fn restart(self) -> Self {
+        self.container()
+            .shutdown()
+            .await
+            .expect("shutdown");
+
+        let TestNode {
+            client,
+            delivery,
+            proxy,
+            data_dir,
+            settings,
+        } = self;
+        client.terminate().await;
+
+        Self::start_in(data_dir, delivery, proxy, settings).await
+    }
+
+    fn container(&self) -> &P2PandaContainer {
+        self.client
+            .rocket()
+            .state::<P2PandaContainer>()
+            .expect("P2PandaContainer state")
+    }
+
+    /// Waits for the node's network to be up, and returns its own address.
+    async fn wait_for_network(&self) -> NodeAddr {
+        let deadline = Instant::now() + START_TIMEOUT;
+
+        loop {
+            if let Ok(node_addr) = self.container().get_node_addr().await {
+                return node_addr;
+            }
+            assert!(Instant::now() < deadline, "the node's network didn't start");
+
+            sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Puts a [`UdpProxy`] in front of the node once its network is up. The node keeps its port
+    /// when restarted, so the proxy stays in front of it.
+    async fn start_proxy(&mut self) {
+        let port = self
+            .wait_for_network()
+            .await
+            .direct_addresses
+            .iter()
+            .find(|address| address.is_ipv4())
+            .expect("an IPv4 address")
+            .port();
+        self.settings
+            .push(("iroh_port_v4".to_string(), port.to_string()));
+
+        self.proxy = Some(UdpProxy::start(SocketAddr::from((Ipv4Addr::LOCALHOST, port))).await);
+    }
+
+    /// Tells the node to reach `peer` through the peer's proxy.
+    async fn add_peer(&self, peer: &TestNode) {
+        let proxy = peer.proxy.as_ref().expect("peer behind a proxy");
+        let peer_address = NodeAddress {
+            public_key: build_public_key_from_hex(peer.panda_node_id().await).expect("valid node id"),
+            direct_addresses: vec![proxy.address()],
+            relay_url: None,
+        };
+
+        self.wait_for_network().await;
+        self.container()
+            .add_peer(peer_address)
+            .await
+            .expect("add peer");
+    }
+
+    /// Where the database of the region `id` is, or would be, kept.
+    pub fn region_database_path(&self, id: i64) -> PathBuf {
+        region_database_path(&database_url(&self.data_dir), id)
+    }
+
+    pub async fn get_json(&self, path: &str) -> Value {
+        let response = self
+            .client
+            .get(path)
+            .header(ContentType::JSON)
+            .header(rocket::http::Accept::JSON)
+            .dispatch()
+            .await;
+
+        assert_eq!(response.status(), Status::Ok, "GET {}", path);
+        response.into_json().await.expect("JSON body")
+    }
+
+    pub async fn post_json(&self, path: &str, body: Value) -> Status {
+        self.client
+            .post(path)
+            .header(ContentType::JSON)
+            .header(rocket::http::Accept::JSON)
+            .body(body.to_string())
+            .dispatch()
+            .await
+            .status()
+    }
+
+    pub async fn panda_node_id(&self) -> String {
+        let details = self.get_json("/api/this_p2panda_node").await;
+
+        details["panda_node_id"]
+            .as_str()
+            .expect("panda_node_id")
+            .to_string()
+    }
+
+    pub async fn region_nodes(&self) -> Vec<Node> {
+        let nodes = self.get_json("/api/this_region/nodes").await;
+        rocket::serde::json::from_value(nodes).expect("list of nodes")
+    }
+
+    /// Polls `path` until `ready` holds for its JSON, or `timeout` passes.
+    pub async fn wait_for_json(&self, path: &str, timeout: Duration, ready: impl Fn(&Value) -> bool) -> Option<Value> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let value = self.get_json(path).await;
+
+            if ready(&value) {
+                return Some(value);
+            }
+            if Instant::now() >= deadline {
+                return None;
+            }
+
+            sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Polls `/api/this_region/nodes` until a node with `node_id` appears, or `timeout` passes.
+    pub async fn wait_for_region_node(&self, node_id: &str, timeout: Duration) -> Option<Node> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let found = self
+                .region_nodes()
+                .await
+                .into_iter()
+                .find(|node| node.id == node_id);
+
+            if found.is_some() || Instant::now() >= deadline {
+                return found;
+            }
+
+            sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+fn database_url(data_dir: &TempDir) -> String {
+    format!("sqlite://{}?mode=rwc", data_dir.path().join("node.sqlite").display())
+}
+
+/// Several test nodes joined to the same region. The first node creates the region and the others
+/// use it as their bootstrap peer.
+pub struct TestRegion {
+    pub nodes: Vec<TestNode>,
+    network_name: String,
+    bootstrap_node_id: String,
+    behind_proxies: bool,
+}
+
+impl TestRegion {
+    pub async fn start(network_name: &str, size: usize) -> Self {
+        Self::start_nodes(network_name, size, false).await
+    }
+
+    /// Starts a region whose nodes only know each other by their [`UdpProxy`], without mDNS, so
+    /// the network between them can be cut with [`TestRegion::cut`].
+    pub async fn start_behind_proxies(network_name: &str, size: usize) -> Self {
+        Self::start_nodes(network_name, size, true).await
+    }
+
+    async fn start_nodes(network_name: &str, size: usize, behind_proxies: bool) -> Self {
+        assert!(size > 0, "a region needs at least one node");
+
+        let mut first = TestNode::start_with(&Self::node_settings(behind_proxies)).await;
+        let status = first
+            .post_json(
+                "/api/this_region/bootstrap",
+                json!({ "network_name": network_name, "bootstrap_peer": null }),
+            )
+            .await;
+        assert_eq!(status, Status::Ok);
+        if behind_proxies {
+            first.start_proxy().await;
+        }
+
+        let mut region = TestRegion {
+            network_name: network_name.to_string(),
+            bootstrap_node_id: first.panda_node_id().await,
+            nodes: vec![first],
+            behind_proxies,
+        };
+
+        for _ in 1..size {
+            region.join().await;
+        }
+
+        region
+    }
+
+    fn node_settings(behind_proxies: bool) -> Vec<(&'static str, &'static str)> {
+        if behind_proxies {
+            vec![("mdns_discovery", "false")]
+        } else {
+            vec![]
+        }
+    }
+
+    /// Starts a new node and joins it to the region, returning its index in `nodes`.
+    pub async fn join(&mut self) -> usize {
+        let mut node = TestNode::start_with(&Self::node_settings(self.behind_proxies)).await;
+        let status = node
+            .post_json(
+                "/api/this_region/bootstrap",
+                json!({
+                    "network_name": self.network_name,
+                    "bootstrap_peer": { "node_id": self.bootstrap_node_id },
+                }),
+            )
+            .await;
+        assert_eq!(status, Status::Ok);
+        if self.behind_proxies {
+            node.start_proxy().await;
+        }
+
+        self.nodes.push(node);
+        let index = self.nodes.len() - 1;
+        self.introduce(index).await;
+
+        index
+    }
+
+    pub async fn restart(&mut self, index: usize) {
+        let node = self.nodes.remove(index);
+        let node = node.restart().await;
+        self.nodes.insert(index, node);
+
+        // the node forgot where its peers are
+        self.introduce(index).await;
+    }
+
+    /// Tells the node at `index` and the other nodes where to reach each other, when they are
+    /// behind proxies.
+    async fn introduce(&self, index: usize) {
+        if !self.behind_proxies {
+            return;
+        }
+
+        for (other, node) in self.nodes.iter().enumerate() {
+            if other != index {
+                self.nodes[index].add_peer(node).await;
+                node.add_peer(&self.nodes[index]).await;
+            }
+        }
+    }
+
+    /// Cuts the network between every node in `side_a` and every node in `side_b`, in both
+    /// directions, until [`TestRegion::heal`] is called. Unlike [`TestRegion::hold_back`], p2panda
+    /// itself loses its connections.
+    pub async fn cut(&self, side_a: &[usize], side_b: &[usize]) {
+        for &a in side_a {
+            for &b in side_b {
+                let (proxy_a, proxy_b) = (self.proxy(a), self.proxy(b));
+                proxy_a.block(proxy_b.target()).await;
+                proxy_b.block(proxy_a.target()).await;
+            }
+        }
+    }
+
+    /// Restores the network between all nodes.
+    pub async fn heal(&self) {
+        for index in 0..self.nodes.len() {
+            self.proxy(index).unblock_all().await;
+        }
+    }
+
+    fn proxy(&self, index: usize) -> &UdpProxy {
+        self.nodes[index]
+            .proxy
+            .as_ref()
+            .expect("a region started behind proxies")
+    }
+
+    /// Holds back the events between every node in `side_a` and every node in `side_b`, in both
+    /// directions, until [`TestRegion::release`] is called. The network stays up, only the event
+    /// handlers don't get the events.
+    pub async fn hold_back(&self, side_a: &[usize], side_b: &[usize]) {
+        for &a in side_a {
+            for &b in side_b {
+                self.set_delivery(a, b, DeliveryCondition::HeldBack)
+                    .await;
+                self.set_delivery(b, a, DeliveryCondition::HeldBack)
+                    .await;
+            }
+        }
+    }
+
+    /// Sets how events authored by node `from` reach the event handler of node `to`.
+    pub async fn set_delivery(&self, from: usize, to: usize, condition: DeliveryCondition) {
+        let from_id = self.nodes[from].panda_node_id().await;
+        self.nodes[to]
+            .delivery
+            .set(&from_id, condition)
+            .await;
+    }
+
+    /// Delivers all events normally again, starting with the ones held back.
+    pub async fn release(&self) {
+        for from in &self.nodes {
+            let from_id = from.panda_node_id().await;
+
+            for to in &self.nodes {
+                to.delivery.release(&from_id).await;
+            }
+        }
+    }
+}
+
+/// A migrated in-memory database, for testing repos and background checks without a node.
+pub async fn migrated_pool() -> SqlitePool {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .expect("in-memory database");
+    migrate(&pool).await.expect("migrations");
+
+    pool
+}
+
+/// A container that is never started, for handling events or checks without a network.
+pub fn idle_container() -> P2PandaContainer {
+    let settings = Settings::default();
+    let (events_tx, _) = mpsc::channel(1);
+
+    P2PandaContainer::new(
+        events_tx,
+        None,
+        settings.network_options(),
+        settings.mesh_topic(),
+        Supervisor::default(),
+        NETWORK_CONFIG_ID as i64,
+    )
+}
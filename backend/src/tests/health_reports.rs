@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use rocket::http::Status;
+use rocket::serde::json::json;
+
+use super::harness::TestRegion;
+use crate::infra::health_reports::{attention, HEALTH_REPORT_INTERVAL};
+use crate::infra::time::unix_now;
+use crate::panda_comms::container::P2PandaContainer;
+use crate::panda_comms::lores_events::NodeHealthReported;
+use crate::repos::entities::NodeHealth;
+
+const SYNC_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[rocket::async_test]
+async fn health_reports_show_which_sites_need_attention() {
+    let region = TestRegion::start("test_health_region", 2).await;
+
+    let reporter = &region.nodes[0];
+    let status = reporter
+        .post_json("/api/v1/this_node/create", json!({ "name": "Reporter" }))
+        .await;
+    assert_eq!(status, Status::Ok);
+    let reporter_id = reporter.panda_node_id().await;
+
+    let container = reporter
+        .client
+        .rocket()
+        .state::<P2PandaContainer>()
+        .expect("P2PandaContainer state");
+    container
+        .report_health(NodeHealthReported {
+            reported_at: unix_now(),
+            version: "1.2.3".to_string(),
+            uptime_secs: Some(3600),
+            disk_free_bytes: Some(1_000_000),
+            disk_used_percent: Some(97.5),
+            load_1m: Some(0.5),
+            open_alerts: vec!["disk_full".to_string()],
+        })
+        .await
+        .expect("report health");
+
+    let nodes = region.nodes[1]
+        .wait_for_json("/api/v1/this_region/nodes", SYNC_TIMEOUT, |nodes| {
+            nodes.as_array().is_some_and(|nodes| {
+                nodes
+                    .iter()
+                    .any(|node| node["id"] == reporter_id && !node["health"].is_null())
+            })
+        })
+        .await
+        .expect("health report to reach the region");
+
+    let reported = nodes
+        .as_array()
+        .and_then(|nodes| {
+            nodes
+                .iter()
+                .find(|node| node["id"] == reporter_id)
+        })
+        .expect("reporter");
+    assert_eq!(reported["health"]["version"], "1.2.3");
+    assert_eq!(reported["attention"], json!(["disk_full"]));
+}
+
+#[test]
+fn sites_that_stop_reporting_need_attention() {
+    let health = NodeHealth {
+        reported_at: 0,
+        version: "1.2.3".to_string(),
+        uptime_secs: None,
+        disk_free_bytes: None,
+        disk_used_percent: None,
+        load_1m: None,
+        open_alerts: vec![],
+    };
+    let interval = HEALTH_REPORT_INTERVAL.as_secs() as i64;
+
+    assert!(attention(Some(&health), interval).is_empty());
+    assert_eq!(attention(Some(&health), 4 * interval), ["no_recent_report"]);
+    assert_eq!(attention(None, 0), ["no_report"]);
+}
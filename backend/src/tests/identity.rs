@@ -0,0 +1,220 @@
+use std::time::Duration;
+
+use p2panda_core::PrivateKey;
+use rocket::http::Status;
+use rocket::serde::json::json;
+
+use super::harness::{migrated_pool, TestNode, TestRegion};
+use crate::panda_comms::identity::{rotation_proof, verify_rotation, IdentityBackup};
+use crate::repos::direct_messages::DirectMessagesRepo;
+use crate::repos::entities::{DirectMessage, Node, Notice};
+use crate::repos::nodes::NodesRepo;
+use crate::repos::notices::NoticesRepo;
+
+const SYNC_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[test]
+fn identity_backup_needs_the_right_passphrase() {
+    let private_key = PrivateKey::new();
+    let backup = IdentityBackup::create(&private_key, "correct horse").unwrap();
+
+    assert_eq!(backup.restore("correct horse").unwrap(), private_key);
+    assert!(backup.restore("wrong horse").is_err());
+}
+
+#[test]
+fn rotation_proof_is_tied_to_both_keys() {
+    let old_node_id = PrivateKey::new().public_key().to_hex();
+    let new_private_key = PrivateKey::new();
+    let new_node_id = new_private_key.public_key().to_hex();
+    let proof = rotation_proof(&old_node_id, &new_private_key);
+
+    assert!(verify_rotation(&old_node_id, &new_node_id, &proof));
+
+    let other_node_id = PrivateKey::new().public_key().to_hex();
+    assert!(!verify_rotation(&other_node_id, &new_node_id, &proof));
+    assert!(!verify_rotation(&old_node_id, &other_node_id, &proof));
+}
+
+#[rocket::async_test]
+async fn rotating_a_key_moves_what_the_node_wrote_to_its_new_id() {
+    let pool = migrated_pool().await;
+    let old_node_id = PrivateKey::new().public_key().to_hex();
+    let new_node_id = PrivateKey::new().public_key().to_hex();
+
+    NodesRepo::init()
+        .upsert(
+            &pool,
+            Node {
+                id: old_node_id.clone(),
+                name: "Pump house".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+    NoticesRepo::init()
+        .insert(
+            &pool,
+            Notice {
+                id: "notice".to_string(),
+                author_node_id: old_node_id.clone(),
+                title: "Outage".to_string(),
+                body: "Back tomorrow".to_string(),
+                priority: "normal".to_string(),
+                posted_at: 1,
+                expires_at: None,
+            },
+        )
+        .await
+        .unwrap();
+    DirectMessagesRepo::init()
+        .insert(
+            &pool,
+            DirectMessage {
+                id: "message".to_string(),
+                sender_node_id: old_node_id.clone(),
+                body: "hello".to_string(),
+                sent_at: 1,
+                received_at: 2,
+                read_at: None,
+            },
+        )
+        .await
+        .unwrap();
+
+    NodesRepo::init()
+        .rotate_key(&pool, &old_node_id, &new_node_id)
+        .await
+        .unwrap();
+
+    let node_ids: Vec<String> = sqlx::query_scalar("SELECT id FROM nodes")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(node_ids, vec![new_node_id.clone()]);
+    let author: String = sqlx::query_scalar("SELECT author_node_id FROM notices")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(author, new_node_id);
+    let sender: String = sqlx::query_scalar("SELECT sender_node_id FROM direct_messages")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(sender, new_node_id);
+}
+
+#[rocket::async_test]
+async fn exported_identity_can_be_imported_on_a_fresh_node() {
+    let region = TestRegion::start("test_identity_export_region", 1).await;
+    let node_id = region.nodes[0].panda_node_id().await;
+
+    let response = region.nodes[0]
+        .client
+        .post("/api/this_p2panda_node/identity/export")
+        .header(rocket::http::ContentType::JSON)
+        .body(json!({ "passphrase": "correct horse" }).to_string())
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let backup: rocket::serde::json::Value = response.into_json().await.unwrap();
+
+    let fresh_node = TestNode::start().await;
+    let status = fresh_node
+        .post_json(
+            "/api/this_p2panda_node/identity/import",
+            json!({ "backup": backup, "passphrase": "wrong horse" }),
+        )
+        .await;
+    assert_eq!(status, Status::UnprocessableEntity);
+
+    let status = fresh_node
+        .post_json(
+            "/api/this_p2panda_node/identity/import",
+            json!({ "backup": backup, "passphrase": "correct horse" }),
+        )
+        .await;
+    assert_eq!(status, Status::Ok);
+
+    let status = fresh_node
+        .post_json(
+            "/api/this_region/bootstrap",
+            json!({ "network_name": "test_identity_import_region", "bootstrap_peer": null }),
+        )
+        .await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(fresh_node.panda_node_id().await, node_id);
+}
+
+#[rocket::async_test]
+async fn rotated_key_replaces_the_old_node_id_in_the_region() {
+    let region = TestRegion::start("test_identity_rotation_region", 2).await;
+    let old_node_id = region.nodes[0].panda_node_id().await;
+
+    let status = region.nodes[0]
+        .post_json("/api/this_node/create", json!({ "name": "Rotator" }))
+        .await;
+    assert_eq!(status, Status::Ok);
+    assert!(region.nodes[1]
+        .wait_for_region_node(&old_node_id, SYNC_TIMEOUT)
+        .await
+        .is_some());
+
+    let status = region.nodes[0]
+        .post_json("/api/this_p2panda_node/identity/rotate", json!({}))
+        .await;
+    assert_eq!(status, Status::Ok);
+
+    let new_node_id = region.nodes[0].panda_node_id().await;
+    assert_ne!(new_node_id, old_node_id);
+
+    let rotated = region.nodes[1]
+        .wait_for_region_node(&new_node_id, SYNC_TIMEOUT)
+        .await
+        .expect("rotation to reach the region");
+    assert_eq!(rotated.name, "Rotator");
+}
+
+#[rocket::async_test]
+async fn encrypted_key_stays_locked_until_unlocked() {
+    let mut region = TestRegion::start("test_key_at_rest_region", 1).await;
+    let node_id = region.nodes[0].panda_node_id().await;
+
+    let status = region.nodes[0]
+        .post_json("/api/this_p2panda_node/key/encrypt", json!({ "passphrase": "correct horse" }))
+        .await;
+    assert_eq!(status, Status::Ok);
+
+    region.restart(0).await;
+    let node = &region.nodes[0];
+
+    assert_eq!(node.get_json("/api/this_p2panda_node/key").await["status"], "locked");
+    let response = node
+        .client
+        .get("/api/this_p2panda_node")
+        .header(rocket::http::Accept::JSON)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Locked);
+    let body: rocket::serde::json::Value = response
+        .into_json()
+        .await
+        .expect("JSON error body");
+    assert_eq!(body["code"], "key_locked");
+    let status = node
+        .post_json("/api/this_p2panda_node/identity/rotate", json!({}))
+        .await;
+    assert_eq!(status, Status::Locked);
+
+    let status = node
+        .post_json("/api/this_p2panda_node/key/unlock", json!({ "passphrase": "wrong horse" }))
+        .await;
+    assert_eq!(status, Status::UnprocessableEntity);
+
+    let status = node
+        .post_json("/api/this_p2panda_node/key/unlock", json!({ "passphrase": "correct horse" }))
+        .await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(node.get_json("/api/this_p2panda_node/key").await["status"], "unlocked");
+    assert_eq!(node.panda_node_id().await, node_id);
+}
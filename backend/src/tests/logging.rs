@@ -0,0 +1,155 @@
+use std::collections::BTreeMap;
+
+use p2panda_core::PrivateKey;
+use rocket::http::Status;
+use rocket::local::asynchronous::Client;
+use rocket::serde::json::{json, Value};
+use rocket::{get, routes};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+use super::harness::TestNode;
+use crate::infra::logging::{in_request_span, log_buffer, LogBufferLayer, LogQuery, LogRecord, RequestSpanFairing, REQUEST_ID_HEADER};
+use crate::panda_comms::container::NodeParams;
+use crate::panda_comms::identity::PrivateKeyStatus;
+
+#[test]
+fn node_params_are_logged_without_their_secrets() {
+    let private_key = PrivateKey::new();
+    let params = NodeParams {
+        private_key: Some(private_key.clone()),
+        network_name: Some("test_logging_region".to_string()),
+        key_status: PrivateKeyStatus::Unlocked,
+        key_passphrase: Some("correct horse battery staple".to_string()),
+        ..Default::default()
+    };
+
+    let logged = format!("{:?}", params);
+
+    assert!(!logged.contains(&private_key.to_hex()), "{}", logged);
+    assert!(!logged.contains("correct horse battery staple"), "{}", logged);
+    assert!(logged.contains(&private_key.public_key().to_hex()), "{}", logged);
+    assert!(logged.contains("test_logging_region"), "{}", logged);
+}
+
+fn record(level: &str, target: &str, message: &str) -> LogRecord {
+    LogRecord {
+        id: 0,
+        timestamp: 0,
+        level: level.to_string(),
+        target: target.to_string(),
+        spans: vec![],
+        message: message.to_string(),
+        fields: BTreeMap::from([("peer".to_string(), "abc123".to_string())]),
+    }
+}
+
+#[test]
+fn log_query_filters_by_level_target_and_text() {
+    let query = LogQuery {
+        level: Some("warn".to_string()),
+        target: Some("lores_node::panda_comms".to_string()),
+        contains: Some("abc".to_string()),
+        limit: None,
+    };
+    let min_level = query.min_level().expect("known level");
+
+    assert!(query.matches(min_level, &record("ERROR", "lores_node::panda_comms::container", "Sync failed")));
+    assert!(query.matches(min_level, &record("WARN", "lores_node::panda_comms::container", "Sync failed")));
+    assert!(!query.matches(min_level, &record("INFO", "lores_node::panda_comms::container", "Sync done")));
+    assert!(!query.matches(min_level, &record("WARN", "lores_node::events", "Sync failed")));
+
+    let unknown = LogQuery {
+        level: Some("loud".to_string()),
+        ..Default::default()
+    };
+    assert!(unknown.min_level().is_err());
+}
+
+#[get("/")]
+async fn logging_handler() -> &'static str {
+    rocket::tokio::task::yield_now().await;
+    tracing::info!("Handling the logging test request");
+    "ok"
+}
+
+#[rocket::async_test]
+async fn handler_logs_carry_the_request_id() {
+    // nothing else sets up logging in tests, and the buffer is all this needs
+    let _ = tracing_subscriber::registry()
+        .with(LogBufferLayer)
+        .try_init();
+
+    let rocket = rocket::build()
+        .attach(RequestSpanFairing)
+        .mount("/", in_request_span(routes![logging_handler]));
+    let client = Client::tracked(rocket)
+        .await
+        .expect("valid rocket instance");
+
+    let response = client.get("/").dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+    let request_id = response
+        .headers()
+        .get_one(REQUEST_ID_HEADER)
+        .expect("request id")
+        .to_string();
+
+    let query = LogQuery {
+        contains: Some("Handling the logging test request".to_string()),
+        ..Default::default()
+    };
+    let records = log_buffer().records(&query, None);
+    assert_eq!(records.len(), 1, "{:?}", records);
+    assert_eq!(records[0].spans, vec!["request".to_string()]);
+    assert_eq!(records[0].fields.get("request_id"), Some(&request_id));
+}
+
+#[rocket::async_test]
+async fn diagnostics_bundle_is_downloaded_without_secrets() {
+    let node = TestNode::start_with(&[("alert_webhook_url", "https://hooks.example.com/services/webhook-token-123")]).await;
+
+    let status = node
+        .post_json(
+            "/api/this_p2panda_node/key/encrypt",
+            json!({ "passphrase": "correct horse battery staple" }),
+        )
+        .await;
+    assert_eq!(status, Status::Ok);
+
+    let response = node
+        .client
+        .get("/api/v1/admin/diagnostics")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let disposition = response
+        .headers()
+        .get_one("Content-Disposition")
+        .expect("attachment");
+    assert!(
+        disposition.starts_with("attachment; filename=\"lores-node-diagnostics-"),
+        "{}",
+        disposition
+    );
+
+    let text = response.into_string().await.expect("diagnostics");
+    assert!(!text.contains("webhook-token-123"), "{}", text);
+    assert!(!text.contains("correct horse battery staple"), "{}", text);
+
+    let bundle: Value = rocket::serde::json::from_str(&text).expect("diagnostics json");
+    assert_eq!(bundle["settings"]["alert_webhook_url"], "https://hooks.example.com/[redacted]");
+    assert_eq!(bundle["versions"]["node"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(bundle["status"]["state"]["state"], "unconfigured");
+    let migrations = bundle["migrations"]
+        .as_array()
+        .expect("migrations");
+    assert!(!migrations.is_empty());
+    assert!(
+        migrations
+            .iter()
+            .all(|migration| migration["applied"] == true),
+        "{:?}",
+        migrations
+    );
+}
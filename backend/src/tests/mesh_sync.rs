@@ -0,0 +1,138 @@
+use std::time::Duration;
+
+use rocket::http::Status;
+use rocket::serde::json::json;
+
+use super::faulty_delivery::DeliveryCondition;
+use super::harness::{TestNode, TestRegion};
+
+const SYNC_TIMEOUT: Duration = Duration::from_secs(60);
+const ABSENCE_WINDOW: Duration = Duration::from_secs(5);
+
+#[rocket::async_test]
+async fn late_joiner_receives_earlier_announcements() {
+    let mut region = TestRegion::start("test_late_joiner_region", 2).await;
+
+    let announcer = &region.nodes[0];
+    let status = announcer
+        .post_json("/api/this_node/create", json!({ "name": "Announcer" }))
+        .await;
+    assert_eq!(status, Status::Ok);
+    let announcer_id = announcer.panda_node_id().await;
+
+    assert!(region.nodes[1]
+        .wait_for_region_node(&announcer_id, SYNC_TIMEOUT)
+        .await
+        .is_some());
+
+    let late_joiner = region.join().await;
+
+    assert!(region.nodes[late_joiner]
+        .wait_for_region_node(&announcer_id, SYNC_TIMEOUT)
+        .await
+        .is_some());
+}
+
+#[rocket::async_test]
+async fn restarted_node_keeps_identity_and_resyncs() {
+    let mut region = TestRegion::start_behind_proxies("test_restart_region", 3).await;
+    let id_before_restart = region.nodes[1].panda_node_id().await;
+    let quick_id = region.nodes[0].panda_node_id().await;
+    let slow_id = region.nodes[2].panda_node_id().await;
+
+    // the others announce themselves while the node is cut off, so it has to sync both at once
+    region.cut(&[1], &[0, 2]).await;
+    announce(&region.nodes[0], "Quick").await;
+    announce(&region.nodes[2], "Slow").await;
+
+    // one announcement is handled soon and the other much later, so the node is still catching
+    // up when it's restarted
+    region
+        .set_delivery(0, 1, DeliveryCondition::Delayed(Duration::from_secs(1)))
+        .await;
+    region
+        .set_delivery(2, 1, DeliveryCondition::Delayed(Duration::from_secs(600)))
+        .await;
+    region.heal().await;
+    region.nodes[1]
+        .wait_for_region_node(&quick_id, SYNC_TIMEOUT)
+        .await
+        .expect("sync didn't start");
+    assert!(!has_node(&region.nodes[1], &slow_id).await, "the node caught up before the restart");
+    region
+        .set_delivery(2, 1, DeliveryCondition::Delivered)
+        .await;
+    region.restart(1).await;
+
+    assert_eq!(region.nodes[1].panda_node_id().await, id_before_restart);
+
+    // the announcement it synced but didn't handle is synced again, and none of them twice
+    region.nodes[1]
+        .wait_for_region_node(&slow_id, SYNC_TIMEOUT)
+        .await
+        .expect("the restarted node didn't catch up");
+    let ids: Vec<String> = region.nodes[1]
+        .region_nodes()
+        .await
+        .into_iter()
+        .map(|node| node.id)
+        .collect();
+    assert_eq!(ids.len(), 2, "{:?}", ids);
+}
+
+#[rocket::async_test]
+async fn partitioned_nodes_converge_once_healed() {
+    let region = TestRegion::start_behind_proxies("test_partition_region", 3).await;
+    let north_id = region.nodes[0].panda_node_id().await;
+    let middle_id = region.nodes[1].panda_node_id().await;
+    let south_id = region.nodes[2].panda_node_id().await;
+
+    announce(&region.nodes[1], "Middle").await;
+    for index in [0, 2] {
+        region.nodes[index]
+            .wait_for_region_node(&middle_id, SYNC_TIMEOUT)
+            .await
+            .expect("the region didn't form");
+    }
+
+    region.cut(&[0, 1], &[2]).await;
+    announce(&region.nodes[0], "North").await;
+    announce(&region.nodes[2], "South").await;
+
+    // each side keeps syncing on its own
+    region.nodes[1]
+        .wait_for_region_node(&north_id, SYNC_TIMEOUT)
+        .await
+        .expect("announcement didn't reach the node on the same side");
+    assert!(region.nodes[2]
+        .wait_for_region_node(&north_id, ABSENCE_WINDOW)
+        .await
+        .is_none());
+    assert!(region.nodes[0]
+        .wait_for_region_node(&south_id, ABSENCE_WINDOW)
+        .await
+        .is_none());
+
+    region.heal().await;
+
+    for (index, node_id) in [(0, &south_id), (1, &south_id), (2, &north_id)] {
+        region.nodes[index]
+            .wait_for_region_node(node_id, SYNC_TIMEOUT)
+            .await
+            .expect("the sides didn't converge");
+    }
+}
+
+async fn announce(node: &TestNode, name: &str) {
+    let status = node
+        .post_json("/api/this_node/create", json!({ "name": name }))
+        .await;
+    assert_eq!(status, Status::Ok);
+}
+
+async fn has_node(node: &TestNode, node_id: &str) -> bool {
+    node.region_nodes()
+        .await
+        .iter()
+        .any(|node| node.id == node_id)
+}
@@ -0,0 +1,29 @@
+use rocket::http::Status;
+
+use super::harness::TestNode;
+
+#[rocket::async_test]
+async fn metrics_are_exposed_in_prometheus_format() {
+    let node = TestNode::start().await;
+
+    node.get_json("/api/v1/this_p2panda_node/status")
+        .await;
+
+    let response = node.client.get("/metrics").dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+    assert!(response
+        .content_type()
+        .is_some_and(|content_type| content_type.is_plain()));
+
+    let body = response
+        .into_string()
+        .await
+        .expect("metrics body");
+    assert!(body.contains("lores_container_state{region=\"0\",state=\"unconfigured\"} 1"), "{}", body);
+    assert!(body.contains("lores_event_queue_depth 0"), "{}", body);
+    assert!(
+        body.contains("lores_http_requests_total{method=\"GET\",route=\"/api/v1/this_p2panda_node/status\",status=\"200\"}"),
+        "{}",
+        body
+    );
+}
@@ -0,0 +1,26 @@
+mod alerts;
+mod api_errors;
+mod api_versions;
+mod backup;
+mod buddy_backups;
+mod cli;
+mod direct_messages;
+mod event_delivery;
+mod faulty_delivery;
+mod faulty_network;
+mod harness;
+mod health_reports;
+mod identity;
+mod logging;
+mod mesh_sync;
+mod metrics;
+mod node_status;
+mod notices;
+mod openapi;
+mod presence;
+mod probes;
+mod region_nodes;
+mod regions;
+mod settings;
+mod supervisor;
+mod system_monitor;
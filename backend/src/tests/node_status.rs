@@ -0,0 +1,40 @@
+use rocket::http::Status;
+use rocket::serde::json::{json, Value};
+
+use super::harness::TestNode;
+
+#[rocket::async_test]
+async fn node_reports_its_state_instead_of_failing_before_joining_a_region() {
+    let node = TestNode::start().await;
+
+    let status = node
+        .get_json("/api/this_p2panda_node/status")
+        .await;
+    assert_eq!(status, json!({ "state": "unconfigured" }));
+
+    let response = node
+        .client
+        .get("/api/this_p2panda_node")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::ServiceUnavailable);
+    let body: Value = response
+        .into_json()
+        .await
+        .expect("JSON error body");
+    assert_eq!(body["code"], "network_not_started");
+
+    let status = node
+        .post_json(
+            "/api/this_region/bootstrap",
+            json!({ "network_name": "test_node_status_region", "bootstrap_peer": null }),
+        )
+        .await;
+    assert_eq!(status, Status::Ok);
+
+    let status = node
+        .get_json("/api/this_p2panda_node/status")
+        .await;
+    assert_eq!(status, json!({ "state": "running" }));
+    assert!(!node.panda_node_id().await.is_empty());
+}
@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+use rocket::http::Status;
+use rocket::serde::json::json;
+use rocket::tokio::time::{sleep, Instant};
+
+use super::harness::TestRegion;
+
+const SYNC_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[rocket::async_test]
+async fn posted_notice_reaches_the_other_nodes() {
+    let region = TestRegion::start("test_notices_region", 2).await;
+
+    let status = region.nodes[0]
+        .post_json(
+            "/api/this_region/notices",
+            json!({ "title": "Power cut", "body": "Running on batteries", "priority": "urgent" }),
+        )
+        .await;
+    assert_eq!(status, Status::Ok);
+
+    let deadline = Instant::now() + SYNC_TIMEOUT;
+    loop {
+        let notices = region.nodes[1]
+            .get_json("/api/this_region/notices")
+            .await;
+        let notices = notices.as_array().expect("list of notices");
+
+        if let Some(notice) = notices.first() {
+            assert_eq!(notice["title"], "Power cut");
+            assert_eq!(notice["priority"], "urgent");
+            break;
+        }
+
+        assert!(Instant::now() < deadline, "notice didn't reach the other node");
+        sleep(Duration::from_millis(250)).await;
+    }
+}
+
+#[rocket::async_test]
+async fn notice_without_title_is_rejected() {
+    let region = TestRegion::start("test_invalid_notice_region", 1).await;
+
+    let status = region.nodes[0]
+        .post_json("/api/this_region/notices", json!({ "title": " ", "body": "No title" }))
+        .await;
+    assert_eq!(status, Status::UnprocessableEntity);
+}
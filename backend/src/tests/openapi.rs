@@ -0,0 +1,18 @@
+use rocket::http::Status;
+
+use super::harness::TestNode;
+
+#[rocket::async_test]
+async fn openapi_document_covers_the_api_routes() {
+    let node = TestNode::start().await;
+
+    let document = node.get_json("/api/openapi.json").await;
+    assert_eq!(document["info"]["title"], "LoRes Node API");
+    assert!(document["paths"]["/api/v1/this_region/nodes"]["get"].is_object());
+    assert!(document["paths"]["/api/v1/this_node/inbox/{id}/read"]["post"].is_object());
+    assert!(document["components"]["schemas"]["Node"].is_object());
+    assert!(document["components"]["schemas"]["ApiError"].is_object());
+
+    let response = node.client.get("/api/docs/").dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+}
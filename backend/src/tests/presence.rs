@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use p2panda_core::PrivateKey;
+
+use crate::panda_comms::presence::{EphemeralMessage, EphemeralMessagePayload, Heartbeat, PresenceTable, PRESENCE_FORGOTTEN_AFTER, PRESENCE_TIMEOUT};
+
+const NOW: i64 = 1_700_000_000;
+
+fn heartbeat() -> Heartbeat {
+    Heartbeat::current(Duration::from_secs(60))
+}
+
+#[rocket::async_test]
+async fn nodes_are_online_until_their_heartbeat_times_out() {
+    let presence = PresenceTable::default();
+    presence
+        .record_at("a".to_string(), heartbeat(), NOW)
+        .await;
+
+    let timeout = PRESENCE_TIMEOUT.as_secs() as i64;
+
+    let presences = presence.all_at(NOW + timeout - 1).await;
+    assert_eq!(presences.len(), 1);
+    assert!(presences[0].online);
+    assert_eq!(presences[0].last_seen_at, NOW);
+
+    let presences = presence.all_at(NOW + timeout).await;
+    assert_eq!(presences.len(), 1);
+    assert!(!presences[0].online);
+}
+
+#[rocket::async_test]
+async fn nodes_not_heard_from_for_long_are_forgotten() {
+    let presence = PresenceTable::default();
+    let forgotten_after = PRESENCE_FORGOTTEN_AFTER.as_secs() as i64;
+
+    presence
+        .record_at("old".to_string(), heartbeat(), NOW)
+        .await;
+    presence
+        .record_at("recent".to_string(), heartbeat(), NOW + 60)
+        .await;
+    presence
+        .record_at("new".to_string(), heartbeat(), NOW + forgotten_after)
+        .await;
+
+    let node_ids: Vec<String> = presence
+        .all_at(NOW + forgotten_after)
+        .await
+        .into_iter()
+        .map(|presence| presence.node_id)
+        .collect();
+    assert_eq!(node_ids, vec!["new".to_string(), "recent".to_string()]);
+}
+
+#[test]
+fn ephemeral_messages_are_only_trusted_when_signed_recently_by_their_sender() {
+    let private_key = PrivateKey::new();
+
+    let message = EphemeralMessage::signed(&private_key, EphemeralMessagePayload::WhoIsOnline, NOW).unwrap();
+    assert_eq!(message.sender_node_id, private_key.public_key().to_hex());
+    assert!(message.verify(NOW + 1));
+
+    // replayed after the presence window
+    assert!(!message.verify(NOW + PRESENCE_TIMEOUT.as_secs() as i64 + 1));
+
+    // claims to come from another node
+    let mut forged = EphemeralMessage::signed(&private_key, EphemeralMessagePayload::WhoIsOnline, NOW).unwrap();
+    forged.sender_node_id = PrivateKey::new().public_key().to_hex();
+    assert!(!forged.verify(NOW));
+
+    // signed payload swapped for another one
+    let mut tampered = EphemeralMessage::signed(&private_key, EphemeralMessagePayload::WhoIsOnline, NOW).unwrap();
+    tampered.payload = EphemeralMessagePayload::Heartbeat(heartbeat());
+    assert!(!tampered.verify(NOW));
+}
@@ -0,0 +1,108 @@
+use rocket::http::Status;
+use rocket::serde::json::Value;
+
+use super::harness::TestNode;
+use crate::infra::db::MigrationStatus;
+use crate::infra::probes::{container_check, migrations_check, task_checks, Readiness};
+use crate::infra::supervisor::{TaskCrash, TaskState, TaskStatus};
+use crate::panda_comms::container::ContainerState;
+
+#[rocket::async_test]
+async fn a_fresh_node_is_healthy_and_ready() {
+    let node = TestNode::start().await;
+
+    let healthz = node.client.get("/healthz").dispatch().await;
+    assert_eq!(healthz.status(), Status::Ok);
+
+    let readyz = node.client.get("/readyz").dispatch().await;
+    assert_eq!(readyz.status(), Status::Ok);
+
+    let readiness: Value = readyz.into_json().await.expect("JSON body");
+    assert_eq!(readiness["ready"], true, "{}", readiness);
+
+    let checks: Vec<(&str, &str)> = readiness["checks"]
+        .as_array()
+        .expect("checks")
+        .iter()
+        .map(|check| (check["name"].as_str().unwrap_or(""), check["detail"].as_str().unwrap_or("")))
+        .collect();
+    assert_eq!(checks[0], ("database", "reachable"));
+    assert_eq!(checks[1].0, "migrations");
+    assert_eq!(checks[2], ("p2panda", "unconfigured"));
+    assert_eq!(
+        checks[3..],
+        [
+            ("alert_checks", "running"),
+            ("buddy_backups", "running"),
+            ("event_handler", "running"),
+            ("health_reports", "running"),
+            ("system_monitor", "running"),
+        ]
+    );
+}
+
+fn task(name: &str, state: TaskState) -> TaskStatus {
+    TaskStatus {
+        name: name.to_string(),
+        state,
+        crashes: 0,
+        last_crash: None,
+    }
+}
+
+#[test]
+fn a_failed_container_or_pending_migration_is_not_ready() {
+    let migrations = [
+        MigrationStatus {
+            version: 1,
+            description: "initial".to_string(),
+            applied: true,
+        },
+        MigrationStatus {
+            version: 2,
+            description: "nodes".to_string(),
+            applied: false,
+        },
+    ];
+
+    let readiness = Readiness::new(
+        vec![migrations_check(&migrations), container_check(&ContainerState::Running)],
+        vec![task("event_handler", TaskState::Running)],
+    );
+    assert!(!readiness.ready);
+    assert_eq!(readiness.checks[0].detail, "not applied: 2");
+
+    assert!(container_check(&ContainerState::Unconfigured).ok);
+    assert!(!container_check(&ContainerState::Starting).ok);
+    assert!(!container_check(&ContainerState::Failed("no relay".to_string())).ok);
+    assert!(
+        Readiness::new(
+            vec![container_check(&ContainerState::Running)],
+            vec![task("event_handler", TaskState::Running)]
+        )
+        .ready
+    );
+}
+
+#[test]
+fn a_crashed_task_or_missing_event_handler_is_not_ready() {
+    let mut messages = task("p2panda_messages", TaskState::Restarting);
+    messages.crashes = 1;
+    messages.last_crash = Some(TaskCrash {
+        crashed_at: 0,
+        reason: "Message stream closed".to_string(),
+    });
+
+    let checks = task_checks(&[task("event_handler", TaskState::Running), messages]);
+    assert_eq!(checks[1].detail, "restarting after: Message stream closed");
+    assert!(checks[0].ok && !checks[1].ok);
+
+    // the listeners stop with the node, the event handler never should
+    assert!(
+        task_checks(&[task("event_handler", TaskState::Running), task("p2panda_messages", TaskState::Stopped)])
+            .iter()
+            .all(|check| check.ok)
+    );
+    assert!(!task_checks(&[task("event_handler", TaskState::Stopped)])[0].ok);
+    assert_eq!(task_checks(&[])[0].detail, "not started");
+}
@@ -0,0 +1,30 @@
+use std::time::Duration;
+
+use rocket::http::Status;
+use rocket::serde::json::json;
+
+use super::harness::TestRegion;
+
+const SYNC_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[rocket::async_test]
+async fn announced_node_shows_up_on_the_other_nodes() {
+    let region = TestRegion::start("test_announce_region", 3).await;
+
+    let announcer = &region.nodes[0];
+    let status = announcer
+        .post_json("/api/this_node/create", json!({ "name": "Announcer" }))
+        .await;
+    assert_eq!(status, Status::Ok);
+
+    let announcer_id = announcer.panda_node_id().await;
+
+    for node in &region.nodes[1..] {
+        let seen = node
+            .wait_for_region_node(&announcer_id, SYNC_TIMEOUT)
+            .await
+            .expect("announced node to reach the region");
+
+        assert_eq!(seen.name, "Announcer");
+    }
+}
@@ -0,0 +1,256 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use rocket::http::{Accept, ContentType, Status};
+use rocket::serde::json::{json, Value};
+
+use super::harness::{TestNode, TestRegion};
+use crate::infra::db::region_database_path;
+
+const SYNC_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[test]
+fn region_databases_are_kept_next_to_the_main_one() {
+    assert_eq!(
+        region_database_path("sqlite:/data/site-manager.db", 2),
+        PathBuf::from("/data/site-manager.region-2.db")
+    );
+    assert_eq!(
+        region_database_path("sqlite:///tmp/node.sqlite?mode=rwc", 1),
+        PathBuf::from("/tmp/node.region-1.sqlite")
+    );
+}
+
+async fn region_names(node: &TestNode) -> Vec<String> {
+    let regions = node.get_json("/api/v1/regions").await;
+
+    regions
+        .as_array()
+        .expect("list of regions")
+        .iter()
+        .map(|region| {
+            region["network_name"]
+                .as_str()
+                .expect("network_name")
+                .to_string()
+        })
+        .collect()
+}
+
+async fn region_status(node: &TestNode, id: i64) -> Status {
+    node.client
+        .get(format!("/api/v1/regions/{}", id))
+        .header(Accept::JSON)
+        .dispatch()
+        .await
+        .status()
+}
+
+async fn wait_for_notice(node: &TestNode, path: &str, title: &str) -> Option<Value> {
+    node.wait_for_json(path, SYNC_TIMEOUT, |notices| {
+        notices.as_array().is_some_and(|notices| {
+            notices
+                .iter()
+                .any(|notice| notice["title"] == title)
+        })
+    })
+    .await
+}
+
+#[rocket::async_test]
+async fn node_takes_part_in_a_second_region() {
+    let first = TestRegion::start("test_regions_first", 2).await;
+    let second = TestRegion::start("test_regions_second", 1).await;
+    let node = &first.nodes[0];
+    let other_node_id = second.nodes[0].panda_node_id().await;
+
+    let status = node
+        .post_json(
+            "/api/v1/regions",
+            json!({ "network_name": "test_regions_second", "bootstrap_peer": { "node_id": other_node_id } }),
+        )
+        .await;
+    assert_eq!(status, Status::Ok);
+
+    assert_eq!(region_names(node).await, ["test_regions_first", "test_regions_second"]);
+
+    // joining twice is refused
+    let status = node
+        .post_json("/api/v1/regions", json!({ "network_name": "test_regions_second" }))
+        .await;
+    assert_eq!(status, Status::Conflict);
+
+    let status = node
+        .post_json("/api/v1/regions/1/notices", json!({ "title": "Flooding", "body": "Road closed" }))
+        .await;
+    assert_eq!(status, Status::Ok);
+
+    wait_for_notice(&second.nodes[0], "/api/this_region/notices", "Flooding")
+        .await
+        .expect("notice didn't reach the second region");
+
+    // the first region keeps syncing, with the other node in it on the same host
+    let status = first.nodes[1]
+        .post_json("/api/this_region/notices", json!({ "title": "Market day", "body": "Saturday" }))
+        .await;
+    assert_eq!(status, Status::Ok);
+    wait_for_notice(node, "/api/v1/regions/0/notices", "Market day")
+        .await
+        .expect("notice didn't reach the node in the first region");
+
+    // and the regions don't see each other's notices
+    let titles = |notices: Value| -> Vec<String> {
+        notices
+            .as_array()
+            .expect("list of notices")
+            .iter()
+            .map(|notice| notice["title"].as_str().unwrap_or("").to_string())
+            .collect()
+    };
+    assert_eq!(titles(node.get_json("/api/v1/regions/0/notices").await), ["Market day"]);
+    assert_eq!(titles(node.get_json("/api/v1/regions/1/notices").await), ["Flooding"]);
+    assert_eq!(
+        titles(
+            first.nodes[1]
+                .get_json("/api/this_region/notices")
+                .await
+        ),
+        ["Market day"]
+    );
+
+    // each region's node shows up in the metrics on its own
+    let metrics = node
+        .client
+        .get("/metrics")
+        .dispatch()
+        .await
+        .into_string()
+        .await
+        .expect("metrics body");
+    assert!(metrics.contains("lores_container_state{region=\"1\",state=\"running\"}"), "{}", metrics);
+}
+
+#[rocket::async_test]
+async fn leaving_a_second_region_keeps_the_first() {
+    let first = TestRegion::start("test_leave_second_first", 1).await;
+    let second = TestRegion::start("test_leave_second_second", 1).await;
+    let node = &first.nodes[0];
+
+    let other_node_id = second.nodes[0].panda_node_id().await;
+    let status = node
+        .post_json(
+            "/api/v1/regions",
+            json!({ "network_name": "test_leave_second_second", "bootstrap_peer": { "node_id": other_node_id } }),
+        )
+        .await;
+    assert_eq!(status, Status::Ok);
+    assert!(node.region_database_path(1).exists());
+
+    let status = node
+        .post_json("/api/v1/regions/1/leave", json!({ "keep_backup": false }))
+        .await;
+    assert_eq!(status, Status::Ok);
+
+    assert_eq!(region_status(node, 1).await, Status::NotFound);
+    assert!(!node.region_database_path(1).exists());
+    assert_eq!(region_names(node).await, ["test_leave_second_first"]);
+    assert_eq!(
+        node.get_json("/api/v1/this_p2panda_node/status")
+            .await["state"],
+        "running"
+    );
+}
+
+#[rocket::async_test]
+async fn restoring_a_backup_closes_the_regions_it_was_not_in() {
+    let first = TestRegion::start("test_restore_regions_first", 1).await;
+    let second = TestRegion::start("test_restore_regions_second", 1).await;
+    let node = &first.nodes[0];
+
+    let response = node
+        .client
+        .post("/api/admin/backup")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let archive = response
+        .into_bytes()
+        .await
+        .expect("backup archive");
+
+    let other_node_id = second.nodes[0].panda_node_id().await;
+    let status = node
+        .post_json(
+            "/api/v1/regions",
+            json!({ "network_name": "test_restore_regions_second", "bootstrap_peer": { "node_id": other_node_id } }),
+        )
+        .await;
+    assert_eq!(status, Status::Ok);
+    assert!(node.region_database_path(1).exists());
+
+    let response = node
+        .client
+        .post("/api/admin/restore")
+        .header(ContentType::Binary)
+        .body(archive)
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+
+    assert_eq!(region_names(node).await, ["test_restore_regions_first"]);
+    assert_eq!(region_status(node, 1).await, Status::NotFound);
+    assert!(!node.region_database_path(1).exists());
+    assert_eq!(region_status(node, 0).await, Status::Ok);
+}
+
+#[rocket::async_test]
+async fn leaving_the_region_resets_the_node() {
+    let region = TestRegion::start("test_leave_region", 2).await;
+    let (staying, leaving) = (&region.nodes[0], &region.nodes[1]);
+    let leaving_id = leaving.panda_node_id().await;
+    let status = leaving
+        .post_json("/api/v1/this_node/create", json!({ "name": "leaving" }))
+        .await;
+    assert_eq!(status, Status::Ok);
+    staying
+        .wait_for_region_node(&leaving_id, SYNC_TIMEOUT)
+        .await
+        .expect("node didn't reach the region");
+
+    let response = leaving
+        .client
+        .post("/api/v1/this_region/leave")
+        .header(ContentType::JSON)
+        .body(json!({ "keep_backup": true }).to_string())
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let left: Value = response.into_json().await.expect("JSON body");
+    let backup_path = left["backup_path"].as_str().expect("backup_path");
+    assert!(PathBuf::from(backup_path).exists());
+
+    assert_eq!(leaving.get_json("/api/v1/this_region").await, json!(null));
+    assert_eq!(
+        leaving
+            .get_json("/api/v1/this_region/nodes")
+            .await,
+        json!([])
+    );
+    assert_eq!(
+        leaving
+            .get_json("/api/v1/this_p2panda_node/status")
+            .await["state"],
+        "unconfigured"
+    );
+
+    staying
+        .wait_for_json("/api/v1/this_region/nodes", SYNC_TIMEOUT, |nodes| {
+            nodes.as_array().is_some_and(|nodes| {
+                nodes
+                    .iter()
+                    .all(|node| node["id"] != leaving_id.as_str())
+            })
+        })
+        .await
+        .expect("the region still lists the node that left");
+}
@@ -0,0 +1,79 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use rocket::tokio::sync::{mpsc, Mutex};
+use rocket::tokio::time::sleep;
+
+use super::harness::{idle_container, migrated_pool};
+use crate::events::fairing::{spawn_event_handler, EVENT_HANDLER_TASK};
+use crate::infra::supervisor::{Supervisor, TaskState, TaskStatus};
+
+async fn wait_for_status(supervisor: &Supervisor, name: &str, ready: impl Fn(&TaskStatus) -> bool) -> TaskStatus {
+    for _ in 0..100 {
+        let status = supervisor
+            .statuses()
+            .into_iter()
+            .find(|status| status.name == name);
+        if let Some(status) = status.filter(|status| ready(status)) {
+            return status;
+        }
+        sleep(Duration::from_millis(50)).await;
+    }
+
+    panic!("task {} never got there: {:?}", name, supervisor.statuses());
+}
+
+#[rocket::async_test]
+async fn crashed_tasks_are_restarted_and_their_crashes_kept() {
+    let supervisor = Supervisor::with_first_backoff(Duration::from_millis(10));
+    let runs = Arc::new(AtomicU32::new(0));
+
+    let task_runs = runs.clone();
+    supervisor.spawn("flaky", move || {
+        let runs = task_runs.clone();
+
+        async move {
+            match runs.fetch_add(1, Ordering::SeqCst) {
+                0 => panic!("first run"),
+                1 => Err(anyhow!("second run")),
+                _ => {
+                    sleep(Duration::from_secs(60)).await;
+                    Ok(())
+                }
+            }
+        }
+    });
+
+    // backing off 10ms, then 20ms
+    let status = wait_for_status(&supervisor, "flaky", |status| status.crashes == 2 && status.state == TaskState::Running).await;
+
+    assert_eq!(runs.load(Ordering::SeqCst), 3);
+    assert_eq!(status.last_crash.expect("crash").reason, "second run");
+
+    supervisor.stop("flaky");
+    assert_eq!(supervisor.statuses()[0].state, TaskState::Stopped);
+}
+
+#[rocket::async_test]
+async fn finished_tasks_are_not_restarted() {
+    let supervisor = Supervisor::default();
+
+    supervisor.spawn("once", || async { Ok(()) });
+
+    let status = wait_for_status(&supervisor, "once", |status| status.state == TaskState::Stopped).await;
+    assert_eq!(status.crashes, 0);
+}
+
+#[rocket::async_test]
+async fn event_handler_stops_cleanly_when_its_channel_closes() {
+    let supervisor = Supervisor::with_first_backoff(Duration::from_millis(10));
+    let (events_tx, events_rx) = mpsc::channel(1);
+
+    spawn_event_handler(&supervisor, Arc::new(Mutex::new(events_rx)), migrated_pool().await, idle_container());
+    drop(events_tx);
+
+    let status = wait_for_status(&supervisor, EVENT_HANDLER_TASK, |status| status.state == TaskState::Stopped).await;
+    assert_eq!(status.crashes, 0);
+}
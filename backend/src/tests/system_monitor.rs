@@ -0,0 +1,140 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::infra::system_monitor::{parse_cpu_times, record_sample, CpuTimes, SystemAlertKind, SystemSources, SystemThresholds};
+use crate::infra::time::unix_now;
+use crate::repos::system::SystemRepo;
+
+use super::harness::{migrated_pool, TestNode};
+
+fn fixtures() -> SystemSources {
+    let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/tests/fixtures/system");
+
+    SystemSources {
+        proc_path: fixtures.join("proc"),
+        sys_path: fixtures.join("sys"),
+        data_path: fixtures,
+    }
+}
+
+#[test]
+fn system_is_read_from_proc_and_sys() {
+    let reading = fixtures().read().expect("reading");
+
+    assert_eq!(reading.cpu, CpuTimes { busy: 5668, total: 9390 });
+    assert_eq!((reading.memory_total_kib, reading.memory_available_kib), (3884164, 2913123));
+    assert!(reading.disk_total_bytes > 0);
+    assert_eq!(reading.temperature_c, Some(51.5));
+    assert_eq!(reading.uptime_secs, 86423);
+    assert_eq!(reading.load_1m, 0.42);
+
+    // loopback is left out
+    let names: Vec<&str> = reading
+        .interfaces
+        .iter()
+        .map(|interface| interface.name.as_str())
+        .collect();
+    assert_eq!(names, ["eth0", "wlan0"]);
+    assert_eq!(reading.interfaces[0].rx_bytes, 98765432);
+    assert_eq!(reading.interfaces[0].rx_errors, 3);
+    assert_eq!(reading.interfaces[0].tx_bytes, 12345678);
+    assert_eq!(reading.interfaces[0].tx_errors, 1);
+}
+
+#[test]
+fn cpu_usage_is_taken_between_samples() {
+    let earlier = parse_cpu_times("cpu  100 0 100 700 100 0 0 0 0 0").expect("cpu times");
+    let later = parse_cpu_times("cpu  250 0 150 1000 100 0 0 0 0 0").expect("cpu times");
+
+    assert_eq!(later.percent_since(&earlier), Some(40.0));
+    assert_eq!(later.percent_since(&later), None);
+
+    let reading = fixtures().read().expect("reading");
+    let first = reading.sample(None, 0);
+    assert_eq!(first.cpu_percent, None);
+    assert!((first.memory_used_percent - 25.0).abs() < 0.01, "{}", first.memory_used_percent);
+}
+
+#[test]
+fn samples_are_checked_against_thresholds() {
+    let mut sample = fixtures()
+        .read()
+        .expect("reading")
+        .sample(None, 0);
+    sample.temperature_c = Some(82.0);
+    let thresholds = SystemThresholds {
+        cpu_percent: 90.0,
+        memory_percent: 20.0,
+        disk_percent: 100.0,
+        temperature_c: 75.0,
+    };
+
+    let crossed: Vec<SystemAlertKind> = thresholds
+        .check(&sample)
+        .into_iter()
+        .filter(|(_, value, threshold)| value > threshold)
+        .map(|(kind, _, _)| kind)
+        .collect();
+
+    // no CPU usage in a first sample, so no CPU alert either way
+    assert_eq!(crossed, [SystemAlertKind::HighMemory, SystemAlertKind::HighTemperature]);
+}
+
+#[rocket::async_test]
+async fn crossing_a_threshold_raises_an_alert_until_the_value_drops_back() {
+    let pool = migrated_pool().await;
+    let repo = SystemRepo::init();
+    let thresholds = SystemThresholds {
+        cpu_percent: 100.0,
+        memory_percent: 100.0,
+        disk_percent: 100.0,
+        temperature_c: 75.0,
+    };
+    let started_at = unix_now();
+    let mut sample = fixtures()
+        .read()
+        .expect("reading")
+        .sample(None, started_at);
+
+    sample.temperature_c = Some(82.0);
+    record_sample(&pool, &sample, &thresholds)
+        .await
+        .unwrap();
+    sample.sampled_at += 60;
+    sample.temperature_c = Some(84.0);
+    record_sample(&pool, &sample, &thresholds)
+        .await
+        .unwrap();
+
+    let open = repo.open_alerts(&pool).await.unwrap();
+    assert_eq!(open.len(), 1, "still hot, so still one alert");
+    assert_eq!(open[0].kind, "high_temperature");
+    assert_eq!(open[0].value, 82.0);
+    assert_eq!(open[0].threshold, 75.0);
+    assert_eq!(open[0].raised_at, started_at);
+
+    sample.sampled_at += 60;
+    sample.temperature_c = Some(60.0);
+    record_sample(&pool, &sample, &thresholds)
+        .await
+        .unwrap();
+
+    assert!(repo.open_alerts(&pool).await.unwrap().is_empty());
+    let alerts = repo.alerts(&pool, 0).await.unwrap();
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0].resolved_at, Some(started_at + 120));
+    assert_eq!(repo.samples(&pool, 0).await.unwrap().len(), 3);
+}
+
+#[rocket::async_test]
+async fn system_is_sampled_after_start() {
+    let node = TestNode::start().await;
+
+    let status = node
+        .wait_for_json("/api/v1/this_node/system", Duration::from_secs(5), |status| !status["latest"].is_null())
+        .await
+        .expect("a system sample");
+
+    assert_eq!(status["history"].as_array().map(Vec::len), Some(1));
+    assert_eq!(status["thresholds"]["disk_percent"], 90.0);
+}
@@ -6,6 +6,8 @@ import { themeSystem } from "./theme"
 import { EnsureNode } from "./contexts/this_node"
 import { ThisP2PandaNode } from "./contexts/this_p2panda_node"
 import { EnsureRegion, Nodes } from "./contexts/this_region"
+import { Logs } from "./contexts/diagnostics"
+import { Alerts } from "./contexts/alerts"
 
 const router = createBrowserRouter(
   [
@@ -22,6 +24,8 @@ const router = createBrowserRouter(
           ],
         },
         { path: "p2panda_node", element: <ThisP2PandaNode /> },
+        { path: "alerts", element: <Alerts /> },
+        { path: "logs", element: <Logs /> },
       ],
     },
   ],
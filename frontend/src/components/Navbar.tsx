@@ -42,6 +42,16 @@ export default function Navbar() {
                   This Node
                 </Button>
               </Link>
+              <Link to="/alerts">
+                <Button colorPalette="blue" variant="ghost">
+                  Alerts
+                </Button>
+              </Link>
+              <Link to="/logs">
+                <Button colorPalette="blue" variant="ghost">
+                  Logs
+                </Button>
+              </Link>
             </Box>
           </Stack>
 
@@ -0,0 +1,21 @@
+import { BaseApi } from "../shared"
+import { ApiResult } from "../shared/types"
+import { Alert, AlertRule, AlertState } from "./types"
+
+export default class AlertsApi extends BaseApi {
+  alerts(state?: AlertState): Promise<ApiResult<Alert[], any>> {
+    return this.apiCall(state ? `alerts?state=${state}` : "alerts")
+  }
+
+  acknowledge(id: string): Promise<ApiResult<Alert, any>> {
+    return this.apiCall(`alerts/${id}/acknowledge`, "POST")
+  }
+
+  rules(): Promise<ApiResult<AlertRule[], any>> {
+    return this.apiCall("alerts/rules")
+  }
+
+  deleteRule(id: string): Promise<ApiResult<any, any>> {
+    return this.apiCall(`alerts/rules/${id}`, "DELETE")
+  }
+}
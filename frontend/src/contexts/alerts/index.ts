@@ -0,0 +1,3 @@
+export { default as Alerts } from "./pages/Alerts"
+export { default as AlertsApi } from "./api"
+export type { Alert, AlertRule, AlertCondition, AlertState } from "./types"
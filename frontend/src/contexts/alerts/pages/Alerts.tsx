@@ -0,0 +1,146 @@
+import { Badge, Button, Container, Heading, Table, VStack } from "@chakra-ui/react"
+import { useEffect, useState } from "react"
+import AlertsApi from "../api"
+import { Alert, AlertCondition, AlertRule } from "../types"
+import { Loading, useLoading } from "../../shared"
+
+const api = new AlertsApi()
+
+const getAlerts = async (): Promise<Alert[]> => {
+  const result = await api.alerts()
+  if ("Ok" in result && result.Ok) return result.Ok
+  return []
+}
+
+const getRules = async (): Promise<AlertRule[]> => {
+  const result = await api.rules()
+  if ("Ok" in result && result.Ok) return result.Ok
+  return []
+}
+
+const describeCondition = (condition: AlertCondition): string => {
+  switch (condition.kind) {
+    case "node_not_seen":
+      return `Node not seen for ${condition.hours} hours`
+    case "disk_above":
+      return `Disk more than ${condition.percent}% full`
+    case "node_unhealthy":
+      return "Node reports system alerts"
+    case "sync_failing":
+      return `Sync failed ${condition.failures} times in an hour`
+  }
+}
+
+const stateColor = {
+  open: "red",
+  acknowledged: "orange",
+  resolved: "green",
+}
+
+export default function Alerts() {
+  const [alerts, setAlerts] = useState<Alert[]>([])
+  const [rules, setRules] = useState<AlertRule[]>([])
+  const [loading, withLoading] = useLoading(true)
+
+  const fetchAll = async () => {
+    withLoading(async () => {
+      setAlerts(await getAlerts())
+      setRules(await getRules())
+    })
+  }
+
+  useEffect(() => {
+    fetchAll()
+  }, [])
+
+  const acknowledge = async (alert: Alert) => {
+    const result = await api.acknowledge(alert.id)
+    if ("Ok" in result && result.Ok) {
+      const acknowledged = result.Ok
+      setAlerts((alerts) =>
+        alerts.map((a) => (a.id == acknowledged.id ? acknowledged : a)),
+      )
+    }
+  }
+
+  const deleteRule = async (rule: AlertRule) => {
+    const result = await api.deleteRule(rule.id)
+    if ("Ok" in result) {
+      setRules((rules) => rules.filter((r) => r.id != rule.id))
+    }
+  }
+
+  if (loading) return <Loading />
+
+  return (
+    <Container maxWidth={"4xl"}>
+      <VStack alignItems={"stretch"}>
+        <Heading as="h1" size="2xl">
+          Alerts
+        </Heading>
+        <Table.Root variant="line" size="sm">
+          <Table.Header>
+            <Table.Row>
+              <Table.ColumnHeader>Opened</Table.ColumnHeader>
+              <Table.ColumnHeader>State</Table.ColumnHeader>
+              <Table.ColumnHeader>Message</Table.ColumnHeader>
+              <Table.ColumnHeader></Table.ColumnHeader>
+            </Table.Row>
+          </Table.Header>
+          <Table.Body>
+            {alerts.map((alert) => (
+              <Table.Row key={alert.id}>
+                <Table.Cell>
+                  {new Date(alert.opened_at * 1000).toLocaleString()}
+                </Table.Cell>
+                <Table.Cell>
+                  <Badge colorPalette={stateColor[alert.state]}>
+                    {alert.state}
+                  </Badge>
+                </Table.Cell>
+                <Table.Cell>{alert.message}</Table.Cell>
+                <Table.Cell>
+                  {alert.state == "open" && (
+                    <Button size="xs" onClick={() => acknowledge(alert)}>
+                      Acknowledge
+                    </Button>
+                  )}
+                </Table.Cell>
+              </Table.Row>
+            ))}
+          </Table.Body>
+        </Table.Root>
+        <Heading as="h2" size="lg">
+          Rules
+        </Heading>
+        <Table.Root variant="line" size="sm">
+          <Table.Header>
+            <Table.Row>
+              <Table.ColumnHeader>Name</Table.ColumnHeader>
+              <Table.ColumnHeader>Condition</Table.ColumnHeader>
+              <Table.ColumnHeader></Table.ColumnHeader>
+            </Table.Row>
+          </Table.Header>
+          <Table.Body>
+            {rules.map((rule) => (
+              <Table.Row key={rule.id}>
+                <Table.Cell>{rule.name}</Table.Cell>
+                <Table.Cell>{describeCondition(rule.condition)}</Table.Cell>
+                <Table.Cell>
+                  <Button
+                    size="xs"
+                    variant="ghost"
+                    colorPalette="red"
+                    onClick={() => deleteRule(rule)}
+                  >
+                    Delete
+                  </Button>
+                </Table.Cell>
+              </Table.Row>
+            ))}
+          </Table.Body>
+        </Table.Root>
+      </VStack>
+    </Container>
+  )
+}
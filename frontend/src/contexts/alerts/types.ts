@@ -0,0 +1,26 @@
+export type AlertCondition =
+  | { kind: "node_not_seen"; hours: number }
+  | { kind: "disk_above"; percent: number }
+  | { kind: "node_unhealthy" }
+  | { kind: "sync_failing"; failures: number }
+
+export type AlertRule = {
+  id: string
+  name: string
+  condition: AlertCondition
+  author_node_id: string
+  updated_at: number
+}
+
+export type AlertState = "open" | "acknowledged" | "resolved"
+
+export type Alert = {
+  id: string
+  rule_id: string
+  node_id: string
+  state: AlertState
+  message: string
+  opened_at: number
+  acknowledged_at: number | null
+  resolved_at: number | null
+}
@@ -0,0 +1,17 @@
+import { BaseApi } from "../shared"
+import { ApiResult } from "../shared/types"
+import { LogLevel } from "./types"
+
+export default class DiagnosticsApi extends BaseApi {
+  showLogs(level: LogLevel, limit: number): Promise<ApiResult<any, any>> {
+    return this.apiCall(`admin/logs?level=${level}&limit=${limit}`)
+  }
+
+  logStreamUrl(level: LogLevel): string {
+    return `${this.base_url}/admin/logs/stream?level=${level}`
+  }
+
+  diagnosticsUrl(): string {
+    return `${this.base_url}/admin/diagnostics`
+  }
+}
@@ -0,0 +1,3 @@
+export { default as Logs } from "./pages/Logs"
+export { default as DiagnosticsApi } from "./api"
+export type { LogRecord, LogLevel } from "./types"
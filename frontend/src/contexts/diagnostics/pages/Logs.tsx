@@ -0,0 +1,98 @@
+import { HStack, VStack, Text, Table, Link, NativeSelect } from "@chakra-ui/react"
+import { useEffect, useState } from "react"
+import DiagnosticsApi from "../api"
+import { LogLevel, LogRecord } from "../types"
+
+const api = new DiagnosticsApi()
+
+// the node keeps 2000, more than a page can usefully show
+const RECORDS_SHOWN = 500
+
+const getLogs = async (level: LogLevel): Promise<LogRecord[]> => {
+  const result = await api.showLogs(level, RECORDS_SHOWN)
+  if ("Ok" in result) return result.Ok
+  return []
+}
+
+const describeFields = (record: LogRecord): string =>
+  Object.entries(record.fields)
+    .map(([key, value]) => `${key}=${value}`)
+    .join(" ")
+
+export default function Logs() {
+  const [level, setLevel] = useState<LogLevel>("info")
+  const [records, setRecords] = useState<LogRecord[]>([])
+
+  useEffect(() => {
+    let closed = false
+    const stream = new EventSource(api.logStreamUrl(level))
+
+    getLogs(level).then((kept) => {
+      if (closed) return
+      // records logged since the stream opened may already be there
+      setRecords((live) => {
+        const lastKept = kept.length ? kept[kept.length - 1].id : -1
+        return [...kept, ...live.filter((record) => record.id > lastKept)]
+      })
+    })
+
+    stream.onmessage = (message) => {
+      const record: LogRecord = JSON.parse(message.data)
+      setRecords((records) => [...records, record].slice(-RECORDS_SHOWN))
+    }
+
+    return () => {
+      closed = true
+      stream.close()
+      setRecords([])
+    }
+  }, [level])
+
+  return (
+    <VStack alignItems={"stretch"}>
+      <Text textStyle="xl">Logs</Text>
+      <HStack justifyContent={"space-between"}>
+        <NativeSelect.Root maxW={"xs"}>
+          <NativeSelect.Field
+            value={level}
+            onChange={(e) => setLevel(e.currentTarget.value as LogLevel)}
+          >
+            <option value="error">Errors</option>
+            <option value="warn">Warnings and up</option>
+            <option value="info">Info and up</option>
+            <option value="debug">Debug and up</option>
+          </NativeSelect.Field>
+          <NativeSelect.Indicator />
+        </NativeSelect.Root>
+        <Link variant="underline" href={api.diagnosticsUrl()}>
+          Download diagnostics
+        </Link>
+      </HStack>
+      <Table.Root variant="line" size="sm">
+        <Table.Header>
+          <Table.Row>
+            <Table.ColumnHeader>Time</Table.ColumnHeader>
+            <Table.ColumnHeader>Level</Table.ColumnHeader>
+            <Table.ColumnHeader>Message</Table.ColumnHeader>
+          </Table.Row>
+        </Table.Header>
+        <Table.Body>
+          {[...records].reverse().map((record) => (
+            <Table.Row key={record.id}>
+              <Table.Cell>
+                {new Date(record.timestamp * 1000).toLocaleTimeString()}
+              </Table.Cell>
+              <Table.Cell>{record.level}</Table.Cell>
+              <Table.Cell>
+                <Text>{record.message}</Text>
+                <Text textStyle="xs" color="fg.muted">
+                  {record.target} {describeFields(record)}
+                </Text>
+              </Table.Cell>
+            </Table.Row>
+          ))}
+        </Table.Body>
+      </Table.Root>
+    </VStack>
+  )
+}
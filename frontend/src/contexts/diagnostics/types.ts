@@ -0,0 +1,11 @@
+export type LogRecord = {
+  id: number
+  timestamp: number
+  level: "ERROR" | "WARN" | "INFO" | "DEBUG" | "TRACE"
+  target: string
+  spans: string[]
+  message: string
+  fields: Record<string, string>
+}
+
+export type LogLevel = "error" | "warn" | "info" | "debug" | "trace"
@@ -1,4 +1,4 @@
-import { ApiResult, OkResult } from "./types"
+import { ApiErrorBody, ApiResult, OkResult } from "./types"
 
 const headers = {
   "Content-Type": "application/json",
@@ -6,7 +6,15 @@ const headers = {
 }
 
 function getApiHost(): string {
-  return import.meta.env.VITE_API_HOST || "/api"
+  return import.meta.env.VITE_API_HOST || "/api/v1"
+}
+
+async function errorBody(response: Response): Promise<ApiErrorBody | null> {
+  try {
+    return await response.json()
+  } catch {
+    return null
+  }
 }
 
 export default class BaseApi {
@@ -33,13 +41,16 @@ export default class BaseApi {
         return {
           Ok: json,
         }
-      } else {
-        if (response.status == 404) {
-          return {
-            Ok: null,
-          }
+      }
+      const error = await errorBody(response)
+      if (error?.code == "not_found") {
+        return {
+          Ok: null,
         }
       }
+      if (error) {
+        throw new Error(`${error.code}: ${error.message}`)
+      }
       throw new Error(`${response.status}: ${response.statusText}`)
     } catch (error) {
       console.error("Failed to connect to API: ", error)
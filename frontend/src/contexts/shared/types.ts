@@ -7,3 +7,17 @@ export interface OkResult<T> {
 }
 
 export type ApiResult<T, U> = OkResult<T> | ErrorResult<U>
+
+export type ApiErrorCode =
+  | "bad_request"
+  | "not_found"
+  | "validation_failed"
+  | "conflict"
+  | "key_locked"
+  | "network_not_started"
+  | "internal_error"
+
+export interface ApiErrorBody {
+  code: ApiErrorCode
+  message: string
+}
@@ -6,6 +6,10 @@ export default class ThisP2PandaNodeApi extends BaseApi {
     return this.apiCall("this_p2panda_node")
   }
 
+  showStatus(): Promise<ApiResult<any, any>> {
+    return this.apiCall("this_p2panda_node/status")
+  }
+
   restart() {
     return this.apiCall("this_p2panda_node/restart", "POST")
   }
@@ -1,7 +1,7 @@
 import { VStack, Text, Table, Box } from "@chakra-ui/react"
 import { useEffect, useState } from "react"
 import ThisP2PandaNodeApi from "../api"
-import { P2PandaNodeDetails } from "../types"
+import { NodeStatus, P2PandaNodeDetails } from "../types"
 import { Button } from "../../../components"
 
 const api = new ThisP2PandaNodeApi()
@@ -12,11 +12,24 @@ const getNode = async (): Promise<P2PandaNodeDetails | null> => {
   return null
 }
 
+const getStatus = async (): Promise<NodeStatus | null> => {
+  const result = await api.showStatus()
+  if ("Ok" in result) return result.Ok
+  return null
+}
+
+const describeStatus = (status: NodeStatus): string =>
+  status.state == "failed" ? `failed: ${status.reason}` : status.state
+
 export default function ThisP2PandaNode() {
   const [node, setNode] = useState<P2PandaNodeDetails | null>(null)
+  const [status, setStatus] = useState<NodeStatus | null>(null)
 
   const fetchNode = async () => {
-    const node = await getNode()
+    const status = await getStatus()
+    setStatus(status)
+    // the node details are only there while the network is running
+    const node = status?.state == "running" ? await getNode() : null
     console.log("fetched node", node)
     setNode(node)
   }
@@ -26,7 +39,16 @@ export default function ThisP2PandaNode() {
   }, [])
 
   if (!node) {
-    return <></>
+    if (!status) {
+      return <></>
+    }
+
+    return (
+      <VStack alignItems={"stretch"}>
+        <Text textStyle="xl">This P2Panda Node</Text>
+        <Text>Network is {describeStatus(status)}</Text>
+      </VStack>
+    )
   }
 
   const restartNode = () => async () => {
@@ -15,3 +15,7 @@ export type P2PandaNodeDetails = {
 export type BootstrapPeer = {
   node_id: string
 }
+
+export type NodeStatus =
+  | { state: "unconfigured" | "starting" | "running" | "stopping" }
+  | { state: "failed"; reason: string }
@@ -1,18 +1,21 @@
 import { BaseApi } from "../shared"
 import { ApiResult } from "../shared/types"
 import { BootstrapPeer } from "../this_p2panda_node"
-import { NodeDetails } from "../this_node"
-import { RegionDetails } from "./types"
+import { LeftRegion, NodePresence, RegionDetails, RegionNode } from "./types"
 
 export default class ThisRegionApi extends BaseApi {
   show(): Promise<ApiResult<RegionDetails, any>> {
     return this.apiCall("this_region")
   }
 
-  nodes(): Promise<ApiResult<NodeDetails[], any>> {
+  nodes(): Promise<ApiResult<RegionNode[], any>> {
     return this.apiCall("this_region/nodes")
   }
 
+  presence(): Promise<ApiResult<NodePresence[], any>> {
+    return this.apiCall("this_region/presence")
+  }
+
   bootstrap(
     network_name: string,
     bootstrap_peer: BootstrapPeer | null,
@@ -22,4 +25,8 @@ export default class ThisRegionApi extends BaseApi {
       bootstrap_peer,
     })
   }
+
+  leave(keep_backup: boolean): Promise<ApiResult<LeftRegion, any>> {
+    return this.apiCall("this_region/leave", "POST", { keep_backup })
+  }
 }
@@ -1,13 +1,30 @@
-import { Table } from "@chakra-ui/react"
-import { NodeDetails } from "../../this_node"
+import { Badge, HStack, Table } from "@chakra-ui/react"
+import { NodePresence, RegionNode } from "../types"
+
+const describeDisk = (node: RegionNode): string => {
+  const used = node.health?.disk_used_percent
+  return used == null ? "-" : `${Math.round(used)}% used`
+}
+
+export default function NodesList({
+  nodes: nodes,
+  presence,
+}: {
+  nodes: RegionNode[]
+  presence: NodePresence[]
+}) {
+  const isOnline = (node: RegionNode) =>
+    presence.some((p) => p.node_id == node.id && p.online)
 
-export default function NodesList({ nodes: nodes }: { nodes: NodeDetails[] }) {
   return (
     <Table.Root variant="line">
       <Table.Header>
         <Table.Row>
           <Table.ColumnHeader>Name</Table.ColumnHeader>
           <Table.ColumnHeader>Node ID</Table.ColumnHeader>
+          <Table.ColumnHeader>Status</Table.ColumnHeader>
+          <Table.ColumnHeader>Disk</Table.ColumnHeader>
+          <Table.ColumnHeader>Attention</Table.ColumnHeader>
         </Table.Row>
       </Table.Header>
       <Table.Body>
@@ -15,6 +32,17 @@ export default function NodesList({ nodes: nodes }: { nodes: NodeDetails[] }) {
           <Table.Row key={node.id}>
             <Table.Cell>{node.name}</Table.Cell>
             <Table.Cell>{node.id}</Table.Cell>
+            <Table.Cell>{isOnline(node) ? "Online" : "Offline"}</Table.Cell>
+            <Table.Cell>{describeDisk(node)}</Table.Cell>
+            <Table.Cell>
+              <HStack wrap="wrap">
+                {node.attention.map((reason) => (
+                  <Badge key={reason} colorPalette="red">
+                    {reason.replaceAll("_", " ")}
+                  </Badge>
+                ))}
+              </HStack>
+            </Table.Cell>
           </Table.Row>
         ))}
       </Table.Body>
@@ -1,19 +1,26 @@
-import { Container, Heading, VStack } from "@chakra-ui/react"
+import { Box, Container, Heading, VStack } from "@chakra-ui/react"
 import { useContext, useEffect, useState } from "react"
 import { RegionContext } from "../provider_contexts"
 import NodesList from "../components/NodesList"
-import { NodeDetails } from "../../this_node"
 import ThisRegionApi from "../api"
 import { Loading, useLoading } from "../../shared"
+import { Button } from "../../../components"
+import { NodePresence, RegionNode } from "../types"
 
 const api = new ThisRegionApi()
 
-const getNodes = async (): Promise<NodeDetails[] | null> => {
+const getNodes = async (): Promise<RegionNode[] | null> => {
   const result = await api.nodes()
   if ("Ok" in result) return result.Ok
   return null
 }
 
+const getPresence = async (): Promise<NodePresence[]> => {
+  const result = await api.presence()
+  if ("Ok" in result && result.Ok) return result.Ok
+  return []
+}
+
 export default function Nodes() {
   const regionDetails = useContext(RegionContext)
 
@@ -21,7 +28,8 @@ export default function Nodes() {
     return <Container>No region</Container>
   }
 
-  const [nodes, setNodes] = useState<NodeDetails[] | null>(null)
+  const [nodes, setNodes] = useState<RegionNode[] | null>(null)
+  const [presence, setPresence] = useState<NodePresence[]>([])
   const [loading, withLoading] = useLoading(true)
 
   const fetchNodes = async () => {
@@ -29,6 +37,7 @@ export default function Nodes() {
       const result = await getNodes()
       console.log("EFFECT: fetchNodes", result)
       setNodes(result)
+      setPresence(await getPresence())
     })
   }
 
@@ -36,6 +45,22 @@ export default function Nodes() {
     if (nodes == null) fetchNodes()
   }, [])
 
+  const leaveRegion = async () => {
+    const confirmed = window.confirm(
+      `Leave ${regionDetails.network_id}? This node forgets everything it synced from the region. A backup is saved first.`,
+    )
+    if (!confirmed) return
+
+    const result = await api.leave(true)
+    if ("Ok" in result) {
+      console.log("left region, backup at", result.Ok.backup_path)
+      // back to the region setup
+      window.location.reload()
+    } else {
+      console.log("Failed to leave region", result)
+    }
+  }
+
   if (loading) return <Loading />
 
   return (
@@ -47,7 +72,10 @@ export default function Nodes() {
         <Heading as="h2" size="lg">
           Nodes
         </Heading>
-        {nodes && <NodesList nodes={nodes} />}
+        {nodes && <NodesList nodes={nodes} presence={presence} />}
+        <Box mt={4}>
+          <Button onClick={leaveRegion}>Leave Region</Button>
+        </Box>
       </VStack>
     </Container>
   )
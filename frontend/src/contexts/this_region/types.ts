@@ -1,3 +1,33 @@
 export type RegionDetails = {
   network_id: string
 }
+
+export type LeftRegion = {
+  backup_path: string | null
+}
+
+export type NodePresence = {
+  node_id: string
+  online: boolean
+  last_seen_at: number
+  uptime_secs: number
+  version: string
+  load: number | null
+}
+
+export type NodeHealth = {
+  reported_at: number
+  version: string
+  uptime_secs: number | null
+  disk_free_bytes: number | null
+  disk_used_percent: number | null
+  load_1m: number | null
+  open_alerts: string[]
+}
+
+export type RegionNode = {
+  id: string
+  name: string
+  health: NodeHealth | null
+  attention: string[]
+}